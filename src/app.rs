@@ -1,16 +1,27 @@
 use iced::keyboard::{self, key};
 use iced::widget::text_editor::{Binding, KeyPress, Motion};
 use iced::widget::{
-    button, column, container, image as iced_image, rich_text, row, scrollable, span, text,
-    text_editor, Space,
+    button, column, container, image as iced_image, pick_list, rich_text, row, scrollable, span,
+    text, text_editor, Space,
 };
 use iced::{font, time, Color, Element, Font, Length, Subscription, Task, Theme};
 
 use crate::poller::{self, PollEvent, PollerConfig, ReceiptMessage};
-use crate::printer::connection::{self, SharedConnection};
+use crate::printer::backend::PrinterAddress;
+use crate::printer::actor::{Job, JobEvent, JobId, JobPayload, PrinterHandle};
+use crate::printer::connection::JobError;
 use crate::printer::discovery::{self, DiscoveredPrinter};
-use crate::printer::models::{find_known_model, EPSON_VENDOR_ID};
+use crate::printer::encoder::PrintOptions;
+use crate::printer::models::PrintWidth;
+use crate::printer::image_proc::{Dither, Rotation};
+use crate::printer::paper::{PaperEstimate, PaperGeometry};
+use crate::printer::queue::{PrintQueue, QueueState};
+use crate::printer::rich_print::PrintCommand;
+use crate::printer::router::{JobKind, Router, Target};
+use crate::printer::status::{self, PrinterStatus, SharedStatus, StatusChange, StatusReport};
+use crate::printer::usage::{self, RollReport};
 use crate::receipt_markdown::{Alignment, ReceiptBlock};
+use crate::upload_server::handler::LogoRequest;
 use crate::word_wrap::{wrap_document, WrappedLine};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,6 +63,8 @@ pub struct ReceivedMessage {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MessagePrintStatus {
     Printing,
+    /// Still queued; the printer needs attention (paper, cover).
+    Waiting(String),
     Printed,
     Failed(String),
 }
@@ -66,29 +79,160 @@ struct QueuedPrint {
     feed_lines: u8,
     /// Indoor brightness boost for thermal printing.
     bright: bool,
+    /// For a strip frame, the strip it belongs to; its frames all print on
+    /// one printer.
+    strip_session: Option<String>,
+    /// Pre-built command list (from `/print/commands`); printed instead of blocks.
+    commands: Option<Vec<PrintCommand>>,
+    /// Density and speed requested for this job.
+    options: PrintOptions,
+}
+
+impl QueuedPrint {
+    /// What the printer thread prints for this job.
+    fn payload(self) -> JobPayload {
+        match (self.commands, self.image_bytes) {
+            (Some(commands), _) => JobPayload::Commands(commands),
+            (None, Some(image)) if self.no_cut => JobPayload::Strip {
+                image,
+                feed: self.feed_lines,
+                bright: self.bright,
+            },
+            (None, None) if self.no_cut => JobPayload::Log(self.blocks),
+            (None, image) => JobPayload::Message {
+                blocks: self.blocks,
+                image,
+            },
+        }
+    }
+
+    /// What kind of job this is, for routing between printers.
+    fn kind(&self) -> JobKind {
+        if self.message_id > 0 {
+            JobKind::Message
+        } else if self.commands.is_some() {
+            JobKind::Commands
+        } else if !self.no_cut {
+            JobKind::Upload
+        } else if self.image_bytes.is_some() {
+            JobKind::Strip
+        } else {
+            JobKind::Text
+        }
+    }
+
+    /// Paper the job will use, printed as `try_print_next_queued` prints it.
+    fn paper_mm(&self, geometry: PaperGeometry) -> u32 {
+        let mut paper = PaperEstimate::new(geometry);
+        if let Some(commands) = &self.commands {
+            paper.commands(commands);
+        } else if self.no_cut {
+            match &self.image_bytes {
+                Some(bytes) => paper.image(bytes, Rotation::Quarter).feed(self.feed_lines),
+                None => paper.blocks(&self.blocks).feed(3),
+            };
+        } else {
+            paper.blocks(&self.blocks);
+            if let Some(bytes) = &self.image_bytes {
+                paper.feed(2).image(bytes, Rotation::Quarter);
+            }
+            paper.feed(3);
+        }
+        paper.mm()
+    }
+}
+
+/// An open printer, and the queue of jobs routed to it.
+struct Lane {
+    printer: DiscoveredPrinter,
+    // The thread that owns this printer's connection
+    handle: PrinterHandle,
+    queue: PrintQueue<QueuedPrint>,
+    printing: bool,
+    // The job being printed, and how many of its chunks are out
+    current_job: Option<JobId>,
+    job_progress: Option<(usize, usize)>,
+    // Real-time status (None = unknown)
+    status: Option<PrinterStatus>,
+    // Set when the printer can't be reached, until it answers again
+    offline: bool,
+}
+
+impl Lane {
+    fn new(printer: DiscoveredPrinter) -> Self {
+        Self {
+            printer,
+            handle: PrinterHandle::spawn(),
+            queue: PrintQueue::default(),
+            printing: false,
+            current_job: None,
+            job_progress: None,
+            status: None,
+            offline: false,
+        }
+    }
+
+    fn key(&self) -> String {
+        self.printer.key()
+    }
+
+    /// Whether a job routed here now would print.
+    fn ready(&self) -> bool {
+        !self.offline
+            && !self.queue.is_paused()
+            && self.status.as_ref().is_none_or(|s| s.blocking_error().is_none())
+    }
+
+    /// The queue as shown in the UI: empty when there's nothing to say.
+    fn queue_text(&self) -> String {
+        let count = self.queue.len();
+        match self.queue.state() {
+            QueueState::Paused(reason) => format!("Paused: {reason} ({count} queued)"),
+            _ if self.offline && count > 0 => format!("Offline ({count} queued)"),
+            QueueState::Printing => match self.job_progress {
+                Some((sent, total)) if total > 0 => {
+                    format!("Printing {}% ({count} queued)", sent * 100 / total)
+                }
+                _ => format!("Printing ({count} queued)"),
+            },
+            _ if count > 0 => format!("{count} queued"),
+            _ => String::new(),
+        }
+    }
 }
 
 pub struct App {
     content: text_editor::Content,
     parsed_blocks: Vec<ReceiptBlock>,
     wrapped_lines: Vec<WrappedLine>,
+    // Estimated paper length of the editor's receipt
+    paper_mm: u32,
     status: ConnectionStatus,
-    discovered: Vec<DiscoveredPrinter>,
+    // Every printer found, each with its own connection and queue
+    lanes: Vec<Lane>,
+    // The printer the editor, drawer and logos use; its status is mirrored
+    // into `shared_status` for the upload server's `GET /status`
     selected_printer: Option<usize>,
+    // Picks a printer for each queued job from the `[[route]]` config
+    router: Router,
+    // Dithering for jobs that don't ask for one; None leaves it to the
+    // printer's config
+    dither: Option<Dither>,
     platform_warnings: Vec<String>,
     last_result: Option<Result<String, String>>,
-    printing: bool,
     show_help: bool,
     show_messages_panel: bool,
-    // Persistent USB connection — stays open across prints to avoid
-    // macOS kIOReturnExclusiveAccess errors from rapid open/close cycles
-    shared_conn: SharedConnection,
+    shared_status: SharedStatus,
+    // Paper used on the selected printer's roll, also mirrored into
+    // `shared_status`
+    roll: Option<RollReport>,
+    // Output chosen with --dry-run / --output; replaces USB discovery
+    output: Option<PrinterAddress>,
     // Poller state
     poller_config: Option<PollerConfig>,
     poller_enabled: bool,
     poller_status: PollerStatus,
     received_messages: Vec<ReceivedMessage>,
-    print_queue: Vec<QueuedPrint>,
     messages_printed_count: u32,
     // Upload server state
     upload_server_enabled: bool,
@@ -106,8 +250,14 @@ pub enum Message {
     ScanPrinters,
     PrintersFound(Result<Vec<DiscoveredPrinter>, String>),
     SelectPrinter(usize),
+    SelectDither(DitherChoice),
     Print,
-    PrintResult(Result<(), String>),
+    /// Cancel the job printing on the printer with this key.
+    CancelPrint(String),
+    PrintResult {
+        printer: String,
+        result: Result<(), String>,
+    },
     DismissWarning(usize),
     HotplugEvent,
     HealthCheck,
@@ -116,8 +266,9 @@ pub enum Message {
     PollEvent(PollEvent),
     TogglePoller,
     PrintMessageResult {
+        printer: String,
         message_id: i64,
-        result: Result<(), String>,
+        result: Result<(), JobError>,
     },
     MarkResult(Result<(), String>),
     ImageDownloaded {
@@ -125,40 +276,283 @@ pub enum Message {
         result: Result<Vec<u8>, String>,
     },
     ToggleMessagesPanel,
-    ConnectionOpened(Result<(), String>),
+    ConnectionOpened {
+        printer: String,
+        result: Result<(), String>,
+    },
+    PulseDrawer,
+    DrawerPulsed(Result<(), String>),
+    BeepResult(Result<(), String>),
+    StatusPolled {
+        printer: String,
+        result: Result<Option<PrinterStatus>, String>,
+    },
+    /// Pushed by the printer (ASB) the moment a sensor changes.
+    StatusChanged(StatusChange),
+    JobEvent(JobEvent),
     // Upload server messages
     UploadEvent(crate::upload_server::subscription::UploadEvent),
 }
 
+/// An entry in the dithering dropdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherChoice {
+    PrinterDefault,
+    Algorithm(Dither),
+}
+
+impl DitherChoice {
+    fn all() -> Vec<DitherChoice> {
+        std::iter::once(DitherChoice::PrinterDefault)
+            .chain(Dither::ALL.map(DitherChoice::Algorithm))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for DitherChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DitherChoice::PrinterDefault => write!(f, "Printer default"),
+            DitherChoice::Algorithm(dither) => write!(f, "{dither}"),
+        }
+    }
+}
+
+fn selected_lane(app: &App) -> Option<&Lane> {
+    app.selected_printer.and_then(|idx| app.lanes.get(idx))
+}
+
+fn selected_status(app: &App) -> Option<&PrinterStatus> {
+    selected_lane(app).and_then(|lane| lane.status.as_ref())
+}
+
+fn lane_index(app: &App, key: &str) -> Option<usize> {
+    app.lanes.iter().position(|lane| lane.key() == key)
+}
+
+/// Record a printer's status. The selected printer's is mirrored into the
+/// shared report served at `GET /status`.
+fn set_printer_status(app: &mut App, idx: usize, status: Option<PrinterStatus>) {
+    if let Some(lane) = app.lanes.get_mut(idx) {
+        lane.status = status;
+    }
+    if app.selected_printer == Some(idx) {
+        mirror_selected(app);
+    }
+}
+
+/// Point the shared report at the selected printer.
+fn mirror_selected(app: &mut App) {
+    let lane = selected_lane(app);
+    let printer = lane.map(|lane| &lane.printer);
+    let report = StatusReport {
+        printer: printer.map(|p| p.model_name.clone()),
+        geometry: printer.map(PaperGeometry::for_printer),
+        roll: printer.map(RollReport::for_printer),
+        status: lane.and_then(|lane| lane.status.clone()),
+    };
+    app.roll = report.roll.clone();
+    if let Ok(mut shared) = app.shared_status.lock() {
+        *shared = report;
+    }
+}
+
+/// Re-read the selected printer's roll usage after a job, or after a reset
+/// from the admin page.
+fn refresh_roll(app: &mut App) {
+    app.roll = selected_lane(app).map(|lane| RollReport::for_printer(&lane.printer));
+    if let Ok(mut report) = app.shared_status.lock() {
+        report.roll = app.roll.clone();
+    }
+}
+
+/// Banner text for a printer that needs attention.
+fn status_banner(status: &PrinterStatus) -> Option<&'static str> {
+    if status.paper_out {
+        Some("Paper out — load a new roll")
+    } else if status.cover_open {
+        Some("Cover open — close the printer cover")
+    } else if status.error {
+        Some("Printer error — check for a paper jam")
+    } else if !status.online {
+        Some("Printer offline")
+    } else if status.paper_near_end {
+        Some("Paper low — replace the roll soon")
+    } else {
+        None
+    }
+}
+
+/// Red while the printer can't print, amber when paper is low.
+fn printer_status_color(status: &PrinterStatus) -> Color {
+    if status.blocking_error().is_some() {
+        Color::from_rgb(1.0, 0.23, 0.19)
+    } else if status.paper_near_end {
+        Color::from_rgb(0.85, 0.55, 0.0)
+    } else {
+        Color::from_rgb(0.20, 0.78, 0.35)
+    }
+}
+
 fn current_max_chars(app: &App) -> u8 {
-    app.selected_printer
-        .and_then(|idx| app.discovered.get(idx))
-        .and_then(|p| find_known_model(EPSON_VENDOR_ID, p.product_id))
-        .map(|m| m.max_chars_per_line)
-        .unwrap_or(42)
+    selected_lane(app)
+        .map(|lane| lane.printer.print_width().chars)
+        .unwrap_or(PrintWidth::MM_80.chars)
 }
 
 /// Eagerly open a persistent USB connection in the background.
-/// Called when a printer is first discovered or when the user selects a different one.
-fn open_connection_async(app: &App, printer_info: &DiscoveredPrinter) -> Task<Message> {
-    let shared = app.shared_conn.clone();
-    let product_id = printer_info.product_id;
-    let model_name = printer_info.model_name.clone();
+/// Called when a printer is discovered, and again on every rescan (an open
+/// connection is kept).
+fn open_connection_async(lane: &Lane) -> Task<Message> {
+    let handle = lane.handle.clone();
+    let printer_info = lane.printer.clone();
+    let printer = lane.key();
     Task::perform(
-        async move { connection::open_shared(&shared, product_id, model_name) },
-        Message::ConnectionOpened,
+        async move { handle.open(&printer_info).await },
+        move |result| Message::ConnectionOpened {
+            printer: printer.clone(),
+            result,
+        },
     )
 }
 
+/// Send a job to a printer thread as the one in progress, and wait for it.
+fn start_job(lane: &mut Lane, job: Job) -> impl std::future::Future<Output = Result<String, JobError>> {
+    lane.printing = true;
+    lane.current_job = Some(job.id);
+    lane.job_progress = None;
+    let handle = lane.handle.clone();
+    async move { handle.run(job).await }
+}
+
+/// Keep a lane for each printer found. Printers still there keep their
+/// connection and queue; jobs waiting on one that's gone are routed again.
+fn sync_lanes(app: &mut App, printers: Vec<DiscoveredPrinter>) -> Task<Message> {
+    let selected = selected_lane(app).map(Lane::key);
+    let mut old = std::mem::take(&mut app.lanes);
+    for printer in printers {
+        let lane = match old.iter().position(|lane| lane.key() == printer.key()) {
+            Some(i) => {
+                let mut lane = old.remove(i);
+                lane.printer = printer;
+                lane
+            }
+            None => {
+                tracing::info!("Printer added: {} ({})", printer.model_name, printer.key());
+                Lane::new(printer)
+            }
+        };
+        app.lanes.push(lane);
+    }
+    app.selected_printer = selected
+        .and_then(|key| lane_index(app, &key))
+        .or(if app.lanes.is_empty() { None } else { Some(0) });
+
+    let mut orphaned = Vec::new();
+    for mut lane in old {
+        tracing::info!("Printer removed: {}", lane.key());
+        // Its thread stops once the last handle is dropped
+        lane.handle.close();
+        orphaned.extend(lane.queue.take_waiting());
+    }
+    let mut tasks: Vec<Task<Message>> = app.lanes.iter().map(open_connection_async).collect();
+    for job in orphaned {
+        tasks.push(enqueue(app, job, true));
+    }
+    Task::batch(tasks)
+}
+
+/// The printer `job` goes to, never `exclude`.
+fn route(app: &mut App, job: &QueuedPrint, exclude: Option<usize>) -> Option<usize> {
+    let targets: Vec<Target<'_>> = app
+        .lanes
+        .iter()
+        .enumerate()
+        .map(|(i, lane)| Target {
+            printer: &lane.printer,
+            ready: Some(i) != exclude && lane.ready(),
+            load: lane.queue.len(),
+        })
+        .collect();
+    let idx = match &job.strip_session {
+        Some(session) => app.router.route_strip(session, &targets)?,
+        None => app.router.route(job.kind(), &targets)?,
+    };
+    (Some(idx) != exclude).then_some(idx)
+}
+
+/// Queue a job on the printer its route picks, starting it if `start` and
+/// that printer is idle.
+fn enqueue(app: &mut App, job: QueuedPrint, start: bool) -> Task<Message> {
+    let Some(idx) = route(app, &job, None) else {
+        // No printer — mark as failed
+        set_message_status(app, job.message_id, MessagePrintStatus::Failed("No printer".into()));
+        return Task::none();
+    };
+    app.lanes[idx].queue.push(job);
+    if start {
+        try_print_next_queued(app, idx)
+    } else {
+        Task::none()
+    }
+}
+
+/// Move the jobs waiting on a printer that can't print to printers that
+/// can, keeping any that no other printer can take.
+fn failover(app: &mut App, from: usize) -> Task<Message> {
+    let paused = app.lanes[from].queue.state().clone();
+    let mut moved_to = Vec::new();
+    for job in app.lanes[from].queue.take_waiting() {
+        let to = route(app, &job, Some(from)).filter(|&to| app.lanes[to].ready());
+        match to {
+            Some(to) => {
+                tracing::info!(
+                    "Moving job {} from {} to {}",
+                    job.message_id,
+                    app.lanes[from].key(),
+                    app.lanes[to].key()
+                );
+                set_message_status(app, job.message_id, MessagePrintStatus::Printing);
+                app.lanes[to].queue.push(job);
+                moved_to.push(to);
+            }
+            None => app.lanes[from].queue.push(job),
+        }
+    }
+    if let QueueState::Paused(reason) = paused {
+        if !app.lanes[from].queue.is_empty() {
+            app.lanes[from].queue.pause(reason);
+        }
+    }
+    moved_to.dedup();
+    let tasks: Vec<Task<Message>> = moved_to
+        .into_iter()
+        .map(|to| try_print_next_queued(app, to))
+        .collect();
+    Task::batch(tasks)
+}
+
+fn set_message_status(app: &mut App, message_id: i64, status: MessagePrintStatus) {
+    if let Some(rm) = app.received_messages.iter_mut().find(|m| m.id == message_id) {
+        rm.status = status;
+    }
+}
+
 fn reparse(app: &mut App) {
     let input = app.content.text();
     app.parsed_blocks = crate::receipt_markdown::parse_receipt_markdown(&input);
     let max_chars = current_max_chars(app);
     app.wrapped_lines = wrap_document(&app.parsed_blocks, max_chars);
+
+    // Print adds three feed lines before the cut
+    let geometry = selected_lane(app)
+        .map(|lane| PaperGeometry::for_printer(&lane.printer))
+        .unwrap_or_default();
+    app.paper_mm = PaperEstimate::new(geometry).blocks(&app.parsed_blocks).feed(3).mm();
 }
 
 impl App {
-    pub fn new(mode: DisplayMode) -> Self {
+    pub fn new(mode: DisplayMode, output: Option<PrinterAddress>) -> Self {
         let poller_config = poller::config::load_config().ok();
         let poller_enabled = poller_config.is_some();
         let poller_status = if poller_config.is_some() {
@@ -177,20 +571,23 @@ impl App {
             content: text_editor::Content::new(),
             parsed_blocks: Vec::new(),
             wrapped_lines: Vec::new(),
+            paper_mm: 0,
             status: ConnectionStatus::Scanning,
-            discovered: Vec::new(),
+            lanes: Vec::new(),
             selected_printer: None,
+            router: Router::from_config(),
+            dither: None,
             platform_warnings: crate::platform::check_prerequisites(),
             last_result: None,
-            printing: false,
             show_help: false,
             show_messages_panel: false,
-            shared_conn: connection::new_shared(),
+            shared_status: status::new_shared_status(),
+            roll: None,
+            output,
             poller_config,
             poller_enabled,
             poller_status,
             received_messages: Vec::new(),
-            print_queue: Vec::new(),
             messages_printed_count: 0,
             upload_server_enabled: true,
             upload_photo_count: 0,
@@ -203,7 +600,7 @@ impl App {
 
 impl Default for App {
     fn default() -> Self {
-        Self::new(DisplayMode::Desktop)
+        Self::new(DisplayMode::Desktop, None)
     }
 }
 
@@ -221,55 +618,44 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
 
         Message::ScanPrinters => {
             app.status = ConnectionStatus::Scanning;
+            let output = app.output.clone();
             Task::perform(
-                async { discovery::scan_for_printers() },
+                async move { discovery::scan_or_output(output.as_ref()) },
                 Message::PrintersFound,
             )
         }
 
         Message::PrintersFound(result) => {
-            match result {
+            let task = match result {
                 Ok(printers) => {
-                    if printers.is_empty() {
-                        app.status = ConnectionStatus::Disconnected;
-                        app.selected_printer = None;
-                        // No printers — close any stale connection
-                        connection::close_shared(&app.shared_conn);
-                    } else {
-                        let first = &printers[0];
-                        app.status = ConnectionStatus::Connected {
-                            model: first.model_name.clone(),
-                            serial: first.serial.clone(),
-                        };
-                        app.selected_printer = Some(0);
-                    }
-                    app.discovered = printers;
+                    let task = sync_lanes(app, printers);
+                    app.status = match selected_lane(app) {
+                        Some(lane) => ConnectionStatus::Connected {
+                            model: lane.printer.model_name.clone(),
+                            serial: lane.printer.serial.clone(),
+                        },
+                        None => ConnectionStatus::Disconnected,
+                    };
+                    task
                 }
                 Err(e) => {
                     app.status = ConnectionStatus::Error(e);
-                    app.discovered.clear();
-                    app.selected_printer = None;
-                    connection::close_shared(&app.shared_conn);
+                    sync_lanes(app, Vec::new())
                 }
-            }
+            };
+            mirror_selected(app);
             reparse(app);
             if app.display_mode == DisplayMode::Kiosk && !app.kiosk_display_blocks.is_empty() {
                 let max_chars = current_max_chars(app);
                 app.kiosk_display_lines = wrap_document(&app.kiosk_display_blocks, max_chars);
             }
-
-            // Eagerly open USB connection to the selected printer
-            if let Some(idx) = app.selected_printer {
-                if let Some(printer_info) = app.discovered.get(idx) {
-                    return open_connection_async(app, printer_info);
-                }
-            }
-            Task::none()
+            task
         }
 
         Message::SelectPrinter(idx) => {
-            if let Some(printer) = app.discovered.get(idx).cloned() {
+            if let Some(printer) = app.lanes.get(idx).map(|lane| lane.printer.clone()) {
                 app.selected_printer = Some(idx);
+                mirror_selected(app);
                 app.status = ConnectionStatus::Connected {
                     model: printer.model_name.clone(),
                     serial: printer.serial.clone(),
@@ -279,20 +665,22 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                     let max_chars = current_max_chars(app);
                     app.kiosk_display_lines = wrap_document(&app.kiosk_display_blocks, max_chars);
                 }
-                // Open connection to newly selected printer (closes old if different)
-                return open_connection_async(app, &printer);
+                return open_connection_async(&app.lanes[idx]);
             }
             reparse(app);
             Task::none()
         }
 
         Message::HotplugEvent => {
-            // Close the persistent connection — the device may have been
-            // unplugged. The PrintersFound handler will reopen if still present.
-            connection::close_shared(&app.shared_conn);
+            // Close the persistent connections — a device may have been
+            // unplugged. The PrintersFound handler reopens those still present.
+            for lane in &app.lanes {
+                lane.handle.close();
+            }
             app.status = ConnectionStatus::Scanning;
+            let output = app.output.clone();
             Task::perform(
-                async { discovery::scan_for_printers() },
+                async move { discovery::scan_or_output(output.as_ref()) },
                 Message::PrintersFound,
             )
         }
@@ -307,36 +695,97 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                 app.last_result = Some(Err("No printer selected".into()));
                 return Task::none();
             };
-            let Some(printer_info) = app.discovered.get(idx).cloned() else {
+            let Some(lane) = app.lanes.get_mut(idx) else {
                 app.last_result = Some(Err("Printer not found".into()));
                 return Task::none();
             };
-
-            app.printing = true;
+            let printer_info = lane.printer.clone();
+            let printer = lane.key();
+
+            let job = Job::new(
+                JobPayload::Receipt(app.parsed_blocks.clone()),
+                PrintOptions {
+                    dither: app.dither,
+                    ..PrintOptions::default()
+                },
+            );
+            let print = start_job(lane, job);
             app.last_result = None;
 
-            let blocks = app.parsed_blocks.clone();
-            let max_chars = current_max_chars(app);
-            let shared = app.shared_conn.clone();
-
+            let paper_mm = app.paper_mm;
             Task::perform(
                 async move {
-                    connection::print_with_shared(
-                        &shared,
-                        printer_info.product_id,
-                        printer_info.model_name.clone(),
-                        |conn| conn.print_rich(&blocks, max_chars),
-                    )
+                    let result = print.await;
+                    if result.is_ok() {
+                        usage::record(&printer_info, paper_mm);
+                    }
+                    result.map(|_| ()).map_err(|e| e.to_string())
+                },
+                move |result| Message::PrintResult {
+                    printer: printer.clone(),
+                    result,
                 },
-                Message::PrintResult,
             )
         }
 
-        Message::PrintResult(result) => {
-            app.printing = false;
+        Message::CancelPrint(printer) => {
+            let lane = lane_index(app, &printer).map(|idx| &app.lanes[idx]);
+            if let Some((lane, id)) = lane.and_then(|lane| Some((lane, lane.current_job?))) {
+                lane.handle.cancel(id);
+            }
+            Task::none()
+        }
+
+        Message::SelectDither(choice) => {
+            app.dither = match choice {
+                DitherChoice::PrinterDefault => None,
+                DitherChoice::Algorithm(dither) => Some(dither),
+            };
+            Task::none()
+        }
+
+        Message::PulseDrawer => {
+            let Some(lane) = selected_lane(app) else {
+                app.last_result = Some(Err("No printer selected".into()));
+                return Task::none();
+            };
+            let handle = lane.handle.clone();
+            let job = Job::new(
+                JobPayload::Commands(vec![PrintCommand::open_drawer()]),
+                PrintOptions::default(),
+            );
+            Task::perform(
+                async move { handle.run(job).await.map(|_| ()).map_err(|e| e.to_string()) },
+                Message::DrawerPulsed,
+            )
+        }
+
+        Message::DrawerPulsed(result) => {
+            app.last_result = Some(result.map(|_| "Drawer opened".into()));
+            Task::none()
+        }
+
+        Message::BeepResult(result) => {
+            if let Err(e) = result {
+                tracing::warn!("Beep failed: {e}");
+            }
+            Task::none()
+        }
+
+        Message::PrintResult { printer, result } => {
+            let idx = lane_index(app, &printer);
+            if let Some(lane) = idx.map(|idx| &mut app.lanes[idx]) {
+                lane.printing = false;
+                lane.current_job = None;
+                lane.job_progress = None;
+            }
+            refresh_roll(app);
             app.last_result = Some(result.map(|_| "Printed successfully".into()));
             // Check print queue for pending polled messages
-            try_print_next_queued(app)
+            match idx {
+                Some(idx) => try_print_next_queued(app, idx),
+                None => Task::none(),
+            }
         }
 
         Message::DismissWarning(idx) => {
@@ -351,10 +800,69 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
             Task::none()
         }
 
-        Message::HealthCheck => Task::perform(
-            async { discovery::scan_for_printers() },
-            Message::PrintersFound,
-        ),
+        Message::HealthCheck => {
+            let output = app.output.clone();
+            let mut tasks = vec![Task::perform(
+                async move { discovery::scan_or_output(output.as_ref()) },
+                Message::PrintersFound,
+            )];
+            for lane in &app.lanes {
+                let handle = lane.handle.clone();
+                let printer = lane.key();
+                tasks.push(Task::perform(
+                    async move { handle.poll_status().await },
+                    move |result| Message::StatusPolled {
+                        printer: printer.clone(),
+                        result,
+                    },
+                ));
+            }
+            Task::batch(tasks)
+        }
+
+        Message::StatusChanged(change) => {
+            let changed: Vec<usize> = (0..app.lanes.len())
+                .filter(|&idx| app.lanes[idx].printer.key() == change.key)
+                .collect();
+            let mut tasks = Vec::new();
+            for idx in changed {
+                set_printer_status(app, idx, Some(change.status.clone()));
+                tasks.push(resume_if_ready(app, idx));
+            }
+            Task::batch(tasks)
+        }
+
+        Message::JobEvent(JobEvent::Progress { id, sent, total }) => {
+            if let Some(lane) = app.lanes.iter_mut().find(|lane| lane.current_job == Some(id)) {
+                lane.job_progress = Some((sent, total));
+            }
+            Task::none()
+        }
+        // Outcomes come back through the task that started the job
+        Message::JobEvent(_) => Task::none(),
+
+        Message::StatusPolled { printer, result } => {
+            let Some(idx) = lane_index(app, &printer) else {
+                return Task::none();
+            };
+            match result {
+                Ok(Some(status)) => {
+                    if app.lanes[idx].status.as_ref() != Some(&status) {
+                        tracing::info!("Printer {printer} status: {}", status.summary());
+                    }
+                    app.lanes[idx].offline = false;
+                    set_printer_status(app, idx, Some(status));
+                }
+                // Busy printing or no read-back — keep the last known status
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Status poll failed for {printer}: {e}");
+                    app.lanes[idx].offline = true;
+                    set_printer_status(app, idx, None);
+                }
+            }
+            resume_if_ready(app, idx)
+        }
 
         // --- Poller messages ---
         Message::PollEvent(event) => match event {
@@ -384,8 +892,54 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
             Task::none()
         }
 
-        Message::PrintMessageResult { message_id, result } => {
-            app.printing = false;
+        Message::PrintMessageResult {
+            printer,
+            message_id,
+            result,
+        } => {
+            let idx = lane_index(app, &printer);
+            if let Some(lane) = idx.map(|idx| &mut app.lanes[idx]) {
+                lane.printing = false;
+                lane.current_job = None;
+                lane.job_progress = None;
+            }
+
+            // Out of paper or cover open: keep the job at the head of the
+            // queue and wait for the printer, without failing the message —
+            // unless another printer can take it
+            if let (Err(JobError::NotReady(status)), Some(idx)) = (&result, idx) {
+                let reason = status
+                    .blocking_error()
+                    .map_or_else(|| status.summary().to_string(), |e| e.to_string());
+                tracing::warn!("Queue on {printer} paused for message_id={message_id}: {reason}");
+                set_message_status(app, message_id, MessagePrintStatus::Waiting(reason.clone()));
+                app.lanes[idx].queue.pause(reason);
+                set_printer_status(app, idx, Some(status.clone()));
+                return failover(app, idx);
+            }
+            let done = idx.and_then(|idx| app.lanes[idx].queue.finish());
+            refresh_roll(app);
+
+            // The printer stopped answering: try the job on another one that's
+            // ready, and move the rest of the queue along with it
+            let mut failover_task = Task::none();
+            if let (Err(JobError::Failed(e)), Some(idx)) = (&result, idx) {
+                app.lanes[idx].offline = true;
+                let retry = done.and_then(|job| {
+                    let to = route(app, &job, Some(idx)).filter(|&to| app.lanes[to].ready())?;
+                    Some((job, to))
+                });
+                if let Some((job, to)) = retry {
+                    tracing::warn!(
+                        "Print failed on {printer} for message_id={message_id}: {e} — retrying on {}",
+                        app.lanes[to].key()
+                    );
+                    app.lanes[to].queue.push(job);
+                    let moved = failover(app, idx);
+                    return Task::batch([try_print_next_queued(app, to), moved]);
+                }
+                failover_task = failover(app, idx);
+            }
 
             match &result {
                 Ok(()) => tracing::info!("Print completed for message_id={message_id}"),
@@ -404,7 +958,7 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                         app.messages_printed_count += 1;
                     }
                     Err(e) => {
-                        rm.status = MessagePrintStatus::Failed(e.clone());
+                        rm.status = MessagePrintStatus::Failed(e.to_string());
                     }
                 }
             }
@@ -431,10 +985,26 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                 Task::none()
             };
 
+            // Kiosk: let the room know a website message came out
+            let beep_task = match idx {
+                Some(idx)
+                    if result.is_ok()
+                        && message_id > 0
+                        && app.display_mode == DisplayMode::Kiosk
+                        && crate::config::file_config().kiosk.beep_on_message =>
+                {
+                    beep(&app.lanes[idx])
+                }
+                _ => Task::none(),
+            };
+
             // Try to print next queued message
-            let next_task = try_print_next_queued(app);
+            let next_task = match idx {
+                Some(idx) => try_print_next_queued(app, idx),
+                None => Task::none(),
+            };
 
-            Task::batch([mark_task, next_task])
+            Task::batch([mark_task, beep_task, next_task, failover_task])
         }
 
         Message::MarkResult(result) => {
@@ -462,8 +1032,9 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                     }
                     // Update the queued print job
                     if let Some(job) = app
-                        .print_queue
+                        .lanes
                         .iter_mut()
+                        .flat_map(|lane| lane.queue.iter_mut())
                         .find(|j| j.message_id == message_id)
                     {
                         job.image_bytes = Some(bytes);
@@ -473,11 +1044,14 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                     tracing::warn!("Image download failed for message {}: {e}", message_id);
                 }
             }
-            // Start printing if not busy
-            if !app.printing {
-                try_print_next_queued(app)
-            } else {
-                Task::none()
+            // Start printing on its printer if that's idle
+            let queued_on = app
+                .lanes
+                .iter()
+                .position(|lane| lane.queue.iter().any(|j| j.message_id == message_id));
+            match queued_on {
+                Some(idx) => try_print_next_queued(app, idx),
+                None => Task::none(),
             }
         }
 
@@ -486,17 +1060,30 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
             Task::none()
         }
 
-        Message::ConnectionOpened(result) => {
+        Message::ConnectionOpened { printer, result } => {
+            let Some(idx) = lane_index(app, &printer) else {
+                return Task::none();
+            };
+            let lane = &mut app.lanes[idx];
             match result {
                 Ok(()) => {
-                    tracing::info!("Persistent USB connection ready");
+                    if lane.offline {
+                        tracing::info!("Printer {printer} is back online");
+                    }
+                    tracing::debug!("Persistent connection to {printer} ready");
+                    lane.offline = false;
+                    try_print_next_queued(app, idx)
                 }
                 Err(e) => {
-                    tracing::warn!("Failed to open persistent USB connection: {e}");
-                    // Don't set error status — prints will try to open on demand
+                    if !lane.offline {
+                        tracing::warn!("Failed to open persistent connection to {printer}: {e}");
+                    }
+                    // Don't set error status — prints will try to open on
+                    // demand — but route new jobs elsewhere meanwhile
+                    lane.offline = true;
+                    failover(app, idx)
                 }
             }
-            Task::none()
         }
 
         Message::UploadEvent(event) => {
@@ -506,16 +1093,16 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                     tracing::info!("Upload server listening on {addr}");
                     Task::none()
                 }
-                UploadEvent::PhotoReceived(image_bytes) => {
+                UploadEvent::PhotoReceived(image_bytes, options) => {
                     app.upload_photo_count += 1;
                     tracing::info!(
                         "Photo upload #{}: {} bytes",
                         app.upload_photo_count,
                         image_bytes.len()
                     );
-                    handle_photo_upload(app, image_bytes)
+                    handle_photo_upload(app, image_bytes, options)
                 }
-                UploadEvent::StripPhotoReceived(image_bytes, feed_lines, bright) => {
+                UploadEvent::StripPhotoReceived(image_bytes, feed_lines, bright, session, options) => {
                     app.upload_photo_count += 1;
                     tracing::info!(
                         "Strip photo #{}: {} bytes (no cut, feed={}, bright={})",
@@ -524,12 +1111,21 @@ pub fn update(app: &mut App, message: Message) -> Task<Message> {
                         feed_lines,
                         bright,
                     );
-                    handle_strip_photo(app, image_bytes, feed_lines, bright)
+                    handle_strip_photo(app, image_bytes, feed_lines, bright, session, options)
                 }
-                UploadEvent::TextReceived { text, source } => {
+                UploadEvent::TextReceived {
+                    text,
+                    source,
+                    options,
+                } => {
                     tracing::info!("Text print received: {} bytes (source={})", text.len(), source);
-                    handle_text_print(app, text, &source)
+                    handle_text_print(app, text, &source, options)
+                }
+                UploadEvent::CommandsReceived(commands, options) => {
+                    tracing::info!("Command list received: {} commands", commands.len());
+                    handle_commands_print(app, commands, options)
                 }
+                UploadEvent::LogoRequested(request) => handle_logo_request(app, request),
                 UploadEvent::Error(e) => {
                     tracing::error!("Upload server error: {e}");
                     Task::none()
@@ -589,15 +1185,18 @@ fn handle_received_messages(app: &mut App, messages: Vec<ReceiptMessage>) -> Tas
             app.received_messages.remove(0);
         }
 
-        // Queue print job (image_bytes filled later if needed)
-        app.print_queue.push(QueuedPrint {
+        // Print job (image_bytes filled later if needed)
+        let job = QueuedPrint {
             message_id: msg.id,
             blocks,
             image_bytes: None,
             no_cut: false,
             feed_lines: 3,
             bright: false,
-        });
+            strip_session: None,
+            commands: None,
+            options: PrintOptions::default(),
+        };
 
         // Start image download if URL present
         if let (Some(image_url), Some(config)) = (msg.image_url.clone(), app.poller_config.clone())
@@ -612,11 +1211,9 @@ fn handle_received_messages(app: &mut App, messages: Vec<ReceiptMessage>) -> Tas
             ));
         }
 
-        // If no image, start printing immediately
-        if !has_image && !app.printing {
-            let task = try_print_next_queued(app);
-            download_tasks.push(task);
-        }
+        // Queue it on its printer; if no image, start printing immediately
+        let task = enqueue(app, job, !has_image);
+        download_tasks.push(task);
     }
 
     // If there are only image messages and none are printing yet, downloads will trigger printing
@@ -624,138 +1221,188 @@ fn handle_received_messages(app: &mut App, messages: Vec<ReceiptMessage>) -> Tas
 }
 
 /// Handle a photo received via the upload server.
-fn handle_photo_upload(app: &mut App, raw_bytes: Vec<u8>) -> Task<Message> {
+fn handle_photo_upload(app: &mut App, raw_bytes: Vec<u8>, options: PrintOptions) -> Task<Message> {
     let blocks = vec![];
 
     let message_id = -(app.upload_photo_count as i64);
 
-    app.print_queue.push(QueuedPrint {
+    let job = QueuedPrint {
         message_id,
         blocks,
         image_bytes: Some(raw_bytes),
         no_cut: false,
         feed_lines: 3,
         bright: false,
-    });
-
-    if !app.printing {
-        try_print_next_queued(app)
-    } else {
-        Task::none()
-    }
+        strip_session: None,
+        commands: None,
+        options,
+    };
+    enqueue(app, job, true)
 }
 
 /// Handle a strip photo (no cut) — for photo booth sequences.
-fn handle_strip_photo(app: &mut App, raw_bytes: Vec<u8>, feed_lines: u8, bright: bool) -> Task<Message> {
+fn handle_strip_photo(
+    app: &mut App,
+    raw_bytes: Vec<u8>,
+    feed_lines: u8,
+    bright: bool,
+    strip_session: Option<String>,
+    options: PrintOptions,
+) -> Task<Message> {
     let message_id = -(app.upload_photo_count as i64);
 
-    app.print_queue.push(QueuedPrint {
+    let job = QueuedPrint {
         message_id,
         blocks: vec![],
         image_bytes: Some(raw_bytes),
         no_cut: true,
         feed_lines,
         bright,
-    });
-
-    if !app.printing {
-        try_print_next_queued(app)
-    } else {
-        Task::none()
-    }
+        strip_session,
+        commands: None,
+        options,
+    };
+    enqueue(app, job, true)
 }
 
 /// Handle text received via the /print/text endpoint.
 /// Prints as a continuous log — no header, no paper cut, just content + spacing.
-fn handle_text_print(app: &mut App, text: String, _source: &str) -> Task<Message> {
-    let mut blocks = crate::receipt_markdown::parse_receipt_markdown(&text);
+fn handle_text_print(
+    app: &mut App,
+    text: String,
+    _source: &str,
+    options: PrintOptions,
+) -> Task<Message> {
+    let mut blocks = crate::receipt_markdown::parse_log_markdown(&text);
     blocks.push(ReceiptBlock::BlankLine);
     blocks.push(ReceiptBlock::BlankLine);
     blocks.push(ReceiptBlock::BlankLine);
 
     let message_id = -(app.upload_photo_count as i64 + 10000);
 
-    app.print_queue.push(QueuedPrint {
+    let job = QueuedPrint {
         message_id,
         blocks,
         image_bytes: None,
         no_cut: true,
         feed_lines: 3,
         bright: false,
-    });
-
-    if !app.printing {
-        try_print_next_queued(app)
-    } else {
-        Task::none()
-    }
+        strip_session: None,
+        commands: None,
+        options,
+    };
+    enqueue(app, job, true)
 }
 
-/// Pop the next queued print job and start it.
-fn try_print_next_queued(app: &mut App) -> Task<Message> {
-    if app.printing {
-        return Task::none();
-    }
+/// Handle a command list received via the /print/commands endpoint.
+fn handle_commands_print(
+    app: &mut App,
+    commands: Vec<PrintCommand>,
+    options: PrintOptions,
+) -> Task<Message> {
+    let message_id = -(app.upload_photo_count as i64 + 20000);
 
-    let Some(job) = app.print_queue.first().cloned() else {
+    let job = QueuedPrint {
+        message_id,
+        blocks: vec![],
+        image_bytes: None,
+        no_cut: true,
+        feed_lines: 0,
+        bright: false,
+        strip_session: None,
+        commands: Some(commands),
+        options,
+    };
+    enqueue(app, job, true)
+}
+
+/// Run an NV logo operation from the admin endpoints on the selected printer
+/// and send the outcome back to the waiting request.
+fn handle_logo_request(app: &App, request: LogoRequest) -> Task<Message> {
+    let Some(lane) = selected_lane(app) else {
+        request.respond(Err("No printer selected".into()));
         return Task::none();
     };
-    app.print_queue.remove(0);
+    let handle = lane.handle.clone();
+    let job = Job::new(JobPayload::Logo(request.op.clone()), PrintOptions::default());
+    Task::future(async move {
+        let result = handle.run(job).await;
+        request.respond(result.map_err(|e| e.to_string()));
+    })
+    .discard()
+}
 
-    let Some(idx) = app.selected_printer else {
-        // No printer — mark as failed
-        if let Some(rm) = app
-            .received_messages
-            .iter_mut()
-            .find(|m| m.id == job.message_id)
-        {
-            rm.status = MessagePrintStatus::Failed("No printer".into());
+/// Sound a printer's buzzer once.
+fn beep(lane: &Lane) -> Task<Message> {
+    let handle = lane.handle.clone();
+    let job = Job::new(JobPayload::Commands(vec![PrintCommand::beep()]), PrintOptions::default());
+    Task::perform(
+        async move { handle.run(job).await.map(|_| ()).map_err(|e| e.to_string()) },
+        Message::BeepResult,
+    )
+}
+
+/// Restart a printer's paused queue once it reports ready again, or move
+/// its jobs to another printer that is.
+fn resume_if_ready(app: &mut App, idx: usize) -> Task<Message> {
+    let lane = &mut app.lanes[idx];
+    let resumed = lane.status.as_ref().is_some_and(|status| lane.queue.update_status(status));
+    if !resumed {
+        return if lane.queue.is_paused() {
+            failover(app, idx)
+        } else {
+            Task::none()
+        };
+    }
+    tracing::info!("Printer {} ready — resuming print queue", lane.key());
+    let waiting: Vec<i64> = lane.queue.iter().map(|job| job.message_id).collect();
+    for rm in app.received_messages.iter_mut() {
+        if matches!(rm.status, MessagePrintStatus::Waiting(_)) && waiting.contains(&rm.id) {
+            rm.status = MessagePrintStatus::Printing;
         }
+    }
+    try_print_next_queued(app, idx)
+}
+
+/// Start the job at the head of a printer's queue. It stays queued until
+/// its result comes back.
+fn try_print_next_queued(app: &mut App, idx: usize) -> Task<Message> {
+    // The dithering picked in the GUI, unless the job asked for one
+    let defaults = PrintOptions {
+        dither: app.dither,
+        ..PrintOptions::default()
+    };
+    let Some(lane) = app.lanes.get_mut(idx) else {
         return Task::none();
     };
-    let Some(printer_info) = app.discovered.get(idx).cloned() else {
+    if lane.printing {
+        return Task::none();
+    }
+
+    let Some(job) = lane.queue.start() else {
         return Task::none();
     };
 
-    app.printing = true;
-    let max_chars = current_max_chars(app);
+    let printer_info = lane.printer.clone();
+    let printer = lane.key();
+    let paper_mm = job.paper_mm(PaperGeometry::for_printer(&printer_info));
     let message_id = job.message_id;
-    let blocks = job.blocks;
-    let image_bytes = job.image_bytes;
-    let no_cut = job.no_cut;
-    let feed_lines = job.feed_lines;
-    let bright = job.bright;
-    let shared = app.shared_conn.clone();
+    let options = job.options.or(defaults);
+    let print = start_job(lane, Job::new(job.payload(), options));
 
     Task::perform(
         async move {
-            let result = connection::print_with_shared(
-                &shared,
-                printer_info.product_id,
-                printer_info.model_name.clone(),
-                |conn| {
-                    if no_cut {
-                        if image_bytes.is_some() {
-                            conn.print_image_no_cut(image_bytes.as_deref().unwrap(), feed_lines, bright)
-                        } else {
-                            conn.print_no_cut(&blocks, max_chars)
-                        }
-                    } else {
-                        conn.print_website_message(&blocks, max_chars, image_bytes.as_deref())
-                    }
-                },
-            );
-
-            // Give the printer time to physically finish before the next job.
-            // Without this delay, rapid successive prints cause USB disconnects
-            // because the printer resets its USB bus while still processing raster data.
+            let result = print.await.map(|_| ());
             if result.is_ok() {
-                tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                usage::record(&printer_info, paper_mm);
             }
-
             result
         },
-        move |result| Message::PrintMessageResult { message_id, result },
+        move |result| Message::PrintMessageResult {
+            printer: printer.clone(),
+            message_id,
+            result,
+        },
     )
 }
 
@@ -772,9 +1419,13 @@ fn kiosk_view(app: &App) -> Element<'_, Message> {
         let mut idle: Vec<Element<'_, Message>> = Vec::new();
 
         let (status_text, status_color) = match &app.status {
-            ConnectionStatus::Connected { model, .. } => {
-                (model.clone(), Color::from_rgb(0.20, 0.78, 0.35))
-            }
+            ConnectionStatus::Connected { model, .. } => match selected_status(app) {
+                Some(status) => (
+                    format!("{model} — {}", status.summary()),
+                    printer_status_color(status),
+                ),
+                None => (model.clone(), Color::from_rgb(0.20, 0.78, 0.35)),
+            },
             ConnectionStatus::Scanning => {
                 ("Scanning...".into(), Color::from_rgb(0.55, 0.55, 0.58))
             }
@@ -787,6 +1438,16 @@ fn kiosk_view(app: &App) -> Element<'_, Message> {
         };
         idle.push(text(status_text).size(10).color(status_color).into());
 
+        if let Some(left) = app.roll.as_ref().and_then(RollReport::summary) {
+            let low = app.roll.as_ref().is_some_and(RollReport::is_low);
+            let color = if low {
+                Color::from_rgb(0.85, 0.55, 0.0)
+            } else {
+                Color::from_rgb(0.55, 0.55, 0.58)
+            };
+            idle.push(text(format!("Paper: {left}")).size(9).color(color).into());
+        }
+
         let poller_text = match &app.poller_status {
             PollerStatus::Polling => "Waiting for messages...",
             PollerStatus::Connecting => "Connecting...",
@@ -811,10 +1472,24 @@ fn kiosk_view(app: &App) -> Element<'_, Message> {
 
         idle
     } else {
-        app.kiosk_display_lines
-            .iter()
-            .map(build_preview_line)
-            .collect()
+        let mut lines: Vec<Element<'_, Message>> = Vec::new();
+        // Problems stay visible over the last message. The roll count
+        // warns before the near-end sensor does.
+        let sensor_banner = selected_status(app)
+            .and_then(|status| status_banner(status).map(|b| (status, b)));
+        if let Some((status, banner)) = sensor_banner {
+            lines.push(text(banner).size(10).color(printer_status_color(status)).into());
+        } else if let Some(roll) = app.roll.as_ref().filter(|r| r.is_low()) {
+            let left = roll.summary().unwrap_or_default();
+            lines.push(
+                text(format!("Paper low — {left}"))
+                    .size(10)
+                    .color(Color::from_rgb(0.85, 0.55, 0.0))
+                    .into(),
+            );
+        }
+        lines.extend(app.kiosk_display_lines.iter().map(build_preview_line));
+        lines
     };
 
     let content = column(lines).spacing(1).padding(4).width(Length::Fill);
@@ -845,13 +1520,18 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
                 .as_ref()
                 .map(|s| format!(" ({s})"))
                 .unwrap_or_default();
-            format!("Connected: {model}{serial_str}")
+            let printer_str = selected_status(app)
+                .map(|s| format!(" — {}", s.summary()))
+                .unwrap_or_default();
+            format!("Connected: {model}{serial_str}{printer_str}")
         }
         ConnectionStatus::Error(e) => format!("Error: {e}"),
     };
 
     let status_color = match &app.status {
-        ConnectionStatus::Connected { .. } => Color::from_rgb(0.20, 0.78, 0.35),
+        ConnectionStatus::Connected { .. } => selected_status(app)
+            .map(printer_status_color)
+            .unwrap_or(Color::from_rgb(0.20, 0.78, 0.35)),
         ConnectionStatus::Error(_) => Color::from_rgb(1.0, 0.23, 0.19),
         ConnectionStatus::Scanning => Color::from_rgb(0.55, 0.55, 0.58),
         ConnectionStatus::Disconnected => Color::from_rgb(0.55, 0.55, 0.58),
@@ -868,6 +1548,21 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
     .padding([8, 12])
     .align_y(iced::Alignment::Center);
 
+    // Printer needs attention (paper low, cover open, ...)
+    let status_banner_section: Element<'_, Message> = match selected_status(app)
+        .and_then(|s| status_banner(s).map(|b| (s, b)))
+    {
+        Some((status, banner)) => container(text(banner).size(13).color(Color::WHITE))
+            .padding([6, 12])
+            .width(Length::Fill)
+            .style(move |_: &Theme| container::Style {
+                background: Some(printer_status_color(status).into()),
+                ..Default::default()
+            })
+            .into(),
+        None => Space::new(0, 0).into(),
+    };
+
     // Platform warnings
     let warnings_section: Element<'_, Message> = if app.platform_warnings.is_empty() {
         Space::new(0, 0).into()
@@ -893,22 +1588,39 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
         .into()
     };
 
-    // Printer selector (if multiple printers found)
-    let printer_selector: Element<'_, Message> = if app.discovered.len() > 1 {
+    // Printer selector (if multiple printers found), with each one's queue
+    let printer_selector: Element<'_, Message> = if app.lanes.len() > 1 {
         column(
-            app.discovered
+            app.lanes
                 .iter()
                 .enumerate()
-                .map(|(i, p)| {
+                .map(|(i, lane)| {
+                    let p = &lane.printer;
                     let label = if app.selected_printer == Some(i) {
-                        format!("(*) {}", p.model_name)
+                        format!("(*) {} — {}", p.model_name, lane.key())
                     } else {
-                        format!("( ) {}", p.model_name)
+                        format!("( ) {} — {}", p.model_name, lane.key())
                     };
-                    button(text(label).size(12))
-                        .on_press(Message::SelectPrinter(i))
-                        .padding(4)
-                        .into()
+                    let queue = text(lane.queue_text())
+                        .size(11)
+                        .color(Color::from_rgb(0.85, 0.55, 0.0));
+                    let cancel: Element<'_, Message> = match lane.current_job {
+                        Some(_) => button(text("Cancel").size(11))
+                            .on_press(Message::CancelPrint(lane.key()))
+                            .padding([2, 8])
+                            .into(),
+                        None => Space::new(0, 0).into(),
+                    };
+                    row![
+                        button(text(label).size(12))
+                            .on_press(Message::SelectPrinter(i))
+                            .padding(4),
+                        queue,
+                        cancel,
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center)
+                    .into()
                 })
                 .collect::<Vec<_>>(),
         )
@@ -940,7 +1652,12 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
         .width(Length::FillPortion(2));
 
     // Preview label
-    let preview_label = text("Preview")
+    let preview_label = if app.wrapped_lines.is_empty() {
+        "Preview".to_string()
+    } else {
+        format!("Preview — ≈{} mm of paper", app.paper_mm)
+    };
+    let preview_label = text(preview_label)
         .size(11)
         .color(Color::from_rgb(0.55, 0.55, 0.58));
 
@@ -992,8 +1709,20 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
     };
 
     // Print button + result
-    let print_btn: Element<'_, Message> = if app.printing {
-        button(text("Printing...").size(13)).padding([6, 20]).into()
+    let selected = selected_lane(app);
+    let print_btn: Element<'_, Message> = if let Some(lane) = selected.filter(|l| l.printing) {
+        let label = match lane.job_progress {
+            Some((sent, total)) if total > 0 => format!("Printing... {}%", sent * 100 / total),
+            _ => "Printing...".to_string(),
+        };
+        row![
+            button(text(label).size(13)).padding([6, 20]),
+            button(text("Cancel").size(13))
+                .on_press_maybe(lane.current_job.map(|_| Message::CancelPrint(lane.key())))
+                .padding([6, 12]),
+        ]
+        .spacing(6)
+        .into()
     } else {
         let can_print = app.selected_printer.is_some() && !app.content.text().trim().is_empty();
         if can_print {
@@ -1018,7 +1747,19 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
         None => Space::new(0, 0).into(),
     };
 
-    let bottom_bar = row![print_btn, Space::with_width(10), result_display]
+    let drawer_btn = button(text("Pulse drawer").size(13))
+        .on_press_maybe(app.selected_printer.map(|_| Message::PulseDrawer))
+        .padding([6, 14]);
+
+    let dither_picker = pick_list(
+        DitherChoice::all(),
+        Some(app.dither.map_or(DitherChoice::PrinterDefault, DitherChoice::Algorithm)),
+        Message::SelectDither,
+    )
+    .text_size(13)
+    .padding([6, 10]);
+
+    let bottom_bar = row![print_btn, drawer_btn, dither_picker, Space::with_width(10), result_display]
         .spacing(10)
         .padding([8, 12])
         .align_y(iced::Alignment::Center);
@@ -1026,6 +1767,7 @@ fn desktop_view(app: &App) -> Element<'_, Message> {
     // Layout
     let content = column![
         status_bar,
+        status_banner_section,
         warnings_section,
         printer_selector,
         editor_preview,
@@ -1071,12 +1813,20 @@ fn build_messages_section(app: &App) -> Element<'_, Message> {
         "Resume"
     };
 
-    let queue_count = app.print_queue.len();
-    let queue_text = if queue_count > 0 {
-        format!("{queue_count} queued")
-    } else {
-        String::new()
-    };
+    // Each printer's queue; named when there's more than one
+    let queue_text = app
+        .lanes
+        .iter()
+        .filter_map(|lane| {
+            let queue = lane.queue_text();
+            match app.lanes.len() {
+                _ if queue.is_empty() => None,
+                1 => Some(queue),
+                _ => Some(format!("{}: {queue}", lane.key())),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" · ");
 
     let panel_label = if app.show_messages_panel {
         "Hide Messages"
@@ -1179,6 +1929,7 @@ fn build_messages_panel(app: &App) -> Element<'_, Message> {
         let (status_text, status_color) = match &msg.status {
             MessagePrintStatus::Printed => ("OK", Color::from_rgb(0.20, 0.78, 0.35)),
             MessagePrintStatus::Printing => ("..", Color::from_rgb(0.55, 0.55, 0.58)),
+            MessagePrintStatus::Waiting(_) => ("WAIT", Color::from_rgb(0.85, 0.55, 0.0)),
             MessagePrintStatus::Failed(e) => {
                 let _ = e;
                 ("FAIL", Color::from_rgb(1.0, 0.23, 0.19))
@@ -1427,7 +2178,14 @@ pub fn subscription(app: &App) -> Subscription<Message> {
     let hotplug = Subscription::run(hotplug_watcher);
     let health = time::every(std::time::Duration::from_secs(5)).map(|_| Message::HealthCheck);
 
-    let mut subs = vec![hotplug, health];
+    let status_events = Subscription::run(status_watcher);
+    let mut subs = vec![hotplug, health, status_events];
+    for lane in &app.lanes {
+        subs.push(Subscription::run_with_id(
+            ("printer-jobs", lane.key()),
+            job_watcher(lane.handle.subscribe()),
+        ));
+    }
 
     if app.poller_enabled {
         if let Some(config) = app.poller_config.clone() {
@@ -1447,7 +2205,10 @@ pub fn subscription(app: &App) -> Subscription<Message> {
         subs.push(
             Subscription::run_with_id(
                 "upload-server",
-                crate::upload_server::subscription::upload_server(bind_addr),
+                crate::upload_server::subscription::upload_server(
+                    bind_addr,
+                    app.shared_status.clone(),
+                ),
             )
             .map(Message::UploadEvent),
         );
@@ -1456,6 +2217,49 @@ pub fn subscription(app: &App) -> Subscription<Message> {
     Subscription::batch(subs)
 }
 
+/// Forward progress of a printer thread's jobs.
+fn job_watcher(
+    mut events: tokio::sync::broadcast::Receiver<JobEvent>,
+) -> impl futures::Stream<Item = Message> {
+    iced::stream::channel(10, |mut output| async move {
+        use futures::SinkExt;
+        use tokio::sync::broadcast::error::RecvError;
+
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if output.send(Message::JobEvent(event)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Forward ASB status changes from the open connection.
+fn status_watcher() -> impl futures::Stream<Item = Message> {
+    iced::stream::channel(10, |mut output| async move {
+        use futures::SinkExt;
+        use tokio::sync::broadcast::error::RecvError;
+
+        let mut events = status::subscribe();
+        loop {
+            match events.recv().await {
+                Ok(change) => {
+                    if output.send(Message::StatusChanged(change)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
 fn hotplug_watcher() -> impl futures::Stream<Item = Message> {
     iced::stream::channel(10, |mut output| async move {
         let watcher = match nusb::watch_devices() {
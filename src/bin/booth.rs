@@ -4,12 +4,22 @@
 //! camera, and print pipeline as a standalone process.
 //!
 //! Pass `--indoor` for brighter capture settings (higher EV, longer exposure).
+//! Pass `--dry-run` or `--output FILE` to encode the strip locally instead of
+//! posting it to the server.
 
 use std::path::PathBuf;
 use std::process::Command;
 
+use receipts::printer::backend::parse_output_args;
+use receipts::printer::connection::PrinterConnection;
+use receipts::printer::discovery::DiscoveredPrinter;
+
 fn main() {
-    let indoor = std::env::args().any(|a| a == "--indoor");
+    let (output, args) = parse_output_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+    let indoor = args.iter().any(|a| a == "--indoor");
     let mode_label = if indoor { "indoor" } else { "outdoor" };
     eprintln!("[booth] Mode: {mode_label}");
 
@@ -20,6 +30,12 @@ fn main() {
     });
 
     let port = std::env::var("UPLOAD_PORT").unwrap_or_else(|_| "80".to_string());
+    let mut local = output.map(|address| {
+        PrinterConnection::open(&DiscoveredPrinter::for_output(address)).unwrap_or_else(|e| {
+            eprintln!("[booth] {e}");
+            std::process::exit(1);
+        })
+    });
 
     // Step 1: Kill any existing camera preview
     eprintln!("[booth] Stopping camera preview...");
@@ -87,8 +103,10 @@ fn main() {
         }
     }
 
-    // Step 3: Print all 3 via /print/strip (no cuts)
+    // Step 3: Print all 3 via /print/strip (no cuts), as one strip session
+    // so the server keeps them on one printer
     let bright_param = if indoor { "&bright=1" } else { "" };
+    let session = chrono::Local::now().format("%Y%m%d%H%M%S%3f").to_string();
     eprintln!("[booth] Printing {} photos as strip (no cuts, {mode_label})...", photos.len());
     for (i, photo) in photos.iter().enumerate() {
         let bytes = match std::fs::read(photo) {
@@ -101,7 +119,16 @@ fn main() {
 
         // Extra feed after last photo so the tear doesn't cut into the image
         let feed = if i == photos.len() - 1 { 10 } else { 3 };
-        let url = format!("http://localhost:{port}/print/strip?feed={feed}{bright_param}");
+
+        if let Some(conn) = local.as_mut() {
+            match conn.print_image_no_cut(&bytes, feed, indoor) {
+                Ok(()) => eprintln!("[booth] Photo {} written to {}", i + 1, conn.address),
+                Err(e) => eprintln!("[booth] Print failed: {e}"),
+            }
+            continue;
+        }
+
+        let url = format!("http://localhost:{port}/print/strip?feed={feed}&session={session}{bright_param}");
         let boundary = "----boothboundary";
         let mut body = Vec::new();
         body.extend_from_slice(
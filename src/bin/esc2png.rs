@@ -0,0 +1,57 @@
+//! Render a captured ESC/POS byte stream to a PNG.
+//!
+//! Usage: esc2png <input.bin> [output.png] [--width 512|576]
+
+use std::path::PathBuf;
+
+use receipts::printer::virtual_printer::{VirtualPrinter, DEFAULT_WIDTH_DOTS};
+
+fn main() {
+    let mut width = DEFAULT_WIDTH_DOTS;
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--width" {
+            width = args
+                .next()
+                .and_then(|w| w.parse().ok())
+                .unwrap_or_else(|| usage("--width needs a number of dots"));
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+
+    let input = paths
+        .first()
+        .cloned()
+        .unwrap_or_else(|| usage("missing input file"));
+    let output = paths
+        .get(1)
+        .cloned()
+        .unwrap_or_else(|| input.with_extension("png"));
+
+    let bytes = std::fs::read(&input).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {e}", input.display());
+        std::process::exit(1);
+    });
+
+    let mut printer = VirtualPrinter::new(width);
+    printer.feed(&bytes);
+    let png = printer.to_png().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    if let Err(e) = std::fs::write(&output, png) {
+        eprintln!("Failed to write {}: {e}", output.display());
+        std::process::exit(1);
+    }
+
+    println!("{}", output.display());
+}
+
+fn usage(problem: &str) -> ! {
+    eprintln!("{problem}");
+    eprintln!("Usage: esc2png <input.bin> [output.png] [--width 512|576]");
+    std::process::exit(2);
+}
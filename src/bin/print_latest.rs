@@ -1,7 +1,16 @@
 use std::path::PathBuf;
 
+use receipts::printer::backend::{parse_output_args, PrinterAddress};
+use receipts::printer::connection::PrinterConnection;
+use receipts::printer::discovery::DiscoveredPrinter;
+
 fn main() {
-    let pics_dir = pics_dir();
+    // --dry-run / --output FILE: encode locally instead of posting to the server
+    let (output, args) = parse_output_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+    let pics_dir = pics_dir(&args);
     let latest = find_latest_jpg(&pics_dir);
 
     match latest {
@@ -11,7 +20,10 @@ fn main() {
                 eprintln!("Failed to read {}: {e}", path.display());
                 std::process::exit(1);
             });
-            send_to_printer(&bytes);
+            match output {
+                Some(address) => print_to_output(&address, &bytes),
+                None => send_to_printer(&bytes),
+            }
             println!("{}", path.display());
         }
         None => {
@@ -21,9 +33,8 @@ fn main() {
     }
 }
 
-fn pics_dir() -> PathBuf {
-    let args: Vec<String> = std::env::args().collect();
-    if let Some(dir) = args.get(1) {
+fn pics_dir(args: &[String]) -> PathBuf {
+    if let Some(dir) = args.first() {
         return PathBuf::from(dir);
     }
     let mut dir = std::env::current_exe()
@@ -49,6 +60,22 @@ fn find_latest_jpg(dir: &PathBuf) -> Option<PathBuf> {
         .map(|e| e.path())
 }
 
+/// Encode the photo exactly as the server's `/print/upload` would and write
+/// it to `address`.
+fn print_to_output(address: &PrinterAddress, bytes: &[u8]) {
+    let printer = DiscoveredPrinter::for_output(address.clone());
+    let max_chars = printer.print_width().chars;
+    let result = PrinterConnection::open(&printer)
+        .and_then(|mut conn| conn.print_website_message(&[], max_chars, Some(bytes)));
+    match result {
+        Ok(()) => eprintln!("Wrote to {address} ({} KB)", bytes.len() / 1024),
+        Err(e) => {
+            eprintln!("Failed to print to {address}: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
 fn send_to_printer(bytes: &[u8]) {
     let port = std::env::var("UPLOAD_PORT").unwrap_or_else(|_| "80".to_string());
     let url = format!("http://localhost:{port}/print/upload");
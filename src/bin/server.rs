@@ -1,15 +1,48 @@
 //! Headless web server for receipt printing.
 //!
 //! Replaces the iced GUI app for the Pi use case — no X11, no DISPLAY needed.
-//! Runs the axum upload server on port 80 and processes print jobs directly.
+//! Runs the axum upload server on port 80 (or `UPLOAD_PORT`) and processes
+//! print jobs directly.
+//!
+//! Pass `--dry-run` to discard output, or `--output FILE` to append the raw
+//! ESC/POS bytes to a file or FIFO (`.png` renders the last job via the
+//! virtual printer), so the full HTTP → queue → print flow runs without a
+//! USB printer.
+//!
+//! Every printer discovered is opened, each with its own queue, and jobs are
+//! shared between them by the `[[route]]` entries in the config file. Set
+//! `PRINTER` to a printer address (`tcp://10.0.0.5:9100`, `usb:04b8:0e28`,
+//! `usb:04b8:0e28?serial=J4KF012345`) to use only that printer.
+//!
+//! Each printer's real-time status is polled every few seconds — and pushed
+//! by the printer itself via ASB where supported — and served at
+//! `GET /status`, the first printer's at the top level. While a printer reports paper out or cover
+//! open its queue pauses, keeping the current job, and carries on once the
+//! printer is ready again; if another printer can take the jobs first, they
+//! move there. A printer that stops answering has its jobs moved the same
+//! way until it's back.
+//!
+//! Ctrl-C or SIGTERM stops the server: the job in progress is cancelled
+//! before its next raster band and the printer connection is closed.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
-use receipts::printer::connection::{self, SharedConnection};
+use receipts::printer::backend::{parse_output_args, PrinterAddress};
+use receipts::printer::actor::{Job, JobPayload, PrinterHandle};
+use receipts::printer::connection::JobError;
 use receipts::printer::discovery;
-use receipts::printer::models::find_known_model;
+use receipts::printer::paper::PaperGeometry;
+use receipts::printer::usage::{self, RollReport};
+use receipts::printer::queue::{PrintQueue, QueueState};
+use receipts::printer::router::{Router, Target};
+use receipts::printer::status::{self, SharedStatus, SharedStatuses};
 use receipts::receipt_markdown;
-use receipts::upload_server::handler::{self, PrintPayload};
+use receipts::upload_server::handler::{self, PrintPayload, Submission};
 
-use tokio::sync::mpsc;
+use axum::http::StatusCode;
+use tokio::sync::mpsc::{self, error::TryRecvError};
 
 #[tokio::main]
 async fn main() {
@@ -23,46 +56,64 @@ async fn main() {
 
     tracing::info!("Starting headless receipt server");
 
-    // Discover printer
-    let printer = match discovery::scan_for_printers() {
+    let output = match parse_output_args(std::env::args().skip(1)) {
+        Ok((output, _)) => output,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let configured = match std::env::var("PRINTER") {
+        Ok(addr) => match addr.parse::<PrinterAddress>() {
+            Ok(address) => Some(address),
+            Err(e) => {
+                eprintln!("Invalid PRINTER: {e}");
+                std::process::exit(2);
+            }
+        },
+        Err(_) => None,
+    };
+
+    // Discover printers: every one found, or just the one configured
+    let fixed = output.or(configured);
+    let printers = match discovery::scan_or_output(fixed.as_ref()) {
         Ok(printers) => {
-            if let Some(p) = printers.into_iter().next() {
-                tracing::info!("Found printer: {} (PID={:04x})", p.model_name, p.product_id);
-                Some(p)
-            } else {
-                tracing::warn!("No Epson printer found — will retry on each print job");
-                None
+            if printers.is_empty() {
+                tracing::warn!("No printer found — will retry on each print job");
             }
+            printers
         }
         Err(e) => {
             tracing::warn!("USB scan failed: {e} — will retry on each print job");
-            None
+            Vec::new()
         }
     };
 
-    // Open shared USB connection
-    let shared = connection::new_shared();
-    if let Some(ref p) = printer {
-        if let Err(e) = connection::open_shared(&shared, p.product_id, p.model_name.clone()) {
-            tracing::warn!("Initial USB connection failed: {e}");
-        }
+    // Each printer gets its own printer thread, status poller, queue and
+    // worker; the first one's status is the top of `GET /status`
+    let printer_status = status::new_shared_status();
+    let statuses = status::new_shared_statuses();
+    let fleet = Arc::new(Fleet::new(printer_status.clone(), statuses.clone()));
+    for p in printers {
+        tracing::info!("Found printer: {} ({})", p.model_name, p.key());
+        fleet.add(p);
     }
+    tokio::spawn(watch_status_events(fleet.clone()));
 
     // Channel for print payloads from the web handler
-    let (tx, rx) = mpsc::channel::<PrintPayload>(32);
+    let (tx, rx) = mpsc::channel::<Submission>(32);
 
-    // Spawn print worker
-    let worker_shared = shared.clone();
-    let worker_printer = printer.clone();
-    tokio::task::spawn_blocking(move || {
-        print_worker(rx, worker_shared, worker_printer);
-    });
+    // Spawn the dispatcher that routes them to the printers
+    let dispatch_fleet = fleet.clone();
+    let dispatcher = tokio::task::spawn_blocking(move || dispatch(rx, dispatch_fleet));
 
     // Build and serve the axum router
-    let router = handler::build_router(tx);
-    let bind_addr = "0.0.0.0:80";
+    let router = handler::build_router(tx, printer_status, statuses);
+    let port = std::env::var("UPLOAD_PORT").unwrap_or_else(|_| "80".to_string());
+    let bind_addr = format!("0.0.0.0:{port}");
 
-    let listener = match tokio::net::TcpListener::bind(bind_addr).await {
+    let listener = match tokio::net::TcpListener::bind(&bind_addr).await {
         Ok(l) => l,
         Err(e) => {
             tracing::error!("Failed to bind {bind_addr}: {e}");
@@ -86,86 +137,590 @@ async fn main() {
 
     tracing::info!("Listening on {bind_addr}");
 
-    if let Err(e) = axum::serve(listener, router).await {
+    let served = axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal())
+        .await;
+
+    // The router held the job sender, so the workers stop once the
+    // printer threads have finished (or cancelled) their jobs
+    fleet.shutdown();
+    let _ = dispatcher.await;
+
+    if let Err(e) = served {
         tracing::error!("Server error: {e}");
         std::process::exit(1);
     }
+    tracing::info!("Server stopped");
 }
 
-/// Blocking print worker — receives payloads from the channel and prints them.
-/// Runs in a blocking thread since USB I/O is synchronous.
-fn print_worker(
-    mut rx: mpsc::Receiver<PrintPayload>,
-    shared: SharedConnection,
-    initial_printer: Option<discovery::DiscoveredPrinter>,
-) {
-    // Cache printer info; re-scan if we didn't find one at startup
-    let mut cached_printer = initial_printer;
+/// Resolve on Ctrl-C, or SIGTERM from a service manager.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Can't listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Can't listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutting down");
+}
+
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How often a paused queue checks whether the printer is ready again.
+const RESUME_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One open printer: its thread, its status and how many jobs it has.
+struct Station {
+    printer: discovery::DiscoveredPrinter,
+    actor: PrinterHandle,
+    status: SharedStatus,
+    /// Jobs routed here and not yet printed, failed or moved on.
+    load: AtomicUsize,
+    /// Set when a job fails to get through, until the printer answers again.
+    offline: AtomicBool,
+}
+
+impl Station {
+    /// Whether a job sent here now would print.
+    fn ready(&self) -> bool {
+        if self.offline.load(Ordering::Relaxed) {
+            return false;
+        }
+        let status = self.status.lock().ok().and_then(|r| r.status.clone());
+        status.is_none_or(|s| s.blocking_error().is_none())
+    }
+}
+
+struct Member {
+    station: Arc<Station>,
+    /// The worker's queue; dropped when the server stops.
+    jobs: Option<mpsc::UnboundedSender<PrintPayload>>,
+}
+
+/// A station's status poller thread, which stops once `stop` is dropped.
+struct Poller {
+    stop: Option<std::sync::mpsc::Sender<()>>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+/// The open printers, and the routes that share jobs between them.
+struct Fleet {
+    members: RwLock<Vec<Member>>,
+    router: Mutex<Router>,
+    workers: Mutex<Vec<std::thread::JoinHandle<()>>>,
+    pollers: Mutex<Vec<Poller>>,
+    /// The first printer's status, at the top of `GET /status`.
+    primary_status: SharedStatus,
+    /// Every printer's status, by key, for `GET /status`.
+    statuses: SharedStatuses,
+}
 
-    while let Some(payload) = rx.blocking_recv() {
-        // If we don't have a printer, try scanning again
-        if cached_printer.is_none() {
+impl Fleet {
+    fn new(primary_status: SharedStatus, statuses: SharedStatuses) -> Self {
+        Self {
+            members: RwLock::new(Vec::new()),
+            router: Mutex::new(Router::from_config()),
+            workers: Mutex::new(Vec::new()),
+            pollers: Mutex::new(Vec::new()),
+            primary_status,
+            statuses,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.members.read().map_or(true, |m| m.is_empty())
+    }
+
+    /// Open a printer and start its status poller and print worker.
+    fn add(self: &Arc<Self>, printer: discovery::DiscoveredPrinter) {
+        let Ok(mut members) = self.members.write() else {
+            return;
+        };
+        let status = if members.is_empty() {
+            self.primary_status.clone()
+        } else {
+            status::new_shared_status()
+        };
+        set_status_printer(&status, &printer);
+        if let Ok(mut statuses) = self.statuses.lock() {
+            statuses.push((printer.key(), status.clone()));
+        }
+
+        let actor = PrinterHandle::spawn();
+        if let Err(e) = actor.open_blocking(&printer) {
+            tracing::warn!("Connection to {} failed: {e}", printer.key());
+        }
+        let station = Arc::new(Station {
+            printer,
+            actor,
+            status,
+            load: AtomicUsize::new(0),
+            offline: AtomicBool::new(false),
+        });
+
+        let (stop, stopped) = std::sync::mpsc::channel();
+        let poll_station = station.clone();
+        let thread = std::thread::spawn(move || status_poller(poll_station, stopped));
+        if let Ok(mut pollers) = self.pollers.lock() {
+            pollers.push(Poller {
+                stop: Some(stop),
+                thread,
+            });
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let fleet = self.clone();
+        let worker_station = station.clone();
+        let worker = std::thread::spawn(move || print_worker(rx, worker_station, fleet));
+        if let Ok(mut workers) = self.workers.lock() {
+            workers.push(worker);
+        }
+        members.push(Member {
+            station,
+            jobs: Some(tx),
+        });
+    }
+
+    /// Route a job to a printer's queue, returning the printer's key. A job
+    /// moving away from `from` only goes to a printer that's ready; with
+    /// none, or once the server is stopping, it comes back. So does a strip
+    /// frame, whose strip stays on the printer it started on.
+    fn dispatch(
+        &self,
+        payload: PrintPayload,
+        from: Option<&Station>,
+    ) -> Result<String, PrintPayload> {
+        let Ok(members) = self.members.read() else {
+            return Err(payload);
+        };
+        match self.pick(&members, &payload, from) {
+            Some(i) => queue(&members[i], payload),
+            None => Err(payload),
+        }
+    }
+
+    /// Route an uploaded job and check its paper on the printer it goes
+    /// to, queueing it there unless it's over `[paper] max_job_mm`. Returns
+    /// the paper note for the response.
+    fn submit(&self, payload: PrintPayload) -> Result<String, (StatusCode, String)> {
+        let unavailable = || {
+            tracing::error!("No printer available — dropping print job");
+            (StatusCode::SERVICE_UNAVAILABLE, "No printer available".to_string())
+        };
+        let Ok(members) = self.members.read() else {
+            return Err(unavailable());
+        };
+        let Some(i) = self.pick(&members, &payload, None) else {
+            return Err(unavailable());
+        };
+        let member = &members[i];
+        let geometry = PaperGeometry::for_printer(&member.station.printer);
+        let paper = handler::paper_check(&payload, geometry)?;
+        let kind = payload.kind();
+        let key = queue(member, payload).map_err(|_| unavailable())?;
+        tracing::debug!("Routed {kind} job to {key}");
+        Ok(paper)
+    }
+
+    /// The member a job goes to by its route, as `dispatch` picks it.
+    fn pick(
+        &self,
+        members: &[Member],
+        payload: &PrintPayload,
+        from: Option<&Station>,
+    ) -> Option<usize> {
+        let targets: Vec<Target<'_>> = members
+            .iter()
+            .map(|m| Target {
+                printer: &m.station.printer,
+                ready: m.jobs.is_some()
+                    && !from.is_some_and(|f| std::ptr::eq(f, &*m.station))
+                    && m.station.ready(),
+                load: m.station.load.load(Ordering::Relaxed),
+            })
+            .collect();
+        let kind = payload.kind();
+        let i = self.router.lock().ok().and_then(|mut router| match payload.strip_session() {
+            Some(session) => router.route_strip(session, &targets),
+            None => router.route(kind, &targets),
+        })?;
+        if from.is_some() && !targets[i].ready {
+            return None;
+        }
+        Some(i)
+    }
+
+    /// Stop the status pollers and every printer thread, cancelling the
+    /// jobs in progress.
+    fn shutdown(&self) {
+        if let Ok(mut pollers) = self.pollers.lock() {
+            for poller in pollers.iter_mut() {
+                poller.stop = None;
+            }
+        }
+        let stations: Vec<Arc<Station>> = self
+            .members
+            .read()
+            .map(|m| m.iter().map(|m| m.station.clone()).collect())
+            .unwrap_or_default();
+        for station in stations {
+            station.actor.shutdown();
+        }
+    }
+
+    /// Close the workers' queues and wait for them and the status pollers
+    /// to finish.
+    fn close(&self) {
+        if let Ok(mut members) = self.members.write() {
+            for member in members.iter_mut() {
+                member.jobs = None;
+            }
+        }
+        let workers = self.workers.lock().map(|mut w| std::mem::take(&mut *w)).unwrap_or_default();
+        for worker in workers {
+            let _ = worker.join();
+        }
+        let pollers = self.pollers.lock().map(|mut p| std::mem::take(&mut *p)).unwrap_or_default();
+        for poller in pollers {
+            drop(poller.stop);
+            let _ = poller.thread.join();
+        }
+    }
+}
+
+/// Put a job on a member's queue, returning the printer's key; it comes
+/// back once the server is stopping.
+fn queue(member: &Member, payload: PrintPayload) -> Result<String, PrintPayload> {
+    let Some(jobs) = &member.jobs else {
+        return Err(payload);
+    };
+    member.station.load.fetch_add(1, Ordering::Relaxed);
+    if let Err(e) = jobs.send(payload) {
+        member.station.load.fetch_sub(1, Ordering::Relaxed);
+        return Err(e.0);
+    }
+    Ok(member.station.printer.key())
+}
+
+/// Route uploaded jobs to the printers until the web server stops. With no
+/// printer, each job first scans for one.
+fn dispatch(mut rx: mpsc::Receiver<Submission>, fleet: Arc<Fleet>) {
+    while let Some(Submission { payload, reply }) = rx.blocking_recv() {
+        if fleet.is_empty() {
             if let Ok(printers) = discovery::scan_for_printers() {
-                if let Some(p) = printers.into_iter().next() {
+                for p in printers {
                     tracing::info!(
                         "Discovered printer on retry: {} (PID={:04x})",
                         p.model_name,
                         p.product_id
                     );
-                    cached_printer = Some(p);
+                    fleet.add(p);
                 }
             }
         }
 
-        let Some(ref printer) = cached_printer else {
-            tracing::error!("No printer available — dropping print job");
+        let _ = reply.send(fleet.submit(payload));
+    }
+
+    fleet.close();
+    tracing::info!("Print workers shut down");
+}
+
+/// Poll a printer's real-time status for routing and `GET /status`,
+/// logging changes. A round is skipped while a job is printing. A printer
+/// marked offline is reconnected first, so it gets jobs again once it's back.
+/// Stops when `stop`'s sender is dropped.
+fn status_poller(station: Arc<Station>, stop: std::sync::mpsc::Receiver<()>) {
+    let key = station.printer.key();
+    loop {
+        if station.offline.load(Ordering::Relaxed) && station.actor.open_blocking(&station.printer).is_err() {
+            if !wait_to_poll(&stop) {
+                break;
+            }
+            continue;
+        }
+        let polled = station.actor.poll_status_blocking();
+        if polled.is_ok() && station.offline.swap(false, Ordering::Relaxed) {
+            tracing::info!("Printer {key} is back online");
+        }
+        match polled {
+            Ok(Some(latest)) => {
+                if let Ok(mut report) = station.status.lock() {
+                    if report.status.as_ref() != Some(&latest) {
+                        tracing::info!("Printer {key} status: {}", latest.summary());
+                    }
+                    report.status = Some(latest);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                if !station.offline.swap(true, Ordering::Relaxed) {
+                    tracing::warn!("Printer {key} status unavailable: {e}");
+                }
+                if let Ok(mut report) = station.status.lock() {
+                    report.status = None;
+                }
+            }
+        }
+        if !wait_to_poll(&stop) {
+            break;
+        }
+    }
+}
+
+/// Sleep until the next status poll; false if the poller was stopped.
+fn wait_to_poll(stop: &std::sync::mpsc::Receiver<()>) -> bool {
+    use std::sync::mpsc::RecvTimeoutError;
+
+    matches!(stop.recv_timeout(STATUS_POLL_INTERVAL), Err(RecvTimeoutError::Timeout))
+}
+
+/// Apply status changes pushed by the printers (ASB) as they happen.
+async fn watch_status_events(fleet: Arc<Fleet>) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut events = status::subscribe();
+    loop {
+        match events.recv().await {
+            Ok(change) => {
+                if let Some(problem) = change.status.blocking_error() {
+                    tracing::warn!("{}: {problem}", change.printer);
+                } else if change.status.paper_near_end {
+                    tracing::warn!("{}: paper low", change.printer);
+                }
+                let Ok(members) = fleet.members.read() else {
+                    continue;
+                };
+                let stations = members
+                    .iter()
+                    .filter(|m| m.station.printer.key() == change.key);
+                for member in stations {
+                    if let Ok(mut report) = member.station.status.lock() {
+                        report.status = Some(change.status.clone());
+                    }
+                }
+            }
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+fn set_status_printer(printer_status: &SharedStatus, printer: &discovery::DiscoveredPrinter) {
+    if let Ok(mut report) = printer_status.lock() {
+        report.printer = Some(printer.model_name.clone());
+        report.geometry = Some(PaperGeometry::for_printer(printer));
+        report.roll = Some(RollReport::for_printer(printer));
+    }
+}
+
+/// Move the jobs waiting on `station` to printers that are ready, keeping
+/// any that no other printer can take.
+fn failover(fleet: &Fleet, station: &Station, queue: &mut PrintQueue<PrintPayload>) {
+    let paused = queue.state().clone();
+    let mut moved = 0;
+    for payload in queue.take_waiting() {
+        match fleet.dispatch(payload, Some(station)) {
+            Ok(key) => {
+                moved += 1;
+                station.load.fetch_sub(1, Ordering::Relaxed);
+                tracing::debug!("Moved a job to {key}");
+            }
+            Err(payload) => queue.push(payload),
+        }
+    }
+    if let QueueState::Paused(reason) = paused {
+        if !queue.is_empty() {
+            queue.pause(reason);
+        }
+    }
+    if moved > 0 {
+        tracing::info!("{moved} jobs moved off printer {}", station.printer.key());
+    }
+}
+
+/// Blocking print worker for one printer — receives the jobs routed to it
+/// and prints them. Runs in its own thread since USB I/O is synchronous.
+///
+/// A job stopped by paper out or an open cover stays at the head of the
+/// queue; new jobs keep queueing behind it until the status report (kept
+/// fresh by the poller and ASB events) says the printer is ready — unless
+/// another printer is ready first, which then takes them over.
+fn print_worker(
+    mut rx: mpsc::UnboundedReceiver<PrintPayload>,
+    station: Arc<Station>,
+    fleet: Arc<Fleet>,
+) {
+    let printer = &station.printer;
+    let key = printer.key();
+    let mut queue = PrintQueue::default();
+
+    loop {
+        if queue.is_empty() {
+            match rx.blocking_recv() {
+                Some(payload) => queue.push(payload),
+                None => break,
+            }
+        }
+        let mut closed = false;
+        loop {
+            match rx.try_recv() {
+                Ok(payload) => queue.push(payload),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    closed = true;
+                    break;
+                }
+            }
+        }
+
+        if queue.is_paused() {
+            if closed {
+                tracing::warn!("Dropping {} jobs waiting for printer {key}", queue.len());
+                break;
+            }
+            failover(&fleet, &station, &mut queue);
+            if !queue.is_paused() {
+                continue;
+            }
+            std::thread::sleep(RESUME_CHECK_INTERVAL);
+            let status = station.status.lock().ok().and_then(|r| r.status.clone());
+            if status.is_some_and(|s| queue.update_status(&s)) {
+                tracing::info!("Printer {key} ready — resuming queue ({} jobs)", queue.len());
+            }
+            continue;
+        }
+        let Some(payload) = queue.start() else {
             continue;
         };
 
-        let product_id = printer.product_id;
-        let model_name = printer.model_name.clone();
-        let max_chars = find_known_model(0x04b8, product_id)
-            .map(|m| m.max_chars_per_line)
-            .unwrap_or(42);
+        let paper_mm = payload.paper(PaperGeometry::for_printer(printer)).mm();
+        let is_logo = matches!(payload, PrintPayload::Logo(_));
 
         let result = match payload {
-            PrintPayload::Image(bytes) => {
-                tracing::info!("Printing image: {} bytes", bytes.len());
-                connection::print_with_shared(&shared, product_id, model_name, |conn| {
-                    conn.print_website_message(&[], max_chars, Some(&bytes))
-                })
-            }
-            PrintPayload::ImageNoCut(bytes, feed, bright) => {
-                tracing::info!("Printing strip image (no cut, feed={}, bright={}): {} bytes", feed, bright, bytes.len());
-                connection::print_with_shared(&shared, product_id, model_name, |conn| {
-                    conn.print_image_no_cut(&bytes, feed, bright)
-                })
-            }
-            PrintPayload::Text { text, source } => {
-                tracing::info!("Printing text: {} bytes (source={})", text.len(), source);
-                let mut blocks = receipt_markdown::parse_receipt_markdown(&text);
-                blocks.push(receipt_markdown::ReceiptBlock::BlankLine);
-                blocks.push(receipt_markdown::ReceiptBlock::BlankLine);
-                blocks.push(receipt_markdown::ReceiptBlock::BlankLine);
-                connection::print_with_shared(&shared, product_id, model_name, |conn| {
-                    conn.print_no_cut(&blocks, max_chars)
-                })
+            PrintPayload::Logo(request) => {
+                tracing::info!("Logo request: {}", request.op);
+                let job = Job::new(JobPayload::Logo(request.op.clone()), Default::default());
+                let result = station.actor.run_blocking(job);
+                if !matches!(result, Err(JobError::NotReady(_))) {
+                    request.respond(result.clone().map_err(|e| e.to_string()));
+                }
+                result.map(|_| ())
             }
+            payload => station.actor.run_blocking(print_job(payload)).map(|_| ()),
         };
 
-        match &result {
-            Ok(()) => {
-                // Give the printer time to finish physically printing before
-                // sending the next job — prevents USB disconnects under load.
-                std::thread::sleep(std::time::Duration::from_secs(3));
+        if let Err(JobError::NotReady(status)) = result {
+            tracing::warn!(
+                "Printer {key} queue paused ({} jobs): {}",
+                queue.len(),
+                status.summary()
+            );
+            queue.pause(status.summary());
+            if let Ok(mut report) = station.status.lock() {
+                report.status = Some(status);
+            }
+            failover(&fleet, &station, &mut queue);
+            continue;
+        }
+        let done = queue.finish();
+        station.load.fetch_sub(1, Ordering::Relaxed);
+
+        if result.is_ok() {
+            station.offline.store(false, Ordering::Relaxed);
+            if paper_mm > 0 {
+                usage::record(printer, paper_mm);
+                if let Ok(mut report) = station.status.lock() {
+                    report.roll = Some(RollReport::for_printer(printer));
+                }
             }
+        }
+
+        // The connection holds the next job back until this one has
+        // printed, so only failures need a pause.
+        match &result {
+            Ok(()) => {}
+            Err(JobError::Cancelled) => tracing::info!("Print cancelled on {key}"),
+            // A bad job, not a bad printer: don't pass it on
+            Err(JobError::Invalid(e)) => tracing::warn!("Rejected job on {key}: {e}"),
             Err(e) => {
-                tracing::error!("Print failed: {e}");
+                tracing::error!("Print failed on {key}: {e}");
+                station.offline.store(true, Ordering::Relaxed);
+                // Another printer may get it out; logos already answered
+                let retry = done.filter(|_| !is_logo);
+                if let Some(payload) = retry {
+                    if let Err(payload) = fleet.dispatch(payload, Some(&station)) {
+                        tracing::warn!("No other printer for the failed {} job", payload.kind());
+                    }
+                }
+                failover(&fleet, &station, &mut queue);
                 // Brief pause before retrying to let USB recover
-                std::thread::sleep(std::time::Duration::from_secs(2));
+                std::thread::sleep(Duration::from_secs(2));
             }
         }
     }
 
-    tracing::info!("Print worker shutting down");
+    tracing::info!("Print worker for {key} shutting down");
+}
+
+/// The printer thread's job for an uploaded payload.
+fn print_job(payload: PrintPayload) -> Job {
+    let (payload, options) = match payload {
+        PrintPayload::Image(bytes, options) => {
+            tracing::info!("Printing image: {} bytes", bytes.len());
+            let message = JobPayload::Message {
+                blocks: Vec::new(),
+                image: Some(bytes),
+            };
+            (message, options)
+        }
+        PrintPayload::ImageNoCut(bytes, feed, bright, _, options) => {
+            tracing::info!("Printing strip image (no cut, feed={}, bright={}): {} bytes", feed, bright, bytes.len());
+            let strip = JobPayload::Strip {
+                image: bytes,
+                feed,
+                bright,
+            };
+            (strip, options)
+        }
+        PrintPayload::Text {
+            text,
+            source,
+            options,
+        } => {
+            tracing::info!("Printing text: {} bytes (source={})", text.len(), source);
+            let mut blocks = receipt_markdown::parse_log_markdown(&text);
+            blocks.push(receipt_markdown::ReceiptBlock::BlankLine);
+            blocks.push(receipt_markdown::ReceiptBlock::BlankLine);
+            blocks.push(receipt_markdown::ReceiptBlock::BlankLine);
+            (JobPayload::Log(blocks), options)
+        }
+        PrintPayload::Commands(commands, options) => {
+            tracing::info!("Printing command list: {} commands", commands.len());
+            (JobPayload::Commands(commands), options)
+        }
+        PrintPayload::Logo(request) => {
+            (JobPayload::Logo(request.op.clone()), Default::default())
+        }
+    };
+    Job::new(payload, options)
 }
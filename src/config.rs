@@ -0,0 +1,365 @@
+//! Optional TOML config file for printer data.
+//!
+//! Read once from the path in `RECEIPTS_CONFIG`, or `receipts.toml` in the
+//! working directory. A missing file is the same as an empty one; a malformed
+//! file is logged and ignored so a typo never stops the printer working.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+
+use crate::printer::backend::PrinterAddress;
+use crate::printer::encoder::PrintOptions;
+use crate::printer::image_proc::Dither;
+use crate::printer::models::{ModelSpec, PrintWidth, PrinterModel};
+use crate::printer::router::RouteRule;
+
+const DEFAULT_PATH: &str = "receipts.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FileConfig {
+    /// `[[model]]` entries, merged over the built-in model registry.
+    #[serde(default)]
+    pub model: Vec<ModelSpec>,
+    /// `[[printer]]` entries: settings for one physical printer.
+    #[serde(default)]
+    pub printer: Vec<PrinterSettings>,
+    #[serde(default)]
+    pub kiosk: KioskSettings,
+    /// `[endpoint.<name>]` print options per server endpoint (`upload`,
+    /// `strip`, `text`, `commands`), e.g. slower and darker for strips.
+    #[serde(default)]
+    pub endpoint: HashMap<String, PrintOptions>,
+    #[serde(default)]
+    pub paper: PaperSettings,
+    /// `[[route]]` entries: which printers each kind of job goes to.
+    #[serde(default)]
+    pub route: Vec<RouteRule>,
+    #[serde(default)]
+    pub resume: ResumeSettings,
+}
+
+/// `[resume]`: what happens to a raster job whose printer drops off the bus
+/// part-way through (e.g. a USB reset).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResumeSettings {
+    #[serde(default)]
+    pub mode: ResumeMode,
+    /// Reconnection attempts before the job fails (default 5).
+    pub attempts: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResumeMode {
+    /// Reconnect and carry on from the band after the last one printed.
+    #[default]
+    Continue,
+    /// Reconnect and print the whole job again under a "continued" line.
+    Reprint,
+    /// Fail the job, as for any other connection error.
+    Off,
+}
+
+impl ResumeSettings {
+    pub fn attempts(&self) -> u32 {
+        match self.mode {
+            ResumeMode::Off => 0,
+            _ => self.attempts.unwrap_or(5),
+        }
+    }
+}
+
+/// `[paper]`: limits on paper used by server jobs, and roll tracking.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PaperSettings {
+    /// Longest job the server accepts, in estimated millimetres.
+    pub max_job_mm: Option<u32>,
+    #[serde(default)]
+    pub over_max: OverMax,
+    /// Length of a full roll; enables the paper-left prediction.
+    pub roll_length_m: Option<u32>,
+    /// Paper left at which the roll is reported low (default 3 m).
+    pub low_roll_m: Option<u32>,
+}
+
+/// What the server does with a job over `max_job_mm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverMax {
+    /// Refuse it with 413 Payload Too Large.
+    #[default]
+    Reject,
+    /// Print it anyway, logging a warning and saying so in the response.
+    Warn,
+}
+
+impl PaperSettings {
+    /// The configured maximum, if `mm` exceeds it.
+    pub fn exceeded_by(&self, mm: u32) -> Option<u32> {
+        self.max_job_mm.filter(|&max| mm > max)
+    }
+}
+
+/// `[kiosk]`: behaviour of the kiosk display.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KioskSettings {
+    /// Sound the printer's buzzer after each website message prints.
+    #[serde(default)]
+    pub beep_on_message: bool,
+}
+
+/// Settings for one printer, matched by USB serial number or by address
+/// (`tcp://10.0.0.20:9100`, `lp:/dev/usb/lp0`, ...). Width fields describe
+/// the roll currently loaded, which may be narrower than the model's.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrinterSettings {
+    pub serial: Option<String>,
+    pub address: Option<String>,
+    /// Model name, for printers whose address doesn't identify them.
+    pub model: Option<String>,
+    /// Width of the loaded roll; 58 selects 384 dots / 32 chars.
+    pub paper_width_mm: Option<u8>,
+    pub dots_per_line: Option<u16>,
+    pub chars_per_line: Option<u8>,
+    pub chars_per_line_font_b: Option<u8>,
+    /// Default print density, -6 to 6.
+    pub density: Option<i8>,
+    /// Default print speed, 1 (fastest) to 13.
+    pub print_speed: Option<u8>,
+    /// Default image dithering, e.g. "atkinson".
+    pub dither: Option<Dither>,
+    #[serde(default)]
+    pub orientation: Orientation,
+    /// Length of a full roll, overriding `[paper] roll_length_m`.
+    pub roll_length_m: Option<u32>,
+}
+
+/// How the printer is mounted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    #[default]
+    Normal,
+    /// Face-down (e.g. under a counter): everything is printed rotated
+    /// 180° and in reverse order, so the torn-off receipt reads normally.
+    UpsideDown,
+}
+
+impl PrinterSettings {
+    fn matches(&self, serial: Option<&str>, address: &PrinterAddress) -> bool {
+        let serial_matches = match (&self.serial, serial) {
+            (Some(want), Some(have)) => want == have,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let address_matches = match &self.address {
+            Some(want) => want
+                .parse::<PrinterAddress>()
+                .is_ok_and(|want| want.selects(address)),
+            None => true,
+        };
+        (self.serial.is_some() || self.address.is_some()) && serial_matches && address_matches
+    }
+
+    /// The line width for this printer: the model's, replaced by the
+    /// standard widths for a different roll, then by explicit overrides.
+    pub fn print_width(&self, model: &PrinterModel) -> PrintWidth {
+        let width = match self.paper_width_mm {
+            Some(mm) if mm != model.paper_width_mm => PrintWidth::for_paper(mm),
+            _ => model.print_width(),
+        };
+        PrintWidth {
+            dots: self.dots_per_line.unwrap_or(width.dots),
+            chars: self.chars_per_line.unwrap_or(width.chars),
+            chars_font_b: self.chars_per_line_font_b.unwrap_or(width.chars_font_b),
+        }
+    }
+
+    pub fn print_options(&self) -> PrintOptions {
+        PrintOptions {
+            density: self.density,
+            speed: self.print_speed,
+            dither: self.dither,
+        }
+    }
+
+    /// Drop a density or speed the printer can't take, with a warning.
+    fn drop_invalid_options(&mut self) {
+        let mut options = self.print_options();
+        for problem in options.drop_invalid() {
+            tracing::warn!("Ignoring [[printer]] setting: {problem}");
+        }
+        self.density = options.density;
+        self.print_speed = options.speed;
+    }
+}
+
+static FILE_CONFIG: LazyLock<FileConfig> = LazyLock::new(load);
+
+/// The config file contents, loaded on first use.
+pub fn file_config() -> &'static FileConfig {
+    &FILE_CONFIG
+}
+
+/// The `[[printer]]` entry for a printer, if any. Entries naming both a
+/// serial and an address must match both.
+pub fn printer_settings(
+    serial: Option<&str>,
+    address: &PrinterAddress,
+) -> Option<&'static PrinterSettings> {
+    file_config()
+        .printer
+        .iter()
+        .find(|p| p.matches(serial, address))
+}
+
+/// Print options configured for a server endpoint; empty if none.
+pub fn endpoint_options(endpoint: &str) -> PrintOptions {
+    file_config()
+        .endpoint
+        .get(endpoint)
+        .copied()
+        .unwrap_or_default()
+}
+
+pub fn config_path() -> PathBuf {
+    std::env::var_os("RECEIPTS_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_PATH))
+}
+
+/// Parse a config file. Out-of-range print options are dropped with a
+/// warning rather than failing the whole file.
+pub fn parse(text: &str) -> Result<FileConfig, String> {
+    let mut config: FileConfig = toml::from_str(text).map_err(|e| e.to_string())?;
+    for printer in &mut config.printer {
+        printer.drop_invalid_options();
+    }
+    for (name, options) in &mut config.endpoint {
+        for problem in options.drop_invalid() {
+            tracing::warn!("Ignoring [endpoint.{name}] setting: {problem}");
+        }
+    }
+    Ok(config)
+}
+
+fn load() -> FileConfig {
+    let path = config_path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return FileConfig::default(),
+        Err(e) => {
+            tracing::warn!("Failed to read {}: {e}", path.display());
+            return FileConfig::default();
+        }
+    };
+
+    match parse(&text) {
+        Ok(config) => {
+            tracing::info!("Loaded config from {}", path.display());
+            config
+        }
+        Err(e) => {
+            tracing::warn!("Ignoring invalid config {}: {e}", path.display());
+            FileConfig::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::models::GENERIC_MODEL;
+
+    #[test]
+    fn printer_entries_match_serial_or_address() {
+        let config = parse(
+            r#"
+            [[printer]]
+            serial = "X58-0001"
+            paper_width_mm = 58
+
+            [[printer]]
+            address = "tcp://10.0.0.20:9100"
+            chars_per_line = 40
+            density = 2
+            print_speed = 4
+            dither = "atkinson"
+            orientation = "upside_down"
+            roll_length_m = 50
+
+            [endpoint.strip]
+            density = 3
+            speed = 9
+            "#,
+        )
+        .unwrap();
+        let usb = PrinterAddress::Usb {
+            vendor_id: 0x0416,
+            product_id: 0x5011,
+            serial: Some("X58-0001".into()),
+            port: None,
+        };
+        let network: PrinterAddress = "tcp://10.0.0.20:9100".parse().unwrap();
+
+        assert!(config.printer[0].matches(Some("X58-0001"), &usb));
+        assert!(!config.printer[0].matches(None, &usb));
+        assert!(config.printer[1].matches(None, &network));
+        assert!(!config.printer[1].matches(None, &usb));
+
+        assert_eq!(config.printer[0].print_width(&GENERIC_MODEL), PrintWidth::MM_58);
+        let custom = config.printer[1].print_width(&GENERIC_MODEL);
+        assert_eq!((custom.dots, custom.chars), (512, 40));
+        assert_eq!(config.printer[1].print_options().speed, Some(4));
+        assert_eq!(config.printer[1].print_options().dither, Some(Dither::Atkinson));
+        assert_eq!(config.printer[1].orientation, Orientation::UpsideDown);
+        assert_eq!(config.printer[0].orientation, Orientation::Normal);
+        assert_eq!(config.printer[1].roll_length_m, Some(50));
+        assert_eq!(config.endpoint["strip"].density, Some(3));
+    }
+
+    #[test]
+    fn out_of_range_print_options_are_dropped() {
+        let config = parse(
+            r#"
+            [[printer]]
+            serial = "X1"
+            density = 120
+            print_speed = 4
+
+            [endpoint.upload]
+            speed = 0
+            "#,
+        )
+        .unwrap();
+        let options = config.printer[0].print_options();
+        assert_eq!((options.density, options.speed), (None, Some(4)));
+        assert_eq!(config.endpoint["upload"].speed, None);
+    }
+
+    #[test]
+    fn paper_limit_is_optional() {
+        let config = parse("[paper]\nmax_job_mm = 2000\nover_max = \"warn\"").unwrap();
+        assert_eq!(config.paper.over_max, OverMax::Warn);
+        assert_eq!(config.paper.exceeded_by(2400), Some(2000));
+        assert_eq!(config.paper.exceeded_by(2000), None);
+        assert_eq!(FileConfig::default().paper.exceeded_by(u32::MAX), None);
+    }
+
+    #[test]
+    fn resume_settings_default_to_continue() {
+        assert_eq!(FileConfig::default().resume.mode, ResumeMode::Continue);
+        assert_eq!(FileConfig::default().resume.attempts(), 5);
+        let config = parse("[resume]\nmode = \"off\"\nattempts = 3").unwrap();
+        assert_eq!(config.resume.attempts(), 0);
+        let config = parse("[resume]\nmode = \"reprint\"\nattempts = 3").unwrap();
+        assert_eq!(config.resume.attempts(), 3);
+    }
+}
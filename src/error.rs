@@ -2,7 +2,7 @@ use thiserror::Error;
 
 #[derive(Debug, Error, Clone)]
 pub enum AppError {
-    #[error("No Epson printer found on USB")]
+    #[error("No receipt printer found on USB")]
     NoPrinterFound,
 
     #[error("USB error: {0}")]
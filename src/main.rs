@@ -1,4 +1,16 @@
 use receipts::app::{self, App, DisplayMode};
+use receipts::printer::backend::parse_output_args;
+use receipts::printer::connection::PrinterConnection;
+use receipts::printer::discovery;
+use receipts::printer::logo::LogoOp;
+
+const LOGO_USAGE: &str = "\
+Usage: receipts logo list
+       receipts logo store KEY IMAGE
+       receipts logo delete KEY
+Manage logos in the printer's NV graphics memory. KEY is two characters
+(e.g. LG); print a stored logo from markdown with ![logo](nv:LG).
+--output FILE / --dry-run work as for the app.";
 
 fn main() -> iced::Result {
     tracing_subscriber::fmt()
@@ -8,9 +20,25 @@ fn main() -> iced::Result {
         )
         .init();
 
+    if std::env::args().nth(1).as_deref() == Some("logo") {
+        std::process::exit(logo_command(std::env::args().skip(2)));
+    }
+
     let mode = detect_display_mode();
     tracing::info!("Starting Receipts printer manager (mode: {:?})", mode);
 
+    // --dry-run / --output FILE: print somewhere other than a USB printer
+    let output = match parse_output_args(std::env::args().skip(1)) {
+        Ok((output, _)) => output,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    if let Some(ref address) = output {
+        tracing::info!("Printing to {address} instead of USB");
+    }
+
     let mut builder = iced::application(app::title, app::update, app::view)
         .theme(app::theme)
         .subscription(app::subscription);
@@ -21,15 +49,73 @@ fn main() -> iced::Result {
     };
 
     builder.run_with(move || {
-        let app = App::new(mode);
+        let scan_output = output.clone();
+        let app = App::new(mode, output);
         let scan = iced::Task::perform(
-            async { receipts::printer::discovery::scan_for_printers() },
+            async move { receipts::printer::discovery::scan_or_output(scan_output.as_ref()) },
             app::Message::PrintersFound,
         );
         (app, scan)
     })
 }
 
+/// `receipts logo ...`: run one logo operation on the first printer found.
+fn logo_command(args: impl Iterator<Item = String>) -> i32 {
+    let (output, args) = match parse_output_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{e}");
+            return 2;
+        }
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let op = match args.as_slice() {
+        ["list"] => LogoOp::List,
+        ["store", key, path] => match std::fs::read(path) {
+            Ok(image) => LogoOp::Store {
+                key: key.to_string(),
+                image,
+            },
+            Err(e) => {
+                eprintln!("Failed to read {path}: {e}");
+                return 1;
+            }
+        },
+        ["delete", key] => LogoOp::Delete {
+            key: key.to_string(),
+        },
+        _ => {
+            eprintln!("{LOGO_USAGE}");
+            return 2;
+        }
+    };
+
+    let printer = match discovery::scan_or_output(output.as_ref()) {
+        Ok(printers) => printers.into_iter().next(),
+        Err(e) => {
+            eprintln!("Printer scan failed: {e}");
+            return 1;
+        }
+    };
+    let Some(printer) = printer else {
+        eprintln!("No printer found");
+        return 1;
+    };
+
+    let result = PrinterConnection::open(&printer)
+        .and_then(|mut conn| conn.run_logo_op(&op).map_err(|e| e.to_string()));
+    match result {
+        Ok(message) => {
+            println!("{message}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", printer.model_name);
+            1
+        }
+    }
+}
+
 fn detect_display_mode() -> DisplayMode {
     for arg in std::env::args().skip(1) {
         match arg.as_str() {
@@ -1,8 +1,68 @@
 use std::path::Path;
 
+use crate::printer::lp::{self, LpDevice};
+
 const UDEV_RULES_PATH: &str = "/etc/udev/rules.d/99-epson-receipt.rules";
+const USBLP_BLACKLIST_FIX: &str = "echo 'blacklist usblp' | sudo tee /etc/modprobe.d/no-usblp.conf \
+     && sudo rmmod usblp";
 
+/// Check printer access for whichever mode is in use: the kernel `usblp`
+/// driver (`/dev/usb/lp*` present) or direct libusb access.
 pub fn check_usb_access() -> Vec<String> {
+    let lp_devices = lp::list_lp_devices();
+    if lp_devices.is_empty() {
+        check_libusb_access()
+    } else {
+        check_lp_access(&lp_devices)
+    }
+}
+
+fn check_lp_access(devices: &[LpDevice]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for dev in devices {
+        let open = std::fs::OpenOptions::new().write(true).open(&dev.path);
+        if let Err(e) = open {
+            if e.kind() == std::io::ErrorKind::PermissionDenied {
+                warnings.push(format!(
+                    "Linux: {} is not writable. Add your user to the 'lp' group: \
+                     sudo usermod -aG lp $USER (then log out and back in).",
+                    dev.path.display()
+                ));
+            }
+        }
+    }
+
+    let paths: Vec<String> = devices.iter().map(|d| d.path.display().to_string()).collect();
+    warnings.push(format!(
+        "Linux: printer is bound to the kernel usblp driver ({}). \
+         Printing goes through the lp device; status read-back depends on the printer. \
+         For direct USB access instead, run: {USBLP_BLACKLIST_FIX}",
+        paths.join(", ")
+    ));
+
+    warnings
+}
+
+/// Error text for a failed libusb open. When `usblp` holds the printer the
+/// open fails as busy — point at the lp device and the blacklist fix.
+pub fn usblp_busy_hint(vendor_id: u16, product_id: u16, original_error: &str) -> String {
+    let base = format!("Failed to open USB device {vendor_id:04x}:{product_id:04x}: {original_error}");
+    let claimed = lp::list_lp_devices()
+        .into_iter()
+        .find(|d| d.vendor_id == Some(vendor_id) && d.product_id == Some(product_id));
+
+    match claimed {
+        Some(dev) => format!(
+            "{base}\nThe kernel usblp driver has claimed this printer. \
+             Use lp:{} instead, or run: {USBLP_BLACKLIST_FIX}",
+            dev.path.display()
+        ),
+        None => base,
+    }
+}
+
+fn check_libusb_access() -> Vec<String> {
     let mut warnings = Vec::new();
 
     if !Path::new(UDEV_RULES_PATH).exists() {
@@ -0,0 +1,743 @@
+//! The printer actor.
+//!
+//! One thread owns the printer connection; the GUI and the server talk to it
+//! through a `PrinterHandle`. Jobs queue on a channel and run one at a time,
+//! each reporting `JobEvent`s as it goes — started, progress after every
+//! chunk written, finished. A job can be cancelled while it waits or between
+//! raster bands. Status polls and printer changes go through the same
+//! channel, so nothing else ever blocks on the device.
+//!
+//! If the printer drops off the bus part-way through an image (a USB reset),
+//! the actor reconnects with backoff and finishes the job from the band after
+//! the last one printed, or prints it again, as `[resume]` in the config says.
+//!
+//! The connection stays open across jobs. On macOS the kernel holds the USB
+//! interface for ~200ms after close, causing `kIOReturnExclusiveAccess` on
+//! rapid reopen; keeping it open avoids this entirely.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use escpos::driver::Driver;
+use tokio::sync::{broadcast, oneshot};
+
+use crate::config::{self, ResumeMode, ResumeSettings};
+use crate::printer::connection::{JobError, PrinterConnection, Unfinished};
+use crate::printer::discovery::DiscoveredPrinter;
+use crate::printer::encoder::PrintOptions;
+use crate::printer::logo::LogoOp;
+use crate::printer::rich_print::PrintCommand;
+use crate::printer::status::PrinterStatus;
+use crate::receipt_markdown::ReceiptBlock;
+
+/// How long a status poll waits for an idle actor to answer.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Wait before the first reconnection after a reset, doubling each attempt.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(8);
+
+pub type JobId = u64;
+
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+
+/// What a job prints (or, for logos, does).
+#[derive(Debug, Clone)]
+pub enum JobPayload {
+    /// A rich receipt, then a cut.
+    Receipt(Vec<ReceiptBlock>),
+    /// Text as a continuous log, without a cut.
+    Log(Vec<ReceiptBlock>),
+    /// Text and an optional photo, then a cut.
+    Message {
+        blocks: Vec<ReceiptBlock>,
+        image: Option<Vec<u8>>,
+    },
+    /// A photo strip frame: the image and `feed` lines, without a cut.
+    Strip { image: Vec<u8>, feed: u8, bright: bool },
+    /// A pre-built command list, sent as-is.
+    Commands(Vec<PrintCommand>),
+    Logo(LogoOp),
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: JobId,
+    pub payload: JobPayload,
+    /// Density and speed over the printer's defaults.
+    pub options: PrintOptions,
+}
+
+impl Job {
+    /// A job with a fresh ID.
+    pub fn new(payload: JobPayload, options: PrintOptions) -> Self {
+        Self {
+            id: NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed),
+            payload,
+            options,
+        }
+    }
+}
+
+/// A finished job: the outcome of a logo operation, or empty for prints.
+pub type JobResult = Result<String, JobError>;
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    Started(JobId),
+    /// `sent` of the job's `total` chunks have been written.
+    Progress { id: JobId, sent: usize, total: usize },
+    Finished { id: JobId, result: JobResult },
+}
+
+/// Where a reply goes: a oneshot for async callers, a std channel for
+/// threads (which may be inside the runtime, where a oneshot can't block).
+type Reply<T> = Box<dyn FnOnce(T) + Send>;
+
+enum Request {
+    Open(DiscoveredPrinter, Reply<Result<(), String>>),
+    Close,
+    Print(Job, Option<oneshot::Sender<JobResult>>),
+    PollStatus(Reply<Result<Option<PrinterStatus>, String>>),
+    Shutdown,
+}
+
+/// State the handle and the actor thread both see.
+#[derive(Default)]
+struct Shared {
+    busy: AtomicBool,
+    stopping: AtomicBool,
+    /// Queued jobs to skip when their turn comes.
+    cancelled: Mutex<HashSet<JobId>>,
+    /// The job being printed, and the flag that stops it between bands.
+    current: Mutex<Option<(JobId, Arc<AtomicBool>)>>,
+}
+
+/// A handle on the printer actor. Clones share the same thread.
+#[derive(Clone)]
+pub struct PrinterHandle {
+    requests: Sender<Request>,
+    events: broadcast::Sender<JobEvent>,
+    shared: Arc<Shared>,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl PrinterHandle {
+    /// Start the actor thread, with no printer until `open` is called.
+    pub fn spawn() -> Self {
+        let (requests, rx) = mpsc::channel();
+        let events = broadcast::channel(64).0;
+        let shared = Arc::new(Shared::default());
+        let actor = Actor {
+            printer: None,
+            conn: None,
+            events: events.clone(),
+            shared: shared.clone(),
+        };
+        let thread = std::thread::Builder::new()
+            .name("printer".into())
+            .spawn(move || actor.run(rx))
+            .expect("failed to spawn printer thread");
+        Self {
+            requests,
+            events,
+            shared,
+            thread: Arc::new(Mutex::new(Some(thread))),
+        }
+    }
+
+    /// Print on `printer` from now on, connecting right away. The printer is
+    /// kept even if it can't be opened yet; the next job tries again.
+    /// Reopening the printer already in use keeps its connection.
+    pub async fn open(&self, printer: &DiscoveredPrinter) -> Result<(), String> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Request::Open(printer.clone(), Box::new(move |opened| {
+            let _ = reply.send(opened);
+        })))?;
+        rx.await.map_err(|_| "Printer thread stopped".to_string())?
+    }
+
+    /// `open`, for threads that can block.
+    pub fn open_blocking(&self, printer: &DiscoveredPrinter) -> Result<(), String> {
+        let (reply, rx) = mpsc::channel();
+        self.send(Request::Open(printer.clone(), Box::new(move |opened| {
+            let _ = reply.send(opened);
+        })))?;
+        rx.recv().map_err(|_| "Printer thread stopped".to_string())?
+    }
+
+    /// Drop the printer and its connection, e.g. once it's unplugged.
+    pub fn close(&self) {
+        let _ = self.send(Request::Close);
+    }
+
+    /// Queue a job. Its outcome arrives as a `JobEvent::Finished`.
+    pub fn submit(&self, job: Job) {
+        let id = job.id;
+        if self.send(Request::Print(job, None)).is_err() {
+            let _ = self.events.send(JobEvent::Finished {
+                id,
+                result: Err(JobError::Cancelled),
+            });
+        }
+    }
+
+    /// Queue a job and wait for its outcome. Jobs sent after `shutdown`
+    /// come back cancelled.
+    pub async fn run(&self, job: Job) -> JobResult {
+        let (reply, rx) = oneshot::channel();
+        self.send(Request::Print(job, Some(reply)))
+            .map_err(|_| JobError::Cancelled)?;
+        rx.await.unwrap_or(Err(JobError::Cancelled))
+    }
+
+    /// `run`, for threads outside the async runtime.
+    pub fn run_blocking(&self, job: Job) -> JobResult {
+        let (reply, rx) = oneshot::channel();
+        self.send(Request::Print(job, Some(reply)))
+            .map_err(|_| JobError::Cancelled)?;
+        rx.blocking_recv().unwrap_or(Err(JobError::Cancelled))
+    }
+
+    /// Cancel a job: skipped if it hasn't started, stopped before its next
+    /// chunk if it has.
+    pub fn cancel(&self, id: JobId) {
+        // Held while marking, so the job can't start in between
+        let current = self.shared.current.lock().unwrap_or_else(|e| e.into_inner());
+        match current.as_ref() {
+            Some((current_id, flag)) if *current_id == id => flag.store(true, Ordering::Relaxed),
+            _ => {
+                let mut cancelled = self.shared.cancelled.lock().unwrap_or_else(|e| e.into_inner());
+                cancelled.insert(id);
+            }
+        }
+    }
+
+    /// The printer's real-time status. `Ok(None)` while a job is printing,
+    /// with no printer, or when the printer can't report it.
+    pub async fn poll_status(&self) -> Result<Option<PrinterStatus>, String> {
+        if self.shared.busy.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let (reply, rx) = oneshot::channel();
+        self.send(Request::PollStatus(Box::new(move |status| {
+            let _ = reply.send(status);
+        })))?;
+        match tokio::time::timeout(STATUS_TIMEOUT, rx).await {
+            Ok(Ok(status)) => status,
+            _ => Ok(None),
+        }
+    }
+
+    /// `poll_status`, for threads that can block.
+    pub fn poll_status_blocking(&self) -> Result<Option<PrinterStatus>, String> {
+        if self.shared.busy.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let (reply, rx) = mpsc::channel();
+        self.send(Request::PollStatus(Box::new(move |status| {
+            let _ = reply.send(status);
+        })))?;
+        rx.recv_timeout(STATUS_TIMEOUT).unwrap_or(Ok(None))
+    }
+
+    /// Progress and completion of every job.
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stop the job in progress, cancel everything queued, close the printer
+    /// and wait for the thread to finish.
+    pub fn shutdown(&self) {
+        self.shared.stopping.store(true, Ordering::Relaxed);
+        let current = self.shared.current.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, flag)) = current.as_ref() {
+            flag.store(true, Ordering::Relaxed);
+        }
+        drop(current);
+        let _ = self.send(Request::Shutdown);
+        let thread = self.thread.lock().ok().and_then(|mut t| t.take());
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+
+    fn send(&self, request: Request) -> Result<(), String> {
+        self.requests
+            .send(request)
+            .map_err(|_| "Printer thread stopped".to_string())
+    }
+}
+
+struct Actor {
+    printer: Option<DiscoveredPrinter>,
+    conn: Option<PrinterConnection>,
+    events: broadcast::Sender<JobEvent>,
+    shared: Arc<Shared>,
+}
+
+impl Actor {
+    fn run(mut self, requests: Receiver<Request>) {
+        // Ends on shutdown, or once every handle is gone
+        while let Ok(request) = requests.recv() {
+            match request {
+                Request::Open(printer, reply) => {
+                    reply(self.open(printer));
+                }
+                Request::Close => {
+                    if self.conn.take().is_some() {
+                        tracing::info!("Closing printer connection");
+                    }
+                    self.printer = None;
+                }
+                Request::Print(job, reply) => {
+                    let result = self.print(job.clone());
+                    // Broadcast first, so whoever is waiting on the reply
+                    // has the whole event stream once it arrives
+                    let _ = self.events.send(JobEvent::Finished {
+                        id: job.id,
+                        result: result.clone(),
+                    });
+                    if let Some(reply) = reply {
+                        let _ = reply.send(result);
+                    }
+                }
+                Request::PollStatus(reply) => {
+                    let status = match self.conn.as_mut() {
+                        Some(conn) => conn.poll_status(),
+                        None => Ok(None),
+                    };
+                    reply(status);
+                }
+                Request::Shutdown => break,
+            }
+        }
+        if self.conn.take().is_some() {
+            tracing::info!("Printer thread stopped; connection closed");
+        }
+    }
+
+    fn open(&mut self, printer: DiscoveredPrinter) -> Result<(), String> {
+        if self.conn.is_some() && self.printer.as_ref().map(|p| &p.address) == Some(&printer.address) {
+            tracing::debug!("Reusing existing connection to {}", printer.model_name);
+            self.printer = Some(printer);
+            return Ok(());
+        }
+        if self.conn.take().is_some() {
+            tracing::info!("Switching printer — closing old connection");
+        }
+        tracing::info!(
+            "Opening persistent connection to {} ({})",
+            printer.model_name,
+            printer.address
+        );
+        self.printer = Some(printer.clone());
+        self.conn = Some(PrinterConnection::open(&printer)?);
+        Ok(())
+    }
+
+    /// Run one job, unless it was cancelled while queued.
+    fn print(&mut self, job: Job) -> JobResult {
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let mut current = self.shared.current.lock().unwrap_or_else(|e| e.into_inner());
+            let skipped = self
+                .shared
+                .cancelled
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&job.id);
+            if skipped || self.shared.stopping.load(Ordering::Relaxed) {
+                tracing::info!("Job {} cancelled before printing", job.id);
+                return Err(JobError::Cancelled);
+            }
+            *current = Some((job.id, cancel.clone()));
+        }
+        self.shared.busy.store(true, Ordering::Relaxed);
+        let _ = self.events.send(JobEvent::Started(job.id));
+
+        let result = self.print_job(&job, &cancel);
+
+        self.shared.busy.store(false, Ordering::Relaxed);
+        *self.shared.current.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        result
+    }
+
+    /// Open the connection if needed, check the printer is ready, and send.
+    /// On an I/O error the connection is dropped so the next job reopens it —
+    /// unless the printer says it stopped for paper or an open cover, which
+    /// is reported as `JobError::NotReady` with the connection kept.
+    fn print_job(&mut self, job: &Job, cancel: &Arc<AtomicBool>) -> JobResult {
+        let Some(printer) = self.printer.clone() else {
+            return Err(JobError::Failed("No printer".into()));
+        };
+
+        if self.conn.is_none() {
+            tracing::info!(
+                "No active connection — opening {} ({})",
+                printer.address,
+                printer.model_name
+            );
+            self.conn = Some(PrinterConnection::open(&printer)?);
+        }
+        // Liveness check: if the pipe is stale, reopen before printing
+        if let Some(Err(e)) = self.conn.as_mut().map(|c| c.ping()) {
+            tracing::warn!("USB liveness check failed, reconnecting: {e}");
+            self.conn = None;
+            self.conn = Some(PrinterConnection::open(&printer)?);
+        }
+        let Some(conn) = self.conn.as_mut() else {
+            return Err(JobError::Failed("No printer".into()));
+        };
+
+        conn.set_job_options(job.options);
+        // Paper out or cover open: fail the job but keep the connection
+        conn.check_ready()?;
+
+        let events = self.events.clone();
+        let id = job.id;
+        conn.watch_job(cancel.clone(), move |sent, total| {
+            let _ = events.send(JobEvent::Progress { id, sent, total });
+        });
+        let max_chars = printer.print_width().chars;
+        let result: JobResult = match &job.payload {
+            JobPayload::Receipt(blocks) => conn.print_rich(blocks, max_chars).map(|_| String::new()).map_err(Into::into),
+            JobPayload::Log(blocks) => conn.print_no_cut(blocks, max_chars).map(|_| String::new()).map_err(Into::into),
+            JobPayload::Message { blocks, image } => conn
+                .print_website_message(blocks, max_chars, image.as_deref())
+                .map(|_| String::new())
+                .map_err(Into::into),
+            JobPayload::Strip { image, feed, bright } => conn
+                .print_image_no_cut(image, *feed, *bright)
+                .map(|_| String::new()),
+            JobPayload::Commands(commands) => conn.print_commands(commands).map(|_| String::new()),
+            JobPayload::Logo(op) => conn.run_logo_op(op),
+        };
+        conn.unwatch_job();
+
+        match result {
+            Ok(message) => {
+                tracing::info!("Print job {id} completed successfully");
+                Ok(message)
+            }
+            Err(_) if cancel.load(Ordering::Relaxed) => {
+                tracing::info!("Print job {id} cancelled");
+                Err(JobError::Cancelled)
+            }
+            // Nothing was sent; the connection is fine
+            Err(JobError::Invalid(e)) => {
+                tracing::warn!("Print job {id} can't be printed: {e}");
+                Err(JobError::Invalid(e))
+            }
+            // Paper ran out (or the cover was opened) mid-job
+            Err(e) if matches!(conn.check_ready(), Err(JobError::NotReady(_))) => {
+                let status = conn.last_status().cloned().unwrap_or_default();
+                tracing::warn!("Print stopped ({}): {e}", status.summary());
+                Err(JobError::NotReady(status))
+            }
+            Err(e) => {
+                let e = e.to_string();
+                tracing::warn!("Print failed, closing connection for recovery: {e}");
+                let unfinished = conn.take_unfinished().filter(Unfinished::has_raster);
+                self.conn = None;
+                match unfinished {
+                    Some(unfinished) => self.resume(&printer, job, unfinished, cancel, e),
+                    None => Err(JobError::Failed(e)),
+                }
+            }
+        }
+    }
+
+    /// Finish an image job cut off by a connection error: reconnect with
+    /// backoff, re-initialise the printer and send the rest of the job (or
+    /// all of it again, per `[resume]`). Fails with `error` once the
+    /// attempts run out.
+    fn resume(
+        &mut self,
+        printer: &DiscoveredPrinter,
+        job: &Job,
+        unfinished: Unfinished,
+        cancel: &Arc<AtomicBool>,
+        error: String,
+    ) -> JobResult {
+        let settings = &config::file_config().resume;
+        if settings.attempts() == 0 {
+            return Err(JobError::Failed(error));
+        }
+        let conn = resume_job(
+            || PrinterConnection::open(printer),
+            |attempt| self.wait_to_reconnect(reconnect_delay(attempt), cancel),
+            settings,
+            &self.events,
+            job,
+            unfinished,
+            cancel,
+        )?;
+        self.conn = Some(conn);
+        Ok(String::new())
+    }
+
+    /// Sleep before a reconnection attempt. False if the job is cancelled
+    /// or the actor is stopping meanwhile.
+    fn wait_to_reconnect(&self, delay: Duration, cancel: &AtomicBool) -> bool {
+        let deadline = std::time::Instant::now() + delay;
+        loop {
+            if cancel.load(Ordering::Relaxed) || self.shared.stopping.load(Ordering::Relaxed) {
+                return false;
+            }
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            std::thread::sleep((deadline - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+/// The reconnect-and-resume loop behind `Actor::resume`, returning the new
+/// connection once the job is done. A printer that can't report its status
+/// or isn't ready yet (paper out or cover open, common right after a reset)
+/// counts as a failed attempt; the job is kept for the next one. Fails with
+/// the last attempt's error.
+fn resume_job<D: Driver>(
+    mut open: impl FnMut() -> Result<PrinterConnection<D>, String>,
+    mut wait: impl FnMut(u32) -> bool,
+    settings: &ResumeSettings,
+    events: &broadcast::Sender<JobEvent>,
+    job: &Job,
+    mut unfinished: Unfinished,
+    cancel: &Arc<AtomicBool>,
+) -> Result<PrinterConnection<D>, JobError> {
+    let mut error = String::new();
+    for attempt in 0..settings.attempts() {
+        if !wait(attempt) {
+            tracing::info!("Print job {} cancelled while reconnecting", job.id);
+            return Err(JobError::Cancelled);
+        }
+        let mut conn = match open() {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::info!("Reconnect attempt {} failed: {e}", attempt + 1);
+                error = e;
+                continue;
+            }
+        };
+        conn.set_job_options(job.options);
+        if let Err(e) = conn.check_ready() {
+            tracing::info!("Printer not ready to resume job {} yet: {e}", job.id);
+            error = e.to_string();
+            continue;
+        }
+        tracing::info!(
+            "Reconnected to {}; resuming job {} after {} bands",
+            conn.model_name,
+            job.id,
+            unfinished.bands_done()
+        );
+
+        let events = events.clone();
+        let id = job.id;
+        conn.watch_job(cancel.clone(), move |sent, total| {
+            let _ = events.send(JobEvent::Progress { id, sent, total });
+        });
+        let rest = unfinished.resumed(settings.mode, conn.dialect());
+        let result = conn.send(&rest);
+        conn.unwatch_job();
+        match result {
+            Ok(()) => {
+                tracing::info!("Print job {id} resumed and completed");
+                return Ok(conn);
+            }
+            Err(_) if cancel.load(Ordering::Relaxed) => return Err(JobError::Cancelled),
+            Err(e) => {
+                tracing::warn!("Resumed job {id} failed again: {e}");
+                error = e;
+                match conn.take_unfinished() {
+                    // Picks up from the continuation next time
+                    Some(next) if settings.mode == ResumeMode::Continue => unfinished = next,
+                    _ => {}
+                }
+            }
+        }
+    }
+    Err(JobError::Failed(error))
+}
+
+/// Backoff before reconnection attempt `attempt` (from 0).
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_DELAY
+        .saturating_mul(1 << attempt.min(8))
+        .min(MAX_RECONNECT_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::backend::{MemoryBackend, PrinterAddress};
+    use crate::printer::encoder::{Chunk, EncodedJob};
+    use crate::receipt_markdown::parse_receipt_markdown;
+
+    /// A network printer that answers status queries from a script.
+    struct ScriptedPrinter {
+        out: MemoryBackend,
+        replies: Mutex<Vec<u8>>,
+    }
+
+    impl Driver for ScriptedPrinter {
+        fn name(&self) -> String {
+            "Scripted".to_string()
+        }
+
+        fn write(&self, data: &[u8]) -> escpos::errors::Result<()> {
+            self.out.write(data)
+        }
+
+        fn read(&self, buf: &mut [u8]) -> escpos::errors::Result<usize> {
+            let mut replies = self.replies.lock().unwrap();
+            if replies.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = replies.remove(0);
+            Ok(1)
+        }
+
+        fn flush(&self) -> escpos::errors::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn jobs_report_progress_then_finish() {
+        let actor = PrinterHandle::spawn();
+        let mut events = actor.subscribe();
+        actor
+            .open_blocking(&DiscoveredPrinter::for_output(PrinterAddress::Memory))
+            .unwrap();
+
+        let job = Job::new(
+            JobPayload::Receipt(parse_receipt_markdown("# Hello\nworld")),
+            PrintOptions::default(),
+        );
+        let id = job.id;
+        assert_eq!(actor.run_blocking(job), Ok(String::new()));
+
+        assert!(matches!(events.try_recv(), Ok(JobEvent::Started(started)) if started == id));
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            last = Some(event);
+            if let Some(JobEvent::Progress { sent, total, .. }) = &last {
+                assert!(sent <= total);
+            }
+        }
+        assert!(matches!(last, Some(JobEvent::Finished { id: done, result: Ok(_) }) if done == id));
+        actor.shutdown();
+    }
+
+    #[test]
+    fn async_requests_await_the_printer_thread() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let actor = PrinterHandle::spawn();
+        runtime.block_on(async {
+            let printer = DiscoveredPrinter::for_output(PrinterAddress::Memory);
+            actor.open(&printer).await.unwrap();
+            // A dry run has no status to report
+            assert!(matches!(actor.poll_status().await, Ok(None)));
+        });
+        actor.shutdown();
+    }
+
+    #[test]
+    fn reconnects_back_off_up_to_a_limit() {
+        let delays: Vec<u64> = (0..7).map(|n| reconnect_delay(n).as_millis() as u64).collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 8000, 8000, 8000]);
+    }
+
+    #[test]
+    fn cancelled_and_late_jobs_do_not_print() {
+        let actor = PrinterHandle::spawn();
+        actor
+            .open_blocking(&DiscoveredPrinter::for_output(PrinterAddress::Memory))
+            .unwrap();
+
+        let job = Job::new(JobPayload::Commands(vec![PrintCommand::Feed]), PrintOptions::default());
+        actor.cancel(job.id);
+        assert_eq!(actor.run_blocking(job), Err(JobError::Cancelled));
+
+        actor.shutdown();
+        let late = Job::new(JobPayload::Commands(vec![PrintCommand::Feed]), PrintOptions::default());
+        assert_eq!(actor.run_blocking(late), Err(JobError::Cancelled));
+    }
+
+    #[test]
+    fn undecodable_images_are_invalid_not_failed() {
+        let actor = PrinterHandle::spawn();
+        actor
+            .open_blocking(&DiscoveredPrinter::for_output(PrinterAddress::Memory))
+            .unwrap();
+
+        let strip = JobPayload::Strip {
+            image: b"not an image".to_vec(),
+            feed: 3,
+            bright: false,
+        };
+        let result = actor.run_blocking(Job::new(strip, PrintOptions::default()));
+        assert!(matches!(result, Err(JobError::Invalid(_))), "{result:?}");
+
+        let next = Job::new(JobPayload::Commands(vec![PrintCommand::Feed]), PrintOptions::default());
+        assert_eq!(actor.run_blocking(next), Ok(String::new()));
+    }
+
+    #[test]
+    fn resuming_waits_out_a_printer_that_is_not_ready_yet() {
+        let printer = DiscoveredPrinter::for_output("tcp://printer:9100".parse().unwrap());
+        let out = MemoryBackend::default();
+        // Paper out on the first reconnect, ready on the second
+        let mut scripts = vec![vec![0x16, 0x12, 0x12], vec![0x1a, 0x32, 0x72]];
+        let mut opened = 0;
+        let open = || {
+            opened += 1;
+            let replies = scripts.pop().ok_or_else(|| "no printer".to_string())?;
+            let driver = ScriptedPrinter {
+                out: out.clone(),
+                replies: Mutex::new(replies),
+            };
+            Ok(PrinterConnection::with_driver(driver, &printer))
+        };
+
+        let job = Job::new(JobPayload::Commands(vec![PrintCommand::Feed]), PrintOptions::default());
+        let chunk = |bytes: &[u8]| Chunk {
+            bytes: bytes.to_vec(),
+            raster_rows: 1,
+        };
+        let unfinished = Unfinished {
+            job: EncodedJob {
+                chunks: vec![chunk(b"printed"), chunk(b"rest")],
+            },
+            next_chunk: 1,
+        };
+        let (events, _) = broadcast::channel(16);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let result = resume_job(
+            open,
+            |_| true,
+            &ResumeSettings::default(),
+            &events,
+            &job,
+            unfinished,
+            &cancel,
+        );
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(opened, 2);
+        let sent = out.contents();
+        assert!(sent.ends_with(b"rest"));
+        assert!(!sent.windows(7).any(|w| w == b"printed"));
+    }
+}
@@ -0,0 +1,208 @@
+//! Automatic Status Back (`GS a`).
+//!
+//! With ASB enabled the printer sends a 4-byte status packet on its own
+//! whenever the paper sensor, cover or error state changes — including between
+//! jobs, when nobody is polling. A reader thread owns the read side of the
+//! driver: it decodes those packets into `PrinterStatus` change events and
+//! passes any other byte (a `DLE EOT` reply) back to the connection.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use escpos::driver::Driver;
+
+use crate::printer::status::{self, PrinterStatus, StatusChange};
+
+/// `GS a n`: report online/offline, error and roll-paper sensor changes.
+pub const ENABLE_ASB: [u8; 3] = [0x1d, b'a', 0x0e];
+pub const DISABLE_ASB: [u8; 3] = [0x1d, b'a', 0x00];
+
+/// How long to wait for a `DLE EOT` reply routed through the reader.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+/// Pause between reads on outputs whose `read` returns immediately.
+const IDLE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// One decoded item from the printer's read channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    /// A single-byte real-time status reply.
+    Reply(u8),
+    Status(PrinterStatus),
+}
+
+/// Splits the read stream into ASB packets and real-time replies.
+///
+/// An ASB packet starts with a byte matching `0xx1 xx00` (bits 1 and 4 of a
+/// `DLE EOT` reply are fixed at 1, so the two can't be confused). Header
+/// responses (`37h ... NUL`, e.g. the NV graphics key list) are passed
+/// through whole, since their data bytes can look like a packet start.
+#[derive(Debug, Default)]
+pub struct AsbParser {
+    packet: Vec<u8>,
+    in_block: bool,
+}
+
+impl AsbParser {
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Incoming> {
+        let mut out = Vec::new();
+        for &b in bytes {
+            if self.in_block {
+                self.in_block = b != 0x00;
+                out.push(Incoming::Reply(b));
+            } else if !self.packet.is_empty() {
+                self.packet.push(b);
+                if self.packet.len() == 4 {
+                    out.push(Incoming::Status(decode_packet(&self.packet)));
+                    self.packet.clear();
+                }
+            } else if b & 0x93 == 0x10 {
+                self.packet.push(b);
+            } else if b == 0x37 {
+                self.in_block = true;
+                out.push(Incoming::Reply(b));
+            } else {
+                out.push(Incoming::Reply(b));
+            }
+        }
+        out
+    }
+}
+
+/// Decode a 4-byte ASB packet (Epson TM layout).
+pub fn decode_packet(packet: &[u8]) -> PrinterStatus {
+    let (b1, b2, b3) = (packet[0], packet[1], packet[2]);
+    PrinterStatus {
+        online: b1 & 0x08 == 0,
+        cover_open: b1 & 0x20 != 0,
+        // Autocutter, unrecoverable and auto-recoverable errors
+        error: b2 & 0x68 != 0,
+        paper_near_end: b3 & 0x03 != 0,
+        paper_out: b3 & 0x0c != 0,
+    }
+}
+
+/// Background reader for one connection. Stops (and joins) on drop.
+pub struct AsbReader {
+    stop: Arc<AtomicBool>,
+    replies: Receiver<u8>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AsbReader {
+    /// Start reading from `driver`, publishing status changes for `printer`
+    /// under its `key`.
+    pub fn spawn<D>(driver: Arc<D>, printer: String, key: String) -> Self
+    where
+        D: Driver + Send + Sync + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (reply_tx, replies) = mpsc::channel();
+        let thread_stop = stop.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut parser = AsbParser::default();
+            let mut last: Option<PrinterStatus> = None;
+            let mut buf = [0u8; 64];
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                let n = match driver.read(&mut buf) {
+                    Ok(0) | Err(_) => {
+                        // Timeouts are normal; a dead device is noticed by the
+                        // next write and the connection is dropped.
+                        std::thread::sleep(IDLE_INTERVAL);
+                        continue;
+                    }
+                    Ok(n) => n,
+                };
+                for item in parser.feed(&buf[..n]) {
+                    match item {
+                        Incoming::Reply(b) => {
+                            let _ = reply_tx.send(b);
+                        }
+                        Incoming::Status(status) if last.as_ref() != Some(&status) => {
+                            tracing::info!("{printer}: {}", status.summary());
+                            last = Some(status.clone());
+                            status::publish(StatusChange {
+                                printer: printer.clone(),
+                                key: key.clone(),
+                                status,
+                            });
+                        }
+                        Incoming::Status(_) => {}
+                    }
+                }
+            }
+        });
+
+        Self {
+            stop,
+            replies,
+            handle: Some(handle),
+        }
+    }
+
+    /// Wait for the next real-time reply byte.
+    pub fn reply(&self) -> Result<u8, String> {
+        match self.replies.recv_timeout(REPLY_TIMEOUT) {
+            Ok(b) => Ok(b),
+            Err(RecvTimeoutError::Timeout) => Err("No status reply".to_string()),
+            Err(RecvTimeoutError::Disconnected) => Err("Status reader stopped".to_string()),
+        }
+    }
+
+    /// Drop replies that arrived after their request timed out.
+    pub fn clear_replies(&self) {
+        while self.replies.try_recv().is_ok() {}
+    }
+}
+
+impl Drop for AsbReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wait for the in-flight read so the device is released on return
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_and_replies_are_separated() {
+        let mut parser = AsbParser::default();
+        // Reply to DLE EOT 1, then a packet split across reads
+        let mut items = parser.feed(&[0x16, 0x10, 0x00]);
+        assert_eq!(items, vec![Incoming::Reply(0x16)]);
+
+        items = parser.feed(&[0x03, 0x00, 0x12]);
+        assert_eq!(items.len(), 2);
+        let Incoming::Status(status) = &items[0] else {
+            panic!("expected a status packet: {items:?}");
+        };
+        assert_eq!(status.summary(), "Paper Low");
+        assert_eq!(items[1], Incoming::Reply(0x12));
+    }
+
+    #[test]
+    fn header_responses_pass_through() {
+        let mut parser = AsbParser::default();
+        // Key list "P0": '0' would otherwise start an ASB packet
+        let items = parser.feed(&[0x37, 0x72, 0x40, b'P', b'0', 0x00, 0x14, 0, 0, 0]);
+        assert_eq!(items.len(), 7);
+        assert!(items[..6].iter().all(|i| matches!(i, Incoming::Reply(_))));
+        assert!(matches!(items[6], Incoming::Status(_)));
+    }
+
+    #[test]
+    fn cover_open_packet() {
+        let status = decode_packet(&[0x38, 0x00, 0x00, 0x00]);
+        assert!(status.cover_open && !status.online);
+        assert_eq!(status.summary(), "Cover Open");
+    }
+}
@@ -0,0 +1,461 @@
+//! Output backends for `PrinterConnection`.
+//!
+//! A `PrinterAddress` says where print jobs go — a USB, line-printer or
+//! network printer, a file or FIFO, an in-memory buffer, or the virtual printer. `Backend` is the opened form of
+//! an address and implements the `escpos` `Driver` trait, so the connection code
+//! is the same whether or not a real printer is attached.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use escpos::driver::Driver;
+use escpos::errors::Result as DriverResult;
+
+use crate::printer::lp::LpBackend;
+use crate::printer::network::{self, NetworkBackend};
+use crate::printer::usb::UsbBackend;
+use crate::printer::virtual_printer::VirtualDriver;
+
+/// Where a printer's bytes go.
+///
+/// Parses from and displays as `usb:04b8:0e28`, `lp:/dev/usb/lp0`,
+/// `tcp://10.0.0.5:9100`,
+/// `file:/tmp/out.bin`, `memory`, or `virtual:/tmp/out.png`. A USB address
+/// can name one of several identical printers by serial number
+/// (`usb:04b8:0e28?serial=X1`) or by the port it's plugged into
+/// (`usb:04b8:0e28?port=001-2.3`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PrinterAddress {
+    Usb {
+        vendor_id: u16,
+        product_id: u16,
+        serial: Option<String>,
+        port: Option<String>,
+    },
+    /// Kernel `usblp` character device.
+    Lp(PathBuf),
+    /// Raw TCP, usually port 9100.
+    Network { host: String, port: u16 },
+    /// Append raw ESC/POS bytes to a file or named pipe.
+    File(PathBuf),
+    /// Keep bytes in memory — a dry run.
+    Memory,
+    /// Render through the virtual printer to a PNG of the last job.
+    Virtual(PathBuf),
+}
+
+impl PrinterAddress {
+    /// Open the address as a `Backend`. The virtual printer renders
+    /// `width_dots` wide, the print width of the printer it stands in for.
+    pub fn open(&self, width_dots: u16) -> Result<Backend, String> {
+        match self {
+            PrinterAddress::Usb {
+                vendor_id,
+                product_id,
+                serial,
+                port,
+            } => UsbBackend::open(*vendor_id, *product_id, serial.as_deref(), port.as_deref())
+                .map(|usb| Backend::Usb(Box::new(usb)))
+                .map_err(|err_str| {
+                    #[cfg(target_os = "macos")]
+                    {
+                        crate::platform::macos::cups_conflict_hint(*product_id, &err_str)
+                    }
+                    #[cfg(target_os = "linux")]
+                    {
+                        crate::platform::linux::usblp_busy_hint(*vendor_id, *product_id, &err_str)
+                    }
+                    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+                    {
+                        format!(
+                            "Failed to open USB device {vendor_id:04x}:{product_id:04x}: {err_str}"
+                        )
+                    }
+                }),
+            PrinterAddress::Lp(path) => LpBackend::open(path).map(Backend::Lp),
+            PrinterAddress::Network { host, port } => {
+                NetworkBackend::open(host, *port).map(Backend::Network)
+            }
+            PrinterAddress::File(path) => FileBackend::open(path.clone()).map(Backend::File),
+            PrinterAddress::Memory => Ok(Backend::Memory(MemoryBackend::default())),
+            PrinterAddress::Virtual(path) => Ok(Backend::Virtual(VirtualDriver::open(
+                width_dots as u32,
+                Some(path.clone()),
+            ))),
+        }
+    }
+
+    /// Whether this address is a physical device (as opposed to an output
+    /// used for development and testing).
+    pub fn is_device(&self) -> bool {
+        matches!(
+            self,
+            PrinterAddress::Usb { .. } | PrinterAddress::Lp(_) | PrinterAddress::Network { .. }
+        )
+    }
+
+    /// Whether this address, as written in config, names the printer found
+    /// at `found`. A USB address without a serial or port names every
+    /// printer with its IDs.
+    pub fn selects(&self, found: &PrinterAddress) -> bool {
+        match (self, found) {
+            (
+                PrinterAddress::Usb {
+                    vendor_id,
+                    product_id,
+                    serial,
+                    port,
+                },
+                PrinterAddress::Usb {
+                    vendor_id: found_vid,
+                    product_id: found_pid,
+                    serial: found_serial,
+                    port: found_port,
+                },
+            ) => {
+                vendor_id == found_vid
+                    && product_id == found_pid
+                    && serial.as_ref().is_none_or(|s| Some(s) == found_serial.as_ref())
+                    && port.as_ref().is_none_or(|p| Some(p) == found_port.as_ref())
+            }
+            _ => self == found,
+        }
+    }
+
+    /// Human-readable name for printer lists when there is no model name.
+    pub fn label(&self) -> String {
+        match self {
+            PrinterAddress::Usb { product_id, .. } => format!("USB printer {product_id:04x}"),
+            PrinterAddress::Lp(path) => format!("Line printer ({})", path.display()),
+            PrinterAddress::Network { host, port } => format!("Network printer ({host}:{port})"),
+            PrinterAddress::File(path) => format!("File output ({})", path.display()),
+            PrinterAddress::Memory => "Dry run".to_string(),
+            PrinterAddress::Virtual(path) => format!("Virtual printer ({})", path.display()),
+        }
+    }
+}
+
+impl fmt::Display for PrinterAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrinterAddress::Usb {
+                vendor_id,
+                product_id,
+                serial,
+                port,
+            } => {
+                write!(f, "usb:{vendor_id:04x}:{product_id:04x}")?;
+                match (serial, port) {
+                    (Some(serial), _) => write!(f, "?serial={serial}"),
+                    (None, Some(port)) => write!(f, "?port={port}"),
+                    (None, None) => Ok(()),
+                }
+            }
+            PrinterAddress::Lp(path) => write!(f, "lp:{}", path.display()),
+            PrinterAddress::Network { host, port } if host.contains(':') => {
+                write!(f, "tcp://[{host}]:{port}")
+            }
+            PrinterAddress::Network { host, port } => write!(f, "tcp://{host}:{port}"),
+            PrinterAddress::File(path) => write!(f, "file:{}", path.display()),
+            PrinterAddress::Memory => write!(f, "memory"),
+            PrinterAddress::Virtual(path) => write!(f, "virtual:{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for PrinterAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s.split_once(':').unwrap_or((s, ""));
+        match scheme {
+            "usb" => {
+                let (ids, query) = rest.split_once('?').unwrap_or((rest, ""));
+                let (vid, pid) = ids
+                    .split_once(':')
+                    .ok_or_else(|| format!("Expected usb:VID:PID, got {s}"))?;
+                let parse = |h: &str| {
+                    u16::from_str_radix(h, 16).map_err(|_| format!("Bad USB id '{h}' in {s}"))
+                };
+                let (mut serial, mut port) = (None, None);
+                for param in query.split('&').filter(|p| !p.is_empty()) {
+                    match param.split_once('=') {
+                        Some(("serial", value)) if !value.is_empty() => {
+                            serial = Some(value.to_string())
+                        }
+                        Some(("port", value)) if !value.is_empty() => {
+                            port = Some(value.to_string())
+                        }
+                        _ => return Err(format!("Expected serial= or port= in {s}")),
+                    }
+                }
+                Ok(PrinterAddress::Usb {
+                    vendor_id: parse(vid)?,
+                    product_id: parse(pid)?,
+                    serial,
+                    port,
+                })
+            }
+            "lp" if !rest.is_empty() => Ok(PrinterAddress::Lp(PathBuf::from(rest))),
+            "tcp" => {
+                let (host, port) = network::parse_host_port(rest.trim_start_matches("//"))?;
+                Ok(PrinterAddress::Network { host, port })
+            }
+            "file" if !rest.is_empty() => Ok(PrinterAddress::File(PathBuf::from(rest))),
+            "virtual" if !rest.is_empty() => Ok(PrinterAddress::Virtual(PathBuf::from(rest))),
+            "memory" => Ok(PrinterAddress::Memory),
+            _ => Err(format!("Unknown printer address: {s}")),
+        }
+    }
+}
+
+/// Pull `--dry-run` and `--output FILE` out of a command line.
+///
+/// `--output` with a `.png` path renders each job through the virtual
+/// printer, replacing the last; any other path gets the raw bytes appended. Returns the chosen address (if
+/// any) and the remaining arguments in order.
+pub fn parse_output_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<(Option<PrinterAddress>, Vec<String>), String> {
+    let mut address = None;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let path = if arg == "--dry-run" {
+            address = Some(PrinterAddress::Memory);
+            continue;
+        } else if arg == "--output" {
+            args.next().ok_or("--output needs a file path")?
+        } else if let Some(path) = arg.strip_prefix("--output=") {
+            path.to_string()
+        } else {
+            rest.push(arg);
+            continue;
+        };
+
+        let path = PathBuf::from(path);
+        let is_png = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        address = Some(if is_png {
+            PrinterAddress::Virtual(path)
+        } else {
+            PrinterAddress::File(path)
+        });
+    }
+
+    Ok((address, rest))
+}
+
+/// An opened output. Dispatches the `Driver` calls to the concrete backend.
+pub enum Backend {
+    Usb(Box<UsbBackend>),
+    Lp(LpBackend),
+    Network(NetworkBackend),
+    File(FileBackend),
+    Memory(MemoryBackend),
+    Virtual(VirtualDriver),
+}
+
+impl Backend {
+    fn driver(&self) -> &dyn Driver {
+        match self {
+            Backend::Usb(d) => d.as_ref(),
+            Backend::Lp(d) => d,
+            Backend::Network(d) => d,
+            Backend::File(d) => d,
+            Backend::Memory(d) => d,
+            Backend::Virtual(d) => d,
+        }
+    }
+}
+
+impl Driver for Backend {
+    fn name(&self) -> String {
+        self.driver().name()
+    }
+
+    fn write(&self, data: &[u8]) -> DriverResult<()> {
+        self.driver().write(data)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> DriverResult<usize> {
+        self.driver().read(buf)
+    }
+
+    fn flush(&self) -> DriverResult<()> {
+        self.driver().flush()
+    }
+}
+
+/// Appends bytes to a file or named pipe.
+///
+/// The file is opened in append mode so successive runs accumulate jobs, and
+/// a FIFO can be read by e.g. `esc2png` or `nc` on the other end. Opening a
+/// FIFO blocks until a reader is attached.
+pub struct FileBackend {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileBackend {
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Driver for FileBackend {
+    fn name(&self) -> String {
+        format!("File ({})", self.path.display())
+    }
+
+    fn write(&self, data: &[u8]) -> DriverResult<()> {
+        self.file.lock()?.write_all(data)?;
+        Ok(())
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> DriverResult<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> DriverResult<()> {
+        self.file.lock()?.flush()?;
+        Ok(())
+    }
+}
+
+/// Collects bytes in memory. Cloning shares the buffer, so a test (or the
+/// dry-run log) can keep a handle and inspect what was "printed".
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    buf: Arc<Mutex<Vec<u8>>>,
+    flushed: Arc<Mutex<usize>>,
+}
+
+impl MemoryBackend {
+    /// Everything written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.buf.lock().map(|b| b.clone()).unwrap_or_default()
+    }
+}
+
+impl Driver for MemoryBackend {
+    fn name(&self) -> String {
+        "Dry run".to_string()
+    }
+
+    fn write(&self, data: &[u8]) -> DriverResult<()> {
+        self.buf.lock()?.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> DriverResult<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> DriverResult<()> {
+        let total = self.buf.lock()?.len();
+        let mut flushed = self.flushed.lock()?;
+        if total > *flushed {
+            tracing::debug!("Dry run: {} bytes written ({total} total)", total - *flushed);
+            *flushed = total;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_round_trips_through_strings() {
+        for s in ["usb:04b8:0e28", "usb:04b8:0e28?serial=X1", "usb:04b8:0e28?port=001-2.3", "lp:/dev/usb/lp0", "tcp://10.0.0.5:9100", "tcp://[fe80::1]:9100", "file:/tmp/receipt.bin", "memory", "virtual:/tmp/r.png"] {
+            let addr: PrinterAddress = s.parse().unwrap();
+            assert_eq!(addr.to_string(), s);
+        }
+        assert_eq!(
+            "tcp://bar.local".parse::<PrinterAddress>().unwrap(),
+            PrinterAddress::Network {
+                host: "bar.local".into(),
+                port: 9100
+            }
+        );
+        assert!("usb:zz:0e28".parse::<PrinterAddress>().is_err());
+        assert!("usb:04b8:0e28?bus=1".parse::<PrinterAddress>().is_err());
+        assert!("ftp:host".parse::<PrinterAddress>().is_err());
+    }
+
+    #[test]
+    fn usb_address_selects_by_serial_or_port() {
+        let found = |serial: Option<&str>, port: &str| PrinterAddress::Usb {
+            vendor_id: 0x04b8,
+            product_id: 0x0e28,
+            serial: serial.map(String::from),
+            port: Some(port.into()),
+        };
+        let any: PrinterAddress = "usb:04b8:0e28".parse().unwrap();
+        let by_serial: PrinterAddress = "usb:04b8:0e28?serial=X1".parse().unwrap();
+        let by_port: PrinterAddress = "usb:04b8:0e28?port=001-2".parse().unwrap();
+
+        assert!(any.selects(&found(Some("X1"), "001-2")));
+        assert!(any.selects(&found(None, "001-3")));
+        assert!(by_serial.selects(&found(Some("X1"), "001-3")));
+        assert!(!by_serial.selects(&found(Some("X2"), "001-3")));
+        assert!(!by_serial.selects(&found(None, "001-3")));
+        assert!(by_port.selects(&found(None, "001-2")));
+        assert!(!by_port.selects(&found(None, "001-2.1")));
+        assert!(!any.selects(&"usb:04b8:0e15".parse().unwrap()));
+    }
+
+    #[test]
+    fn output_flags_are_extracted() {
+        let args = ["pics", "--output", "out.bin", "--kiosk"].map(String::from);
+        let (addr, rest) = parse_output_args(args).unwrap();
+        assert_eq!(addr, Some(PrinterAddress::File("out.bin".into())));
+        assert_eq!(rest, vec!["pics", "--kiosk"]);
+
+        let (addr, _) = parse_output_args(["--output=strip.PNG".to_string()]).unwrap();
+        assert_eq!(addr, Some(PrinterAddress::Virtual("strip.PNG".into())));
+
+        let (addr, rest) = parse_output_args(["--dry-run".to_string()]).unwrap();
+        assert_eq!(addr, Some(PrinterAddress::Memory));
+        assert!(rest.is_empty());
+
+        assert!(parse_output_args(["--output".to_string()]).is_err());
+    }
+
+    #[test]
+    fn file_backend_appends() {
+        let path = std::env::temp_dir().join(format!("receipts-backend-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for chunk in [b"ab", b"cd"] {
+            let backend = PrinterAddress::File(path.clone()).open(512).unwrap();
+            backend.write(chunk).unwrap();
+            backend.flush().unwrap();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcd");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn virtual_printer_takes_the_print_width() {
+        let Ok(Backend::Virtual(driver)) = PrinterAddress::Virtual("58mm.png".into()).open(384) else {
+            panic!("not a virtual printer");
+        };
+        assert_eq!(driver.printer().lock().unwrap().width(), 384);
+    }
+}
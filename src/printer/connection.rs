@@ -1,297 +1,914 @@
-use std::sync::{Arc, Mutex};
-
-use crate::printer::models::EPSON_VENDOR_ID;
-use escpos::driver::NativeUsbDriver;
-use escpos::printer::Printer;
-use escpos::utils::Protocol;
-
-/// A shared, persistent USB connection. Wraps an optional `PrinterConnection`
-/// behind `Arc<Mutex<>>` so the iced async task pool can use it across prints
-/// without reopening the USB interface each time.
-///
-/// On macOS, the kernel holds the USB interface for ~200ms after close, causing
-/// `kIOReturnExclusiveAccess` on rapid reopen. Keeping the connection open
-/// across prints avoids this entirely.
-pub type SharedConnection = Arc<Mutex<Option<PrinterConnection>>>;
-
-pub struct PrinterConnection {
-    printer: Printer<NativeUsbDriver>,
-    pub product_id: u16,
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::ResumeMode;
+use crate::printer::asb::{self, AsbReader};
+use crate::printer::backend::{Backend, PrinterAddress};
+use crate::printer::discovery::DiscoveredPrinter;
+use crate::printer::encoder::{Chunk, Dialect, EncodedJob, PrintOptions};
+use crate::printer::flow::{self, Pace, Pacer};
+use crate::printer::logo::{self, LogoOp};
+use crate::printer::models::FlowControl;
+use crate::printer::rich_print::PrintCommand;
+use crate::printer::status::PrinterStatus;
+use crate::receipt_markdown::{Alignment, ReceiptBlock};
+use escpos::driver::Driver;
+
+/// Pause between reads while waiting on an output that answers at once.
+const REPLY_POLL: Duration = Duration::from_millis(10);
+
+/// Unanswered status queries, with none answered yet, before a printer is
+/// taken not to report status at all.
+const STATUS_ATTEMPTS: u32 = 3;
+
+/// Why a job didn't print.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    /// Paper out, cover open or offline. The printer is reachable; the same
+    /// job should be retried once it reports ready.
+    NotReady(PrinterStatus),
+    /// Stopped on request, while queued or between bands.
+    Cancelled,
+    /// The job itself can't be printed: an image that doesn't decode, a
+    /// command that can't be encoded. Nothing was sent, so the printer is
+    /// fine, and any printer would fail it the same way.
+    Invalid(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::NotReady(status) => match status.blocking_error() {
+                Some(e) => write!(f, "{e}"),
+                None => write!(f, "{}", status.summary()),
+            },
+            JobError::Cancelled => write!(f, "Cancelled"),
+            JobError::Invalid(e) | JobError::Failed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<String> for JobError {
+    fn from(e: String) -> Self {
+        JobError::Failed(e)
+    }
+}
+
+/// A job that stopped on a write or flush error, and how far it got.
+#[derive(Debug, Clone)]
+pub struct Unfinished {
+    /// The job as encoded, before its print settings were added; sending
+    /// the resumed job adds them again.
+    pub job: EncodedJob,
+    /// The first chunk not known to have reached the printer: after the
+    /// last band it acknowledged, or the last one written when it doesn't
+    /// acknowledge bands.
+    pub next_chunk: usize,
+}
+
+impl Unfinished {
+    /// Whether the job prints an image, so is worth resuming.
+    pub fn has_raster(&self) -> bool {
+        self.job.chunks.iter().any(|c| c.raster_rows > 0)
+    }
+
+    /// Raster bands known to have printed.
+    pub fn bands_done(&self) -> usize {
+        self.job.chunks[..self.next_chunk.min(self.job.chunks.len())]
+            .iter()
+            .filter(|c| c.raster_rows > 0)
+            .count()
+    }
+
+    /// What to send once the printer is back: after a reset, either the
+    /// rest of the job or all of it under a "continued" line.
+    pub fn resumed(&self, mode: ResumeMode, dialect: Dialect) -> EncodedJob {
+        let mut chunks = match mode {
+            ResumeMode::Reprint => EncodedJob::commands(
+                &[
+                    PrintCommand::SetAlignment(Alignment::Center),
+                    PrintCommand::Write("- continued -".into()),
+                    PrintCommand::Feed,
+                    PrintCommand::SetAlignment(Alignment::Left),
+                ],
+                dialect,
+            )
+            .map(|marker| marker.chunks)
+            .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let rest = match mode {
+            ResumeMode::Reprint => &self.job.chunks[..],
+            _ => &self.job.chunks[self.next_chunk.min(self.job.chunks.len())..],
+        };
+        if chunks.is_empty() {
+            chunks.push(Chunk {
+                bytes: vec![0x1b, b'@'],
+                raster_rows: 0,
+            });
+        }
+        chunks.extend_from_slice(rest);
+        EncodedJob { chunks }
+    }
+}
+
+pub struct PrinterConnection<D: Driver = Backend> {
+    /// Shared with the ASB reader thread, which owns the read side.
+    driver: Arc<D>,
+    dialect: Dialect,
+    /// Density and speed from the printer's config entry.
+    default_options: PrintOptions,
+    /// Options for the job in progress, over the defaults.
+    job_options: PrintOptions,
+    /// How raster bands are held back. Only physical printers need it.
+    flow: Option<FlowControl>,
+    /// The model's pace at its standard speed level.
+    pace: Pace,
+    pacer: Pacer,
+    /// `GS ( H` requests not yet answered: ID, the band's size and its
+    /// chunk in the job.
+    outstanding: VecDeque<([u8; 4], usize, usize)>,
+    /// The first chunk of the job in progress not known to have printed.
+    resume_at: usize,
+    /// The last job, if it stopped on a connection error.
+    unfinished: Option<Unfinished>,
+    next_response: u32,
+    /// The printer has answered a `GS ( H` request on this connection.
+    answered: bool,
+    /// Set to stop the job in progress before its next chunk.
+    cancel: Option<Arc<AtomicBool>>,
+    /// Told how many of the job's chunks have been written.
+    on_progress: Option<Box<dyn FnMut(usize, usize) + Send>>,
+    /// Cleared once `STATUS_ATTEMPTS` status queries in a row go
+    /// unanswered, so printers without read-back aren't asked before every
+    /// job. Never set for file outputs.
+    reports_status: bool,
+    /// Status queries that have gone unanswered in a row.
+    status_failures: u32,
+    last_status: Option<PrinterStatus>,
+    /// Running while Automatic Status Back is enabled.
+    asb: Option<AsbReader>,
+    /// `DiscoveredPrinter::key()`, sent with ASB status changes.
+    key: String,
+    pub address: PrinterAddress,
     pub model_name: String,
 }
 
 impl PrinterConnection {
-    pub fn open(product_id: u16, model_name: String) -> Result<Self, String> {
-        let driver = NativeUsbDriver::open(EPSON_VENDOR_ID, product_id).map_err(|e| {
-            let err_str = e.to_string();
-            #[cfg(target_os = "macos")]
-            {
-                crate::platform::macos::cups_conflict_hint(product_id, &err_str)
-            }
-            #[cfg(not(target_os = "macos"))]
-            {
-                format!(
-                    "Failed to open USB device {EPSON_VENDOR_ID:04x}:{product_id:04x}: {err_str}"
-                )
+    pub fn open(printer: &DiscoveredPrinter) -> Result<Self, String> {
+        let driver = printer.address.open(printer.print_width().dots)?;
+        let mut conn = Self::with_driver(driver, printer);
+        if conn.reports_status {
+            if let Err(e) = conn.enable_asb() {
+                tracing::warn!("Automatic status back unavailable: {e}");
             }
-        })?;
-
-        let printer = Printer::new(driver, Protocol::default(), None);
+        }
+        Ok(conn)
+    }
+}
 
-        Ok(Self {
-            printer,
-            product_id,
-            model_name,
-        })
+impl<D: Driver + Send + Sync + 'static> PrinterConnection<D> {
+    /// Turn on Automatic Status Back and start the reader that turns its
+    /// packets into `StatusChange` events. From here on, `DLE EOT` replies
+    /// are read through the reader too.
+    pub fn enable_asb(&mut self) -> Result<(), String> {
+        self.driver
+            .write(&asb::ENABLE_ASB)
+            .and_then(|_| self.driver.flush())
+            .map_err(|e| format!("Enabling ASB failed: {e}"))?;
+        self.asb = Some(AsbReader::spawn(
+            self.driver.clone(),
+            self.model_name.clone(),
+            self.key.clone(),
+        ));
+        Ok(())
     }
+}
 
-    pub fn print_rich(
-        &mut self,
-        blocks: &[crate::receipt_markdown::ReceiptBlock],
-        max_chars: u8,
-    ) -> Result<(), String> {
-        self.printer.init().map_err(|e| e.to_string())?;
+impl<D: Driver> PrinterConnection<D> {
+    /// Wrap an already-open driver, e.g. a `MemoryBackend` in tests.
+    pub fn with_driver(driver: D, printer: &DiscoveredPrinter) -> Self {
+        let model = printer.model();
+        let pace = Pace::for_model(model);
+        Self {
+            driver: Arc::new(driver),
+            dialect: printer.dialect(),
+            default_options: printer.print_options(),
+            job_options: PrintOptions::default(),
+            flow: printer.address.is_device().then_some(model.flow_control),
+            pace,
+            pacer: Pacer::new(pace),
+            outstanding: VecDeque::new(),
+            resume_at: 0,
+            unfinished: None,
+            next_response: 0,
+            answered: false,
+            cancel: None,
+            on_progress: None,
+            // Status requests would end up in the output file
+            reports_status: !matches!(
+                printer.address,
+                PrinterAddress::File(_) | PrinterAddress::Memory
+            ),
+            status_failures: 0,
+            last_status: None,
+            asb: None,
+            key: printer.key(),
+            address: printer.address.clone(),
+            model_name: printer.model_name.clone(),
+        }
+    }
 
-        let commands = crate::printer::rich_print::generate_commands(blocks, max_chars);
-        crate::printer::rich_print::execute_commands(&mut self.printer, &commands)?;
+    /// The encoding dialect for this printer, for building jobs off-device.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
 
-        self.printer
-            .feeds(3)
-            .map_err(|e| e.to_string())?
-            .print_cut()
-            .map_err(|e| e.to_string())?;
+    /// The dialect jobs are encoded in: the printer's, with images dithered
+    /// as the job options (or the printer's defaults) ask.
+    fn job_dialect(&self) -> Dialect {
+        let options = self.job_options.or(self.default_options);
+        Dialect {
+            dither: options.dither.unwrap_or_default(),
+            ..self.dialect
+        }
+    }
 
-        Ok(())
+    /// Density, speed and dithering for the next jobs sent, over the
+    /// printer's defaults.
+    pub fn set_job_options(&mut self, mut options: PrintOptions) {
+        for problem in options.drop_invalid() {
+            tracing::warn!("Ignoring job option: {problem}");
+        }
+        self.job_options = options;
     }
 
-    /// Print text without cutting — for continuous log-style output.
-    pub fn print_no_cut(
+    /// Report progress of the jobs sent from now on, and stop them before
+    /// the next chunk once `cancel` is set.
+    pub fn watch_job(
         &mut self,
-        blocks: &[crate::receipt_markdown::ReceiptBlock],
-        max_chars: u8,
-    ) -> Result<(), String> {
-        self.printer.init().map_err(|e| e.to_string())?;
+        cancel: Arc<AtomicBool>,
+        on_progress: impl FnMut(usize, usize) + Send + 'static,
+    ) {
+        self.cancel = Some(cancel);
+        self.on_progress = Some(Box::new(on_progress));
+    }
 
-        let commands = crate::printer::rich_print::generate_commands(blocks, max_chars);
-        crate::printer::rich_print::execute_commands(&mut self.printer, &commands)?;
+    pub fn unwatch_job(&mut self) {
+        self.cancel = None;
+        self.on_progress = None;
+    }
+
+    /// Write an encoded job to the printer, chunk by chunk.
+    ///
+    /// Density and speed are applied first, and again after each `ESC @`
+    /// in the job, since a reset restores the printer's own settings.
+    ///
+    /// Each raster band is written and flushed on its own, once the printer
+    /// has room for it (see `flow`). Sending an entire image in one burst
+    /// overflows the printer's receive buffer and causes a USB bus reset
+    /// (kernel 6.12+). A job starts once the previous one should have
+    /// finished printing.
+    ///
+    /// A cancelled job (see `watch_job`) stops before its next chunk and
+    /// resets the printer, leaving whatever has printed so far. A job that
+    /// fails on the connection is kept with how far it got, see
+    /// `take_unfinished`.
+    pub fn send(&mut self, job: &EncodedJob) -> Result<(), String> {
+        let options = self.job_options.or(self.default_options);
+        options.validate()?;
+        let settings = options.control_bytes(self.dialect);
+        let mut sent = job.clone();
+        sent.apply_settings(&settings);
+
+        self.resume_at = 0;
+        self.unfinished = None;
+        let result = self.send_chunks(&sent, options);
+        let cancelled = self.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed));
+        if result.is_err() && !cancelled {
+            // Settings may have gone in as a chunk of their own
+            let added = sent.chunks.len() - job.chunks.len();
+            self.unfinished = Some(Unfinished {
+                job: job.clone(),
+                next_chunk: self.resume_at.saturating_sub(added),
+            });
+        }
+        result
+    }
 
-        self.printer
-            .feeds(3)
-            .map_err(|e| e.to_string())?
-            .print()
-            .map_err(|e| e.to_string())?;
+    /// The last job sent, if it failed on the connection part-way through.
+    pub fn take_unfinished(&mut self) -> Option<Unfinished> {
+        self.unfinished.take()
+    }
 
+    fn send_chunks(&mut self, job: &EncodedJob, options: PrintOptions) -> Result<(), String> {
+        if self.flow.is_some() {
+            if let Some(at) = self.pacer.idle_at() {
+                flow::sleep_until(at);
+            }
+        }
+        self.pacer.set_pace(self.pace.at_level(options.speed));
+
+        let total = job.chunks.len();
+        for (i, chunk) in job.chunks.iter().enumerate() {
+            if self.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+                self.outstanding.clear();
+                self.write_command(&[0x1b, b'@'])?;
+                return Err("Cancelled".to_string());
+            }
+            let band = chunk.raster_rows > 0;
+            if band {
+                self.make_room(chunk.bytes.len())?;
+            }
+            self.driver
+                .write(&chunk.bytes)
+                .map_err(|e| format!("Write to {} failed: {e}", self.address))?;
+            // Outputs other than printers take the job in one flush
+            if band && self.flow.is_some() {
+                self.driver
+                    .flush()
+                    .map_err(|e| format!("Band flush failed: {e}"))?;
+                self.band_sent(i, chunk.raster_rows, chunk.bytes.len())?;
+            }
+            // Bands awaiting a reply may still be lost
+            if self.outstanding.is_empty() {
+                self.resume_at = i + 1;
+            }
+            if let Some(on_progress) = self.on_progress.as_mut() {
+                on_progress(i + 1, total);
+            }
+        }
+        self.driver.flush().map_err(|e| e.to_string())?;
+        while !self.outstanding.is_empty() {
+            self.await_response()?;
+        }
         Ok(())
     }
 
-    /// Print a website message: text content + optional image, then cut.
-    ///
-    /// Text and image are printed as separate init cycles to avoid
-    /// escpos bit_image resetting printer state and clearing buffered text.
-    pub fn print_website_message(
-        &mut self,
-        blocks: &[crate::receipt_markdown::ReceiptBlock],
-        max_chars: u8,
-        image_bytes: Option<&[u8]>,
-    ) -> Result<(), String> {
-        // Print text portion
-        self.printer.init().map_err(|e| e.to_string())?;
-        let commands = crate::printer::rich_print::generate_commands(blocks, max_chars);
-        crate::printer::rich_print::execute_commands(&mut self.printer, &commands)?;
-
-        // Print image if present — re-init to isolate from text
-        if let Some(bytes) = image_bytes {
-            if !bytes.is_empty() {
-                self.printer.feeds(2).map_err(|e| e.to_string())?;
-                self.printer.init().map_err(|e| e.to_string())?;
-                if let Err(e) = self.print_image(bytes) {
-                    tracing::warn!("Image print failed (non-fatal): {e}");
+    /// Before a band: wait until the printer has room for `bytes` more.
+    fn make_room(&mut self, bytes: usize) -> Result<(), String> {
+        match self.flow {
+            Some(FlowControl::Response) => {
+                let limit = self.pacer.pace().buffer_bytes.saturating_sub(bytes);
+                while self.outstanding.iter().map(|&(_, b, _)| b).sum::<usize>() > limit {
+                    self.await_response()?;
                 }
             }
+            Some(FlowControl::Paced) => {
+                flow::sleep_until(self.pacer.ready_at(bytes, Instant::now()));
+            }
+            None => {}
         }
+        Ok(())
+    }
 
-        // Feed and cut
-        self.printer
-            .feeds(3)
-            .map_err(|e| e.to_string())?
-            .print_cut()
-            .map_err(|e| e.to_string())?;
-
+    /// After a band: note when it should have printed, and ask the printer
+    /// to say when it has.
+    fn band_sent(&mut self, chunk: usize, rows: usize, bytes: usize) -> Result<(), String> {
+        if self.flow.is_none() {
+            return Ok(());
+        }
+        self.pacer.sent(rows, bytes, Instant::now());
+        if self.flow == Some(FlowControl::Response) {
+            let id = self.next_response;
+            self.next_response = id.wrapping_add(1);
+            self.write_command(&flow::response_request(id))?;
+            self.outstanding.push_back((flow::response_id(id), bytes, chunk));
+        }
         Ok(())
     }
 
-    /// Print an image without cutting — for photo strip sequences.
-    /// Sends the image + a small feed, but no cut command.
-    /// If `bright` is true, applies indoor brightness boost before dithering.
-    pub fn print_image_no_cut(&mut self, image_bytes: &[u8], extra_feed: u8, bright: bool) -> Result<(), String> {
-        self.printer.init().map_err(|e| e.to_string())?;
-        self.print_image_inner(image_bytes, bright)?;
-        self.printer
-            .feeds(extra_feed)
-            .map_err(|e| e.to_string())?
-            .print()
-            .map_err(|e| e.to_string())?;
+    /// Wait for the oldest `GS ( H` reply. A printer that never answers is
+    /// paced by estimate from then on; one that stops answering mid-job has
+    /// stalled (e.g. out of paper), which fails the job.
+    fn await_response(&mut self) -> Result<(), String> {
+        let Some((id, _, chunk)) = self.outstanding.pop_front() else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let deadline = self.pacer.idle_at().map_or(now, |at| at.max(now)) + flow::RESPONSE_SLACK;
+        let mut reply = Vec::new();
+        while Instant::now() < deadline {
+            let b = match self.read_reply_byte() {
+                Ok(b) => b,
+                Err(_) => {
+                    std::thread::sleep(REPLY_POLL);
+                    continue;
+                }
+            };
+            // Skip anything before the reply header, e.g. a late status byte
+            if reply.is_empty() && b != 0x37 {
+                continue;
+            }
+            reply.push(b);
+            if b != 0x00 && reply.len() < 7 {
+                continue;
+            }
+            if flow::parse_response(&reply) == Some(id) {
+                self.answered = true;
+                self.resume_at = self.resume_at.max(chunk + 1);
+                if self.outstanding.is_empty() {
+                    self.pacer.drained(Instant::now());
+                }
+                return Ok(());
+            }
+            reply.clear();
+        }
+
+        self.outstanding.clear();
+        if self.answered {
+            return Err(format!("{} stopped printing", self.address));
+        }
+        tracing::warn!(
+            "{} doesn't answer GS ( H; pacing raster data by print speed",
+            self.address
+        );
+        self.flow = Some(FlowControl::Paced);
         Ok(())
     }
 
-    /// Print an image using ESC/POS bit image commands.
-    /// Resizes, applies gamma + Floyd-Steinberg dithering, then sends to printer.
-    fn print_image(&mut self, image_bytes: &[u8]) -> Result<(), String> {
-        self.print_image_inner(image_bytes, false)
+    /// Ask for one real-time status byte (`DLE EOT n`) and read the reply.
+    fn real_time_status(&mut self, n: u8) -> Result<u8, String> {
+        if let Some(reader) = &self.asb {
+            reader.clear_replies();
+        }
+        self.driver
+            .write(&[0x10, 0x04, n])
+            .and_then(|_| self.driver.flush())
+            .map_err(|e| format!("Status request failed: {e}"))?;
+        self.read_reply_byte()
+    }
+
+    /// Read one byte of a reply, through the ASB reader when it's running.
+    fn read_reply_byte(&mut self) -> Result<u8, String> {
+        if let Some(reader) = &self.asb {
+            return reader.reply();
+        }
+        let mut reply = [0u8; 1];
+        match self.driver.read(&mut reply) {
+            Ok(1) => Ok(reply[0]),
+            Ok(_) => Err(format!("{} does not report status", self.address)),
+            Err(e) => Err(format!("Status read failed: {e}")),
+        }
+    }
+
+    fn write_command(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.driver
+            .write(bytes)
+            .and_then(|_| self.driver.flush())
+            .map_err(|e| format!("Write to {} failed: {e}", self.address))
     }
 
-    fn print_image_inner(&mut self, image_bytes: &[u8], bright: bool) -> Result<(), String> {
-        let img = image::load_from_memory(image_bytes)
-            .map_err(|e| format!("Image decode failed: {e}"))?;
+    /// Key codes of the logos stored in NV graphics memory.
+    pub fn list_logos(&mut self) -> Result<Vec<String>, String> {
+        if !self.reports_status {
+            return Err(format!("{} can't report stored logos", self.address));
+        }
+        if let Some(reader) = &self.asb {
+            reader.clear_replies();
+        }
+        self.write_command(&logo::LIST_KEYS)?;
+
+        let mut keys = Vec::new();
+        loop {
+            let header = [self.read_reply_byte()?, self.read_reply_byte()?];
+            if header != logo::KEY_LIST_HEADER {
+                return Err(format!("Unexpected key list reply: {header:02x?}"));
+            }
+            let status = self.read_reply_byte()?;
+            let mut data = Vec::new();
+            loop {
+                match self.read_reply_byte()? {
+                    0x00 => break,
+                    b => data.push(b),
+                }
+            }
+            keys.extend(logo::parse_keys(&data));
+            if status != logo::KEY_LIST_MORE {
+                return Ok(keys);
+            }
+            self.write_command(&[logo::ACK])?;
+        }
+    }
 
-        // Rotate 90° clockwise so portrait photos print upright on receipt paper
-        let img = img.rotate90();
+    /// Store an image as an NV logo, scaled down to the printer width.
+    pub fn store_logo(&mut self, key: &str, image_bytes: &[u8]) -> Result<(), JobError> {
+        let raster = logo::prepare(
+            image_bytes,
+            self.dialect.dots_per_line as u32,
+            self.dialect.upside_down,
+        )
+        .map_err(JobError::Invalid)?;
+        let cmd = logo::store_command(key, &raster).map_err(JobError::Invalid)?;
+        Ok(self.write_command(&cmd)?)
+    }
 
-        let resized = img.resize(512, u32::MAX, image::imageops::FilterType::Lanczos3);
+    pub fn delete_logo(&mut self, key: &str) -> Result<(), JobError> {
+        let cmd = logo::delete_command(key).map_err(JobError::Invalid)?;
+        Ok(self.write_command(&cmd)?)
+    }
 
-        // Grayscale → gamma correction → Floyd-Steinberg dither
-        let mut gray = resized.to_luma8();
-        if bright {
-            crate::printer::image_proc::dither_for_thermal_bright(&mut gray);
-        } else {
-            crate::printer::image_proc::dither_for_thermal(&mut gray);
+    /// Run a logo operation and describe the result.
+    pub fn run_logo_op(&mut self, op: &LogoOp) -> Result<String, JobError> {
+        match op {
+            LogoOp::List => {
+                let keys = self.list_logos()?;
+                if keys.is_empty() {
+                    Ok("No logos stored".to_string())
+                } else {
+                    Ok(keys.join("\n"))
+                }
+            }
+            LogoOp::Store { key, image } => {
+                self.store_logo(key, image)?;
+                Ok(format!("Stored logo {key}"))
+            }
+            LogoOp::Delete { key } => {
+                self.delete_logo(key)?;
+                Ok(format!("Deleted logo {key}"))
+            }
         }
+    }
 
-        let width = gray.width() as usize;
-        let height = gray.height() as usize;
-        let width_bytes = (width + 7) / 8;
-        tracing::info!("Dithered image: {width}x{height}px, sending as banded raster");
+    /// Query printer, offline and paper-sensor status.
+    pub fn query_status(&mut self) -> Result<PrinterStatus, String> {
+        let printer = self.real_time_status(1)?;
+        let offline = self.real_time_status(2)?;
+        let paper = self.real_time_status(4)?;
+        let status = PrinterStatus::from_status_bytes(printer, offline, paper);
+        self.last_status = Some(status.clone());
+        Ok(status)
+    }
 
-        // Convert 8-bit dithered (0 or 255) to 1-bit packed raster
-        let mut raster = vec![0u8; width_bytes * height];
-        for y in 0..height {
-            for x in 0..width {
-                if gray.get_pixel(x as u32, y as u32)[0] == 0 {
-                    raster[y * width_bytes + x / 8] |= 0x80 >> (x % 8);
+    /// Query status unless the printer has already shown it can't answer.
+    /// `Ok(None)` means the status is unknown. A printer that has never
+    /// answered is asked again next time, up to `STATUS_ATTEMPTS` times.
+    pub fn poll_status(&mut self) -> Result<Option<PrinterStatus>, String> {
+        if !self.reports_status {
+            return Ok(None);
+        }
+        match self.query_status() {
+            Ok(status) => {
+                self.status_failures = 0;
+                Ok(Some(status))
+            }
+            Err(e) if self.last_status.is_none() => {
+                self.status_failures += 1;
+                if self.status_failures >= STATUS_ATTEMPTS {
+                    tracing::info!("{} does not report status: {e}", self.address);
+                    self.reports_status = false;
+                } else {
+                    tracing::debug!("No status from {}: {e}", self.address);
                 }
+                Ok(None)
             }
+            Err(e) => Err(e),
         }
+    }
 
-        // Send in bands of 24 rows with small delays to avoid overflowing
-        // the printer's ~16KB receive buffer, which causes USB bus resets.
-        const BAND_HEIGHT: usize = 24;
+    /// The status from the most recent successful query.
+    pub fn last_status(&self) -> Option<&PrinterStatus> {
+        self.last_status.as_ref()
+    }
 
-        for band_start in (0..height).step_by(BAND_HEIGHT) {
-            let band_end = (band_start + BAND_HEIGHT).min(height);
-            let band_h = band_end - band_start;
+    /// Refuse a job the printer can't print (paper out, cover open, ...).
+    /// Printers that don't report status are assumed ready.
+    pub fn check_ready(&mut self) -> Result<(), JobError> {
+        match self.poll_status()? {
+            Some(status) if status.blocking_error().is_some() => Err(JobError::NotReady(status)),
+            _ => Ok(()),
+        }
+    }
 
-            // GS v 0: print raster bit image
-            let cmd: [u8; 8] = [
-                0x1d, 0x76, 0x30, 0x00,
-                (width_bytes & 0xFF) as u8,
-                ((width_bytes >> 8) & 0xFF) as u8,
-                (band_h & 0xFF) as u8,
-                ((band_h >> 8) & 0xFF) as u8,
-            ];
-            self.printer.custom(&cmd).map_err(|e| format!("Raster cmd failed: {e}"))?;
+    /// Send `ESC @` to check that the output still accepts data.
+    pub fn ping(&mut self) -> Result<(), String> {
+        self.driver.write(&[0x1b, b'@']).map_err(|e| e.to_string())
+    }
 
-            let data_start = band_start * width_bytes;
-            let data_end = band_end * width_bytes;
-            self.printer
-                .custom(&raster[data_start..data_end])
-                .map_err(|e| format!("Raster data failed: {e}"))?;
+    pub fn print_rich(&mut self, blocks: &[ReceiptBlock], max_chars: u8) -> Result<(), String> {
+        let job = EncodedJob::receipt(blocks, max_chars, true, self.job_dialect());
+        self.send(&job)
+    }
 
-            // Flush each band to USB immediately. The escpos library buffers
-            // all commands until print() — without this, the entire image is
-            // sent in one burst which overflows the printer's receive buffer
-            // and causes a USB bus reset (kernel 6.12+).
-            self.printer.print().map_err(|e| format!("Band flush failed: {e}"))?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-        }
+    /// Print text without cutting — for continuous log-style output.
+    pub fn print_no_cut(&mut self, blocks: &[ReceiptBlock], max_chars: u8) -> Result<(), String> {
+        let job = EncodedJob::receipt(blocks, max_chars, false, self.job_dialect());
+        self.send(&job)
+    }
 
-        Ok(())
+    /// Print a pre-built command list (the JSON receipt IR) as-is.
+    /// No implicit feed or cut — the caller controls both via the commands.
+    pub fn print_commands(&mut self, commands: &[PrintCommand]) -> Result<(), JobError> {
+        let job = EncodedJob::commands(commands, self.job_dialect()).map_err(JobError::Invalid)?;
+        Ok(self.send(&job)?)
+    }
+
+    /// Print a website message: text content + optional image, then cut.
+    ///
+    /// Text and image are separated by a re-init so the raster never
+    /// inherits text formatting state.
+    pub fn print_website_message(
+        &mut self,
+        blocks: &[ReceiptBlock],
+        max_chars: u8,
+        image_bytes: Option<&[u8]>,
+    ) -> Result<(), String> {
+        let job = EncodedJob::website_message(blocks, max_chars, image_bytes, self.job_dialect());
+        self.send(&job)
+    }
+
+    /// Print an image without cutting — for photo strip sequences.
+    /// Sends the image + a small feed, but no cut command.
+    /// If `bright` is true, applies indoor brightness boost before dithering.
+    pub fn print_image_no_cut(&mut self, image_bytes: &[u8], extra_feed: u8, bright: bool) -> Result<(), JobError> {
+        let job = EncodedJob::image_no_cut(image_bytes, extra_feed, bright, self.job_dialect())
+            .map_err(JobError::Invalid)?;
+        Ok(self.send(&job)?)
     }
 }
 
-/// Create a new empty shared connection slot.
-pub fn new_shared() -> SharedConnection {
-    Arc::new(Mutex::new(None))
+impl<D: Driver> Drop for PrinterConnection<D> {
+    fn drop(&mut self) {
+        if self.asb.is_some() {
+            // Best effort: leave the printer quiet for the next client
+            let _ = self.driver.write(&asb::DISABLE_ASB);
+            let _ = self.driver.flush();
+        }
+    }
 }
 
-/// Open a USB connection and store it in the shared slot.
-/// If a connection is already open to the same printer, reuses it.
-/// If open to a different printer, closes the old one first.
-pub fn open_shared(
-    shared: &SharedConnection,
-    product_id: u16,
-    model_name: String,
-) -> Result<(), String> {
-    let mut guard = shared.lock().map_err(|e| format!("Lock poisoned: {e}"))?;
-
-    // Already connected to this printer? Keep it.
-    if let Some(ref conn) = *guard {
-        if conn.product_id == product_id {
-            tracing::debug!("Reusing existing USB connection to {model_name}");
-            return Ok(());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::backend::MemoryBackend;
+    use crate::printer::image_proc::Dither;
+    use crate::printer::virtual_printer::VirtualDriver;
+    use crate::receipt_markdown::parse_receipt_markdown;
+    use std::sync::Mutex;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = image::DynamicImage::ImageLuma8(image::GrayImage::new(width, height));
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        png.into_inner()
+    }
+
+    #[test]
+    fn memory_backend_receives_encoded_job() {
+        let memory = MemoryBackend::default();
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Memory);
+        let mut conn = PrinterConnection::with_driver(memory.clone(), &printer);
+        let blocks = parse_receipt_markdown("# Hello\nworld");
+        conn.print_rich(&blocks, 42).unwrap();
+
+        let expected = EncodedJob::receipt(&blocks, 42, true, Dialect::EPSON).to_bytes();
+        assert_eq!(memory.contents(), expected);
+        assert!(conn.query_status().is_err());
+        // Outputs that can't answer are treated as ready
+        assert_eq!(conn.poll_status().unwrap(), None);
+        assert!(conn.check_ready().is_ok());
+    }
+
+    #[test]
+    fn job_options_override_printer_defaults() {
+        let memory = MemoryBackend::default();
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Memory);
+        let mut conn = PrinterConnection::with_driver(memory.clone(), &printer);
+        conn.default_options = PrintOptions {
+            density: Some(1),
+            speed: Some(3),
+            dither: Some(Dither::Atkinson),
+        };
+        conn.set_job_options(PrintOptions {
+            density: None,
+            speed: Some(10),
+            dither: Some(Dither::Threshold),
+        });
+        assert_eq!(conn.job_dialect().dither, Dither::Threshold);
+        conn.print_commands(&[PrintCommand::Feed]).unwrap();
+
+        let expected = [
+            &[0x1b, b'@'][..],
+            &[0x1d, b'(', b'K', 2, 0, 49, 1],
+            &[0x1d, b'(', b'K', 2, 0, 50, 10],
+            &[0x1b, b'd', 1],
+        ]
+        .concat();
+        assert_eq!(memory.contents(), expected);
+    }
+
+    #[test]
+    fn virtual_printer_reports_ready() {
+        let driver = VirtualDriver::open(512, None);
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Virtual("out.png".into()));
+        let mut conn = PrinterConnection::with_driver(driver, &printer);
+        assert_eq!(conn.poll_status().unwrap().unwrap().summary(), "Ready");
+        assert!(conn.check_ready().is_ok());
+    }
+
+    #[test]
+    fn asb_events_and_replies_share_the_reader() {
+        let mut events = crate::printer::status::subscribe();
+        let driver = VirtualDriver::open(512, None);
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Virtual("asb.png".into()));
+        let mut conn = PrinterConnection::with_driver(driver, &printer);
+        conn.enable_asb().unwrap();
+
+        // DLE EOT replies come back through the reader thread
+        assert_eq!(conn.poll_status().unwrap().unwrap().summary(), "Ready");
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        let change = loop {
+            match events.try_recv() {
+                Ok(change) if change.key == printer.key() => break change,
+                Ok(_) => {}
+                Err(_) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(e) => panic!("no status event: {e}"),
+            }
+        };
+        assert_eq!(change.status.summary(), "Ready");
+    }
+
+    #[test]
+    fn raster_bands_wait_for_transmission_responses() {
+        let driver = VirtualDriver::open(512, None);
+        let virtual_printer = driver.printer();
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Virtual("flow.png".into()));
+        let mut conn = PrinterConnection::with_driver(driver, &printer);
+        conn.flow = Some(FlowControl::Response);
+        // Room for two 1544-byte bands at a time
+        conn.pace.buffer_bytes = 4000;
+
+        conn.print_image_no_cut(&png(200, 512), 0, false).unwrap();
+
+        // Turned a quarter for the strip: nine 24-row bands, each answered
+        // before the job returns
+        assert_eq!(conn.next_response, 9);
+        assert!(conn.outstanding.is_empty() && conn.answered);
+        assert_eq!(conn.flow, Some(FlowControl::Response));
+        assert!(virtual_printer.lock().unwrap().read_responses().is_empty());
+    }
+
+    #[test]
+    fn cancelled_job_stops_between_bands() {
+        let memory = MemoryBackend::default();
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Memory);
+        let mut conn = PrinterConnection::with_driver(memory.clone(), &printer);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        // Cancel once the init chunk and the first band are out
+        conn.watch_job(cancel, move |sent, _| flag.store(sent >= 2, Ordering::Relaxed));
+
+        let job = EncodedJob::image_no_cut(&png(200, 512), 0, false, Dialect::EPSON).unwrap();
+        assert!(conn.send(&job).is_err());
+        let sent: Vec<u8> = job.chunks[..2].iter().flat_map(|c| c.bytes.clone()).collect();
+        assert_eq!(memory.contents(), [sent, vec![0x1b, b'@']].concat());
+    }
+
+    /// Fails every write from the `n`th on, like a printer reset mid-job.
+    struct ResetDriver {
+        out: MemoryBackend,
+        writes_left: Mutex<usize>,
+    }
+
+    impl Driver for ResetDriver {
+        fn name(&self) -> String {
+            "reset".into()
+        }
+        fn write(&self, data: &[u8]) -> escpos::errors::Result<()> {
+            let mut left = self.writes_left.lock().unwrap();
+            if *left == 0 {
+                return Err(escpos::errors::PrinterError::Io("device reset".into()));
+            }
+            *left -= 1;
+            self.out.write(data)
+        }
+        fn read(&self, _buf: &mut [u8]) -> escpos::errors::Result<usize> {
+            Ok(0)
+        }
+        fn flush(&self) -> escpos::errors::Result<()> {
+            Ok(())
         }
-        tracing::info!("Switching printer — closing old connection");
     }
 
-    tracing::info!("Opening persistent USB connection to {model_name}");
-    let conn = PrinterConnection::open(product_id, model_name)?;
-    *guard = Some(conn);
-    Ok(())
-}
+    #[test]
+    fn job_cut_off_resumes_after_the_last_band() {
+        let driver = ResetDriver {
+            out: MemoryBackend::default(),
+            writes_left: Mutex::new(3),
+        };
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Memory);
+        let mut conn = PrinterConnection::with_driver(driver, &printer);
+        let job = EncodedJob::image_no_cut(&png(200, 512), 0, false, Dialect::EPSON).unwrap();
+
+        // The init chunk and two bands get through
+        assert!(conn.send(&job).is_err());
+        let unfinished = conn.take_unfinished().unwrap();
+        assert!(unfinished.has_raster());
+        assert_eq!((unfinished.next_chunk, unfinished.bands_done()), (3, 2));
+
+        let memory = MemoryBackend::default();
+        let mut conn = PrinterConnection::with_driver(memory.clone(), &printer);
+        conn.send(&unfinished.resumed(ResumeMode::Continue, Dialect::EPSON)).unwrap();
+        let rest: Vec<u8> = job.chunks[3..].iter().flat_map(|c| c.bytes.clone()).collect();
+        assert_eq!(memory.contents(), [vec![0x1b, b'@'], rest].concat());
+        assert!(conn.take_unfinished().is_none());
+
+        let reprint = unfinished.resumed(ResumeMode::Reprint, Dialect::EPSON).to_bytes();
+        assert!(reprint.starts_with(&[0x1b, b'@']));
+        assert!(reprint.ends_with(&job.to_bytes()));
+    }
 
-/// Close the shared connection (e.g., on disconnect or error).
-pub fn close_shared(shared: &SharedConnection) {
-    if let Ok(mut guard) = shared.lock() {
-        if guard.is_some() {
-            tracing::info!("Closing persistent USB connection");
-            *guard = None;
+    #[test]
+    fn resumed_job_sends_its_settings_once() {
+        let options = PrintOptions {
+            density: Some(2),
+            speed: Some(4),
+            ..Default::default()
+        };
+        let settings = options.control_bytes(Dialect::EPSON);
+        let count = |bytes: &[u8]| bytes.windows(settings.len()).filter(|w| *w == settings).count();
+        let driver = ResetDriver {
+            out: MemoryBackend::default(),
+            writes_left: Mutex::new(3),
+        };
+        let printer = DiscoveredPrinter::for_output(PrinterAddress::Memory);
+        let mut conn = PrinterConnection::with_driver(driver, &printer);
+        conn.default_options = options;
+        let job = EncodedJob::image_no_cut(&png(200, 512), 0, false, Dialect::EPSON).unwrap();
+        assert!(conn.send(&job).is_err());
+        let unfinished = conn.take_unfinished().unwrap();
+        assert_eq!(count(&unfinished.job.to_bytes()), 0);
+
+        for mode in [ResumeMode::Continue, ResumeMode::Reprint] {
+            let memory = MemoryBackend::default();
+            let mut conn = PrinterConnection::with_driver(memory.clone(), &printer);
+            conn.default_options = options;
+            conn.send(&unfinished.resumed(mode, Dialect::EPSON)).unwrap();
+            let out = memory.contents();
+            assert!(out.starts_with(&[&[0x1b, b'@'][..], &settings].concat()), "{mode:?}");
+            // Once after each ESC @, never twice in a row
+            let doubled = [settings.clone(), settings.clone()].concat();
+            assert_eq!(count(&out), out.windows(2).filter(|w| *w == [0x1b, b'@']).count());
+            assert!(!out.windows(doubled.len()).any(|w| w == doubled), "{mode:?}");
         }
     }
-}
 
-/// Print using the shared connection. Opens a new connection if needed.
-/// On USB error, clears the connection so the next call will reopen.
-pub fn print_with_shared(
-    shared: &SharedConnection,
-    product_id: u16,
-    model_name: String,
-    f: impl FnOnce(&mut PrinterConnection) -> Result<(), String>,
-) -> Result<(), String> {
-    let mut guard = shared.lock().map_err(|e| format!("Lock poisoned: {e}"))?;
-
-    // Open connection if not already open (or if it was cleared after an error)
-    if guard.is_none() {
-        tracing::info!("No active connection — opening USB to {model_name}");
-        let conn = PrinterConnection::open(product_id, model_name.clone())?;
-        *guard = Some(conn);
-    }
-
-    // Liveness check: send an init command to verify the USB pipe is functional.
-    // If it fails, the connection is stale — close and reopen before printing.
-    {
-        let conn = guard.as_mut().unwrap();
-        if let Err(e) = conn.printer.init() {
-            tracing::warn!(
-                "USB liveness check failed, reconnecting: {e}"
-            );
-            *guard = None;
-            let conn = PrinterConnection::open(product_id, model_name.clone())?;
-            *guard = Some(conn);
-        }
-    }
-
-    let conn = guard.as_mut().unwrap();
-
-    match f(conn) {
-        Ok(()) => {
-            tracing::info!("Print job completed successfully");
+    /// Replies to each status request with the next canned byte.
+    struct StatusDriver(Mutex<Vec<u8>>);
+
+    impl Driver for StatusDriver {
+        fn name(&self) -> String {
+            "status".into()
+        }
+        fn write(&self, _data: &[u8]) -> escpos::errors::Result<()> {
+            Ok(())
+        }
+        fn read(&self, buf: &mut [u8]) -> escpos::errors::Result<usize> {
+            let mut replies = self.0.lock().unwrap();
+            if replies.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = replies.remove(0);
+            Ok(1)
+        }
+        fn flush(&self) -> escpos::errors::Result<()> {
             Ok(())
         }
-        Err(e) => {
-            // USB error — connection is likely broken. Close it so next
-            // print attempt will reopen fresh.
-            tracing::warn!("Print failed, closing connection for recovery: {e}");
-            *guard = None;
-            Err(e)
+    }
+
+    #[test]
+    fn paper_out_blocks_job() {
+        let driver = StatusDriver(Mutex::new(vec![0x1a, 0x32, 0x72]));
+        let printer = DiscoveredPrinter::for_output("tcp://printer:9100".parse().unwrap());
+        let mut conn = PrinterConnection::with_driver(driver, &printer);
+        let err = conn.check_ready().unwrap_err();
+        assert!(matches!(err, JobError::NotReady(_)));
+        assert_eq!(err.to_string(), "Paper out");
+        assert!(conn.last_status().unwrap().paper_out);
+    }
+
+    #[test]
+    fn status_is_asked_again_until_it_never_answers() {
+        let printer = DiscoveredPrinter::for_output("tcp://printer:9100".parse().unwrap());
+        let silent = || StatusDriver(Mutex::new(Vec::new()));
+        let mut conn = PrinterConnection::with_driver(silent(), &printer);
+        // No reply to the first query, e.g. while the printer starts up
+        assert_eq!(conn.poll_status().unwrap(), None);
+        conn.driver.0.lock().unwrap().extend([0x1a, 0x32, 0x72]);
+        assert!(matches!(conn.check_ready(), Err(JobError::NotReady(_))));
+
+        let mut conn = PrinterConnection::with_driver(silent(), &printer);
+        for _ in 0..STATUS_ATTEMPTS {
+            assert_eq!(conn.poll_status().unwrap(), None);
         }
+        // Given up on: not asked any more
+        conn.driver.0.lock().unwrap().extend([0x1a, 0x32, 0x72]);
+        assert!(conn.check_ready().is_ok());
     }
 }
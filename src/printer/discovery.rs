@@ -1,6 +1,15 @@
 use nusb::MaybeFuture;
 
-use crate::printer::models::{find_known_model, is_epson_device};
+use crate::config::{self, Orientation, PrinterSettings};
+use crate::printer::backend::PrinterAddress;
+use crate::printer::encoder::{Dialect, PrintOptions};
+use crate::printer::lp::{self, LpDevice};
+use crate::printer::models::{
+    find_known_model, find_model_by_name, find_vendor, model_for, PrintWidth, PrinterModel,
+    USB_CLASS_PRINTER,
+};
+use crate::printer::network;
+use crate::printer::usb;
 
 #[derive(Debug, Clone)]
 pub struct DiscoveredPrinter {
@@ -8,17 +17,204 @@ pub struct DiscoveredPrinter {
     pub product_id: u16,
     pub model_name: String,
     pub serial: Option<String>,
+    /// USB manufacturer string, used to recognise vendors without their own VID.
+    pub manufacturer: Option<String>,
+    pub address: PrinterAddress,
 }
 
+impl DiscoveredPrinter {
+    /// A printer given by address rather than found on USB: a network
+    /// printer, or a non-device output (`--dry-run`, `--output`).
+    pub fn for_output(address: PrinterAddress) -> Self {
+        Self {
+            vendor_id: 0,
+            product_id: 0,
+            model_name: address.label(),
+            serial: match &address {
+                PrinterAddress::Usb { serial, .. } => serial.clone(),
+                _ => None,
+            },
+            manufacturer: None,
+            address,
+        }
+    }
+
+    /// What identifies this printer among several open ones: its USB serial
+    /// number, or its address when it has none. Both stay the same when the
+    /// printer is unplugged and plugged back in, so reconnects find the same
+    /// physical unit.
+    pub fn key(&self) -> String {
+        self.serial.clone().unwrap_or_else(|| self.address.to_string())
+    }
+
+    /// This printer's `[[printer]]` entry from the config file.
+    pub fn settings(&self) -> Option<&'static PrinterSettings> {
+        config::printer_settings(self.serial.as_deref(), &self.address)
+    }
+
+    /// The model named in the config file, if any.
+    fn configured_model(&self) -> Option<&'static PrinterModel> {
+        let name = self.settings()?.model.as_deref()?;
+        let model = find_model_by_name(name);
+        if model.is_none() {
+            tracing::warn!("Unknown model '{name}' configured for {}", self.address);
+        }
+        model
+    }
+
+    /// The model descriptor: configured model, exact model, vendor defaults,
+    /// or generic.
+    pub fn model(&self) -> &'static PrinterModel {
+        self.configured_model().unwrap_or_else(|| {
+            model_for(self.vendor_id, self.product_id, self.manufacturer.as_deref())
+        })
+    }
+
+    /// Line width on the loaded roll: the model's, unless the printer's
+    /// config entry says otherwise.
+    pub fn print_width(&self) -> PrintWidth {
+        let model = self.model();
+        match self.settings() {
+            Some(settings) => settings.print_width(model),
+            None => model.print_width(),
+        }
+    }
+
+    /// Default density and speed from the printer's config entry.
+    pub fn print_options(&self) -> PrintOptions {
+        self.settings()
+            .map(PrinterSettings::print_options)
+            .unwrap_or_default()
+    }
+
+    /// How to encode jobs for this printer. Addresses that don't identify a
+    /// model (network, file and dry-run outputs) get the Epson dialect unless
+    /// a model is configured.
+    pub fn dialect(&self) -> Dialect {
+        let identified = self.vendor_id != 0 || self.configured_model().is_some();
+        let dialect = Dialect::for_model(identified.then(|| self.model()));
+        Dialect {
+            dots_per_line: self.print_width().dots,
+            upside_down: self
+                .settings()
+                .is_some_and(|s| s.orientation == Orientation::UpsideDown),
+            ..dialect
+        }
+    }
+}
+
+/// List printers, or only the given output when one was chosen on the
+/// command line — so dry runs never touch USB.
+pub fn scan_or_output(output: Option<&PrinterAddress>) -> Result<Vec<DiscoveredPrinter>, String> {
+    match output {
+        Some(address) => Ok(vec![DiscoveredPrinter::for_output(address.clone())]),
+        None => scan_for_printers(),
+    }
+}
+
+/// List USB printers (including ones bound to the kernel `usblp` driver)
+/// followed by reachable network printers from `NETWORK_PRINTERS`.
 pub fn scan_for_printers() -> Result<Vec<DiscoveredPrinter>, String> {
+    let lp_devices = lp::list_lp_devices();
+    let mut printers = match scan_usb_printers() {
+        Ok(printers) => printers,
+        Err(e) if !lp_devices.is_empty() || std::env::var_os("NETWORK_PRINTERS").is_some() => {
+            tracing::warn!("USB scan failed: {e}");
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    merge_lp_devices(&mut printers, lp_devices);
+    printers.extend(scan_network_printers());
+    Ok(printers)
+}
+
+/// Point USB printers that `usblp` has claimed at their `/dev/usb/lp*` node
+/// (libusb can't open them), and add any other lp devices as printers.
+fn merge_lp_devices(printers: &mut Vec<DiscoveredPrinter>, devices: Vec<LpDevice>) {
+    for dev in devices {
+        let address = PrinterAddress::Lp(dev.path.clone());
+        let same_device = |p: &&mut DiscoveredPrinter| {
+            Some(p.vendor_id) == dev.vendor_id
+                && Some(p.product_id) == dev.product_id
+                && (p.serial.is_none() || dev.serial.is_none() || p.serial == dev.serial)
+                && matches!(p.address, PrinterAddress::Usb { .. })
+        };
+
+        if let Some(existing) = printers.iter_mut().find(same_device) {
+            tracing::info!("{} is bound to usblp — using {address}", existing.model_name);
+            existing.address = address;
+            continue;
+        }
+        // Without IDs there's nothing to go on; take it as before
+        if let (Some(vid), Some(pid)) = (dev.vendor_id, dev.product_id) {
+            if !is_receipt_printer(vid, pid, None, dev.serial.as_deref(), &address) {
+                tracing::debug!("Skipping {address} ({vid:04x}:{pid:04x}): not a known receipt printer");
+                continue;
+            }
+        }
+
+        let vendor_id = dev.vendor_id.unwrap_or(0);
+        let product_id = dev.product_id.unwrap_or(0);
+        let model_name = find_known_model(vendor_id, product_id)
+            .map(|m| m.name.to_string())
+            .or(dev.product)
+            .unwrap_or_else(|| address.label());
+        tracing::info!("Found line printer: {model_name} ({address})");
+        printers.push(DiscoveredPrinter {
+            vendor_id,
+            product_id,
+            model_name,
+            serial: dev.serial,
+            manufacturer: None,
+            address,
+        });
+    }
+}
+
+fn scan_network_printers() -> Vec<DiscoveredPrinter> {
+    network::configured_printers()
+        .into_iter()
+        .filter(|(host, port)| {
+            let up = network::probe(host, *port);
+            if !up {
+                tracing::debug!("Network printer {host}:{port} not reachable");
+            }
+            up
+        })
+        .map(|(host, port)| {
+            tracing::info!("Found network printer at {host}:{port}");
+            DiscoveredPrinter::for_output(PrinterAddress::Network { host, port })
+        })
+        .collect()
+}
+
+fn scan_usb_printers() -> Result<Vec<DiscoveredPrinter>, String> {
     let devices = nusb::list_devices().wait().map_err(|e| e.to_string())?;
     let mut printers = Vec::new();
 
     for dev in devices {
         let vid = dev.vendor_id();
         let pid = dev.product_id();
+        let manufacturer = dev.manufacturer_string();
 
-        if !is_epson_device(vid) {
+        // Known vendors by ID or manufacturer string; anything else only if it
+        // exposes a printer-class interface and the config names it.
+        let vendor = find_vendor(vid, manufacturer);
+        let printer_class = dev.class() == USB_CLASS_PRINTER
+            || dev.interfaces().any(|i| i.class() == USB_CLASS_PRINTER);
+        if vendor.is_none() && !printer_class {
+            continue;
+        }
+        let serial = dev.serial_number().map(|s| s.to_string());
+        let address = PrinterAddress::Usb {
+            vendor_id: vid,
+            product_id: pid,
+            serial: serial.clone(),
+            port: usb::port_path(&dev),
+        };
+        if !is_receipt_printer(vid, pid, manufacturer, serial.as_deref(), &address) {
+            tracing::debug!("Skipping USB printer {vid:04x}:{pid:04x}: not a known receipt printer");
             continue;
         }
 
@@ -27,11 +223,15 @@ pub fn scan_for_printers() -> Result<Vec<DiscoveredPrinter>, String> {
         } else {
             dev.product_string()
                 .map(|s| s.to_string())
-                .unwrap_or_else(|| format!("Epson {pid:04x}"))
+                .unwrap_or_else(|| match vendor {
+                    Some(v) => format!("{} {pid:04x}", v.name),
+                    None => format!("USB printer {vid:04x}:{pid:04x}"),
+                })
         };
 
         tracing::info!(
-            "Found Epson device: {} (VID={:04x} PID={:04x})",
+            "Found {} device: {} (VID={:04x} PID={:04x})",
+            vendor.map(|v| v.name).unwrap_or("ESC/POS"),
             model_name,
             vid,
             pid
@@ -41,9 +241,124 @@ pub fn scan_for_printers() -> Result<Vec<DiscoveredPrinter>, String> {
             vendor_id: vid,
             product_id: pid,
             model_name,
-            serial: dev.serial_number().map(|s| s.to_string()),
+            serial,
+            manufacturer: manufacturer.map(|s| s.to_string()),
+            address,
         });
     }
 
+    keep_needed_ports(&mut printers);
     Ok(printers)
 }
+
+/// Whether a USB printer is one to print receipts on: a known vendor or
+/// model, or one with a `[[printer]]` entry. The printer class alone would
+/// take in inkjets and laser printers too.
+fn is_receipt_printer(
+    vendor_id: u16,
+    product_id: u16,
+    manufacturer: Option<&str>,
+    serial: Option<&str>,
+    address: &PrinterAddress,
+) -> bool {
+    find_vendor(vendor_id, manufacturer).is_some()
+        || find_known_model(vendor_id, product_id).is_some()
+        || config::printer_settings(serial, address).is_some()
+}
+
+/// Keep the port in a USB address only for a printer without a serial
+/// number that has an identical twin attached — the one case where nothing
+/// else tells them apart. Anywhere else the port would stop the printer
+/// being found after moving it to another socket.
+fn keep_needed_ports(printers: &mut [DiscoveredPrinter]) {
+    let ids: Vec<(u16, u16)> = printers.iter().map(|p| (p.vendor_id, p.product_id)).collect();
+    for printer in printers.iter_mut() {
+        let twins = ids
+            .iter()
+            .filter(|&&id| id == (printer.vendor_id, printer.product_id))
+            .count();
+        if let PrinterAddress::Usb { serial, port, .. } = &mut printer.address {
+            if serial.is_some() || twins < 2 {
+                *port = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lp_device(path: &str, ids: Option<(u16, u16)>) -> LpDevice {
+        LpDevice {
+            path: path.into(),
+            vendor_id: ids.map(|(v, _)| v),
+            product_id: ids.map(|(_, p)| p),
+            product: Some("Receipt printer".into()),
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn usblp_bound_printer_switches_to_lp_address() {
+        let mut printers = vec![DiscoveredPrinter {
+            vendor_id: 0x04b8,
+            product_id: 0x0e28,
+            model_name: "TM-T88VI".into(),
+            serial: Some("X1".into()),
+            manufacturer: Some("EPSON".into()),
+            address: PrinterAddress::Usb {
+                vendor_id: 0x04b8,
+                product_id: 0x0e28,
+                serial: Some("X1".into()),
+                port: None,
+            },
+        }];
+        merge_lp_devices(
+            &mut printers,
+            vec![
+                lp_device("/dev/usb/lp0", Some((0x04b8, 0x0e28))),
+                lp_device("/dev/usb/lp1", None),
+                // An office inkjet
+                lp_device("/dev/usb/lp2", Some((0x03f0, 0x0c17))),
+            ],
+        );
+
+        assert_eq!(printers.len(), 2);
+        assert_eq!(printers[0].address, PrinterAddress::Lp("/dev/usb/lp0".into()));
+        assert_eq!(printers[0].model_name, "TM-T88VI");
+        assert_eq!(printers[1].address, PrinterAddress::Lp("/dev/usb/lp1".into()));
+        assert_eq!(printers[1].model_name, "Receipt printer");
+    }
+
+    #[test]
+    fn ports_only_tell_apart_printers_without_serials() {
+        let usb = |pid: u16, serial: Option<&str>, port: &str| DiscoveredPrinter {
+            vendor_id: 0x04b8,
+            product_id: pid,
+            model_name: "TM-T88VI".into(),
+            serial: serial.map(String::from),
+            manufacturer: None,
+            address: PrinterAddress::Usb {
+                vendor_id: 0x04b8,
+                product_id: pid,
+                serial: serial.map(String::from),
+                port: Some(port.into()),
+            },
+        };
+        let mut printers = vec![
+            usb(0x0e28, None, "001-1"),
+            usb(0x0e28, None, "001-2"),
+            usb(0x0e28, Some("X1"), "001-3"),
+            usb(0x0e15, None, "001-4"),
+        ];
+        keep_needed_ports(&mut printers);
+
+        let keys: Vec<String> = printers.iter().map(DiscoveredPrinter::key).collect();
+        assert_eq!(
+            keys,
+            ["usb:04b8:0e28?port=001-1", "usb:04b8:0e28?port=001-2", "X1", "usb:04b8:0e15"]
+        );
+        assert_eq!(printers[2].address.to_string(), "usb:04b8:0e28?serial=X1");
+    }
+}
@@ -0,0 +1,982 @@
+//! Pure ESC/POS byte encoder.
+//!
+//! Compiles `PrintCommand`s, raster images and cuts into the exact byte stream
+//! the printer receives. Nothing here touches hardware, so jobs can be
+//! asserted byte-for-byte in tests, written to a `.bin` file and replayed later
+//! (`cat job.bin > /dev/usb/lp0`), or handed to any transport that can write
+//! bytes.
+//!
+//! For a printer mounted upside down, text is printed rotated (`ESC {`),
+//! images are rotated before dithering, and the printed lines, images and
+//! logos of each document are sent last-first, so the torn-off receipt
+//! reads top to bottom. The feed before each cut stays in place.
+
+use serde::{Deserialize, Serialize};
+
+use crate::printer::image_proc::{self, Dither, RasterImage, Rotation};
+use crate::printer::logo;
+use crate::printer::models::{Buzzer, Cutter, PrintControl, PrinterModel};
+use crate::printer::page::{self, Page, PageDirection};
+use crate::printer::rich_print::{self, CutMode, PrintCommand, Symbology};
+use crate::receipt_markdown::{Alignment, ReceiptBlock};
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const DC2: u8 = 0x12;
+
+/// Per-model differences in how commands are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    /// Partial cut (`GS V 66`) is available; otherwise partial cuts become full cuts.
+    pub partial_cut: bool,
+    /// The printer has an autocutter. Without one, cut commands are dropped.
+    pub cutter: bool,
+    /// Cutter supports "feed to cut position, then cut" (`GS V 65/66 n`).
+    /// Older and generic mechanisms only understand `GS V 0/1`.
+    pub feed_and_cut: bool,
+    /// QR codes via `GS ( k` are supported.
+    pub qr_codes: bool,
+    /// 1D barcodes via `GS k` are supported.
+    pub barcodes: bool,
+    /// Page mode (`ESC L` … `FF`) is supported.
+    pub page_mode: bool,
+    /// Rows per `GS v 0` band. Each band is flushed separately so large images
+    /// never overflow the printer's receive buffer.
+    pub raster_band_rows: u16,
+    /// Printable width in dots. Raster images are scaled to fit.
+    pub dots_per_line: u16,
+    /// How print density and speed are set, if at all.
+    pub print_control: PrintControl,
+    /// How the buzzer is sounded, if there is one.
+    pub buzzer: Buzzer,
+    /// The printer is mounted face-down; see the module docs. This is a
+    /// per-printer setting, never part of a model.
+    pub upside_down: bool,
+    /// How images are dithered: the job's choice, else the printer's.
+    pub dither: Dither,
+}
+
+impl Dialect {
+    /// Epson TM series: the reference ESC/POS implementation.
+    pub const EPSON: Dialect = Dialect {
+        partial_cut: true,
+        cutter: true,
+        feed_and_cut: true,
+        qr_codes: true,
+        barcodes: true,
+        page_mode: true,
+        raster_band_rows: 24,
+        dots_per_line: 512,
+        print_control: PrintControl::GsK,
+        buzzer: Buzzer::EscA,
+        upside_down: false,
+        dither: Dither::FloydSteinberg,
+    };
+
+    /// Conservative subset for generic/clone mechanisms: plain `GS V` cuts,
+    /// no QR, and shorter bands for their smaller receive buffers.
+    pub const GENERIC: Dialect = Dialect {
+        partial_cut: false,
+        cutter: true,
+        feed_and_cut: false,
+        qr_codes: false,
+        barcodes: true,
+        page_mode: false,
+        raster_band_rows: 16,
+        dots_per_line: 512,
+        print_control: PrintControl::None,
+        buzzer: Buzzer::EscB,
+        upside_down: false,
+        dither: Dither::FloydSteinberg,
+    };
+
+    /// Choose the dialect for a model, defaulting to Epson for unknown devices.
+    pub fn for_model(model: Option<&PrinterModel>) -> Dialect {
+        match model {
+            Some(m) => Dialect {
+                partial_cut: m.cutter == Cutter::Partial,
+                cutter: m.cutter != Cutter::None,
+                feed_and_cut: m.feed_and_cut,
+                qr_codes: m.qr_codes,
+                barcodes: m.barcodes,
+                page_mode: m.page_mode,
+                raster_band_rows: m.raster_band_rows,
+                dots_per_line: m.dots_per_line,
+                print_control: m.print_control,
+                buzzer: m.buzzer,
+                upside_down: false,
+                dither: Dither::FloydSteinberg,
+            },
+            None => Dialect::EPSON,
+        }
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect::EPSON
+    }
+}
+
+/// Hardware print density and speed, and image dithering, for one job.
+/// Unset fields fall back to the printer's defaults, then to whatever the
+/// printer is configured for (Floyd-Steinberg for dithering).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrintOptions {
+    /// -6 (lightest) to 6 (darkest); 0 is the printer's standard density.
+    pub density: Option<i8>,
+    /// 1 (fastest) to 13 (slowest). Slower prints dense rasters more evenly.
+    pub speed: Option<u8>,
+    pub dither: Option<Dither>,
+}
+
+impl PrintOptions {
+    pub const DENSITY_RANGE: std::ops::RangeInclusive<i8> = -6..=6;
+    pub const SPEED_RANGE: std::ops::RangeInclusive<u8> = 1..=13;
+
+    /// These options, with unset fields taken from `defaults`.
+    pub fn or(self, defaults: PrintOptions) -> PrintOptions {
+        PrintOptions {
+            density: self.density.or(defaults.density),
+            speed: self.speed.or(defaults.speed),
+            dither: self.dither.or(defaults.dither),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.density.is_none() && self.speed.is_none() && self.dither.is_none()
+    }
+
+    /// Clear a density or speed out of range, returning what was wrong
+    /// with each.
+    pub fn drop_invalid(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(density) = self.density.filter(|d| !Self::DENSITY_RANGE.contains(d)) {
+            problems.push(format!("Density must be -6 to 6, got {density}"));
+            self.density = None;
+        }
+        if let Some(speed) = self.speed.filter(|s| !Self::SPEED_RANGE.contains(s)) {
+            problems.push(format!("Speed must be 1 to 13, got {speed}"));
+            self.speed = None;
+        }
+        problems
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(density) = self.density {
+            if !Self::DENSITY_RANGE.contains(&density) {
+                return Err(format!("Density must be -6 to 6, got {density}"));
+            }
+        }
+        if let Some(speed) = self.speed {
+            if !Self::SPEED_RANGE.contains(&speed) {
+                return Err(format!("Speed must be 1 to 13, got {speed}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The commands that apply these options in `dialect`. Empty when
+    /// nothing is set or the printer can't be adjusted.
+    pub fn control_bytes(&self, dialect: Dialect) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match dialect.print_control {
+            PrintControl::None => {}
+            PrintControl::GsK => {
+                // GS ( K fn 49: density as a signed byte; fn 50: speed level
+                if let Some(density) = self.density {
+                    bytes.extend_from_slice(&[GS, b'(', b'K', 2, 0, 49, density as u8]);
+                }
+                if let Some(speed) = self.speed {
+                    bytes.extend_from_slice(&[GS, b'(', b'K', 2, 0, 50, speed]);
+                }
+            }
+            PrintControl::Dc2 => {
+                // DC2 # n: heating density in steps, 10 being standard.
+                // These mechanisms have no speed setting.
+                if let Some(density) = self.density {
+                    let level = (10 + density as i16).clamp(0, u8::MAX as i16);
+                    bytes.extend_from_slice(&[DC2, b'#', level as u8]);
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// A contiguous run of bytes. Raster bands are kept in their own chunks so
+/// the transport can flush and pace between them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub bytes: Vec<u8>,
+    /// Number of raster rows carried by this chunk (0 for text/control data).
+    pub raster_rows: usize,
+}
+
+/// An encoded print job, ready to be written to a printer or a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodedJob {
+    pub chunks: Vec<Chunk>,
+}
+
+impl EncodedJob {
+    /// The whole job as one byte stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.chunks.iter().flat_map(|c| c.bytes.iter().copied()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|c| c.bytes.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Insert printer settings after every `ESC @` (which would reset them),
+    /// or at the start of a job that never resets.
+    pub fn apply_settings(&mut self, settings: &[u8]) {
+        if settings.is_empty() {
+            return;
+        }
+        let mut applied = false;
+        for chunk in self.chunks.iter_mut().filter(|c| c.raster_rows == 0) {
+            let mut bytes = Vec::with_capacity(chunk.bytes.len() + settings.len());
+            let mut rest = chunk.bytes.as_slice();
+            while let Some(at) = rest.windows(2).position(|w| w == [ESC, b'@']) {
+                bytes.extend_from_slice(&rest[..at + 2]);
+                bytes.extend_from_slice(settings);
+                rest = &rest[at + 2..];
+                applied = true;
+            }
+            bytes.extend_from_slice(rest);
+            chunk.bytes = bytes;
+        }
+        if !applied {
+            self.chunks.insert(
+                0,
+                Chunk {
+                    bytes: settings.to_vec(),
+                    raster_rows: 0,
+                },
+            );
+        }
+    }
+
+    /// Rich receipt: init, text, feed 3, then a full cut if `cut` is set.
+    pub fn receipt(blocks: &[ReceiptBlock], max_chars: u8, cut: bool, dialect: Dialect) -> Self {
+        let commands = rich_print::generate_commands(blocks, max_chars);
+        let mut enc = Encoder::new(dialect);
+        enc.init();
+        enc.text_commands(&commands);
+        enc.feed(3);
+        if cut {
+            enc.cut(CutMode::Full);
+        }
+        enc.finish()
+    }
+
+    /// Website message: text content, optional image, then feed and cut.
+    ///
+    /// The image is preceded by a re-init so its raster never inherits text
+    /// state. An undecodable image is skipped rather than failing the job.
+    pub fn website_message(
+        blocks: &[ReceiptBlock],
+        max_chars: u8,
+        image_bytes: Option<&[u8]>,
+        dialect: Dialect,
+    ) -> Self {
+        let commands = rich_print::generate_commands(blocks, max_chars);
+        let mut enc = Encoder::new(dialect);
+        enc.init();
+        enc.text_commands(&commands);
+
+        if let Some(bytes) = image_bytes.filter(|b| !b.is_empty()) {
+            let rotation = Rotation::Quarter.flipped_if(dialect.upside_down);
+            match image_proc::prepare_raster(
+                bytes,
+                false,
+                rotation,
+                dialect.dots_per_line as u32,
+                dialect.dither,
+            ) {
+                Ok(raster) => {
+                    enc.feed(2);
+                    enc.init();
+                    enc.raster(&raster);
+                }
+                Err(e) => tracing::warn!("Image print failed (non-fatal): {e}"),
+            }
+        }
+
+        enc.feed(3);
+        enc.cut(CutMode::Full);
+        enc.finish()
+    }
+
+    /// Photo strip frame: rotated image plus `extra_feed` lines, no cut.
+    pub fn image_no_cut(
+        image_bytes: &[u8],
+        extra_feed: u8,
+        bright: bool,
+        dialect: Dialect,
+    ) -> Result<Self, String> {
+        // Rotate 90° clockwise so portrait photos print upright on receipt paper
+        let rotation = Rotation::Quarter.flipped_if(dialect.upside_down);
+        let raster = image_proc::prepare_raster(
+            image_bytes,
+            bright,
+            rotation,
+            dialect.dots_per_line as u32,
+            dialect.dither,
+        )?;
+        let mut enc = Encoder::new(dialect);
+        enc.init();
+        enc.raster(&raster);
+        enc.feed(extra_feed);
+        Ok(enc.finish())
+    }
+
+    /// A JSON receipt IR command list, verbatim after an init.
+    pub fn commands(commands: &[PrintCommand], dialect: Dialect) -> Result<Self, String> {
+        let mut enc = Encoder::new(dialect);
+        enc.init();
+        enc.commands(commands)?;
+        Ok(enc.finish())
+    }
+}
+
+/// Formatting in effect at a point in the job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct TextState {
+    bold: bool,
+    underline: bool,
+    double_size: bool,
+    alignment: Alignment,
+}
+
+/// Upside-down printing: one thing printed on its own line (or a blank
+/// feed), which can be moved without changing how it looks.
+struct Unit {
+    chunks: Vec<Chunk>,
+    prints: bool,
+}
+
+/// Incremental ESC/POS encoder.
+pub struct Encoder {
+    dialect: Dialect,
+    chunks: Vec<Chunk>,
+    current: Vec<u8>,
+    /// Line length of the page region being encoded, if any.
+    region_dots: Option<u16>,
+    text_state: TextState,
+    /// Upside-down printing: the finished units of the current document,
+    /// reversed at the next cut.
+    units: Vec<Unit>,
+    /// The unit being built has restated the formatting it starts with.
+    unit_started: bool,
+    /// The unit being built puts something on the paper.
+    unit_prints: bool,
+    /// Upside-down printing: output of finished documents.
+    done: Vec<Chunk>,
+}
+
+impl Encoder {
+    pub fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            chunks: Vec::new(),
+            current: Vec::new(),
+            region_dots: None,
+            text_state: TextState::default(),
+            units: Vec::new(),
+            unit_started: false,
+            unit_prints: false,
+            done: Vec::new(),
+        }
+    }
+
+    /// `ESC @` — reset formatting to power-on defaults. Upside down, also
+    /// `ESC { 1`, which the reset turns off.
+    pub fn init(&mut self) -> &mut Self {
+        self.text_state = TextState::default();
+        if !self.reversing() {
+            return self.raw(&[ESC, b'@']);
+        }
+        self.end_unit();
+        self.unit_started = true;
+        self.raw(&[ESC, b'@', ESC, b'{', 1]);
+        self.end_unit();
+        self
+    }
+
+    /// Append bytes verbatim.
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        if self.reversing() && !self.unit_started {
+            self.unit_started = true;
+            let state = self.state_bytes();
+            self.current.extend_from_slice(&state);
+        }
+        self.current.extend_from_slice(bytes);
+        self
+    }
+
+    /// `ESC d n` — print and feed `n` lines. Upside down, the blank lines
+    /// become a unit of their own, so they stay below the text they follow.
+    pub fn feed(&mut self, lines: u8) -> &mut Self {
+        if !self.reversing() {
+            return self.raw(&[ESC, b'd', lines]);
+        }
+        if self.unit_prints && lines > 1 {
+            self.raw(&[ESC, b'd', 1]);
+            self.end_unit();
+            self.raw(&[ESC, b'd', lines - 1]);
+        } else {
+            self.raw(&[ESC, b'd', lines]);
+        }
+        self.end_unit();
+        self
+    }
+
+    /// Whether units are being collected for upside-down printing. Page
+    /// regions are rotated by the printer instead.
+    fn reversing(&self) -> bool {
+        self.dialect.upside_down && self.region_dots.is_none()
+    }
+
+    /// Commands that restore the current formatting, for the start of a
+    /// unit that may be moved.
+    fn state_bytes(&self) -> Vec<u8> {
+        let s = self.text_state;
+        vec![
+            ESC, b'E', s.bold as u8,
+            ESC, b'-', s.underline as u8,
+            GS, b'!', if s.double_size { 0x11 } else { 0x00 },
+            ESC, b'a', alignment_code(s.alignment),
+            ESC, b'{', 1,
+        ]
+    }
+
+    /// Close the unit being built.
+    fn end_unit(&mut self) {
+        if !self.reversing() {
+            return;
+        }
+        self.flush_current();
+        let chunks = std::mem::take(&mut self.chunks);
+        if !chunks.is_empty() {
+            self.units.push(Unit {
+                chunks,
+                prints: self.unit_prints,
+            });
+        }
+        self.unit_started = false;
+        self.unit_prints = false;
+    }
+
+    /// Something that prints on its own line (image, barcode, logo, page)
+    /// gets a unit of its own.
+    fn begin_standalone(&mut self) {
+        if self.unit_prints {
+            self.end_unit();
+        }
+    }
+
+    fn end_standalone(&mut self) {
+        self.unit_prints = true;
+        self.end_unit();
+    }
+
+    /// `ESC a` mirrored for images and logos upside down: the printer only
+    /// rotates text, so a left-aligned image must be printed on the right.
+    fn mirror_alignment(&mut self) {
+        if self.reversing() {
+            let mirrored = match self.text_state.alignment {
+                Alignment::Left => Alignment::Right,
+                Alignment::Center => Alignment::Center,
+                Alignment::Right => Alignment::Left,
+            };
+            self.raw(&[ESC, b'a', alignment_code(mirrored)]);
+        }
+    }
+
+    /// Send the current document's units last-first. Non-printing units at
+    /// either end (the reset before it, the feed to the cutter after it)
+    /// stay where they are.
+    fn end_document(&mut self) {
+        if !self.reversing() {
+            return;
+        }
+        self.end_unit();
+        let mut units = std::mem::take(&mut self.units);
+        let lead = units.iter().take_while(|u| !u.prints).count();
+        let trail = units[lead..].iter().rev().take_while(|u| !u.prints).count();
+        let end = units.len() - trail;
+        units[lead..end].reverse();
+        self.done.extend(units.into_iter().flat_map(|u| u.chunks));
+    }
+
+    pub fn cut(&mut self, mode: CutMode) -> &mut Self {
+        if !self.dialect.cutter {
+            return self;
+        }
+        let partial = mode == CutMode::Partial && self.dialect.partial_cut;
+        let cmd: &[u8] = match (self.dialect.feed_and_cut, partial) {
+            (true, false) => &[GS, b'V', 65, 0],
+            (true, true) => &[GS, b'V', 66, 0],
+            (false, false) => &[GS, b'V', 0],
+            (false, true) => &[GS, b'V', 1],
+        };
+        if !self.reversing() {
+            return self.raw(cmd);
+        }
+        self.end_document();
+        self.done.push(Chunk {
+            bytes: cmd.to_vec(),
+            raster_rows: 0,
+        });
+        self
+    }
+
+    /// Encode one command. Fails only for images that cannot be decoded or
+    /// features the dialect lacks.
+    pub fn command(&mut self, cmd: &PrintCommand) -> Result<&mut Self, String> {
+        match cmd {
+            PrintCommand::SetBold(on) => {
+                self.text_state.bold = *on;
+                self.raw(&[ESC, b'E', *on as u8])
+            }
+            PrintCommand::SetUnderline(on) => {
+                self.text_state.underline = *on;
+                self.raw(&[ESC, b'-', *on as u8])
+            }
+            PrintCommand::SetDoubleSize(on) => {
+                self.text_state.double_size = *on;
+                self.raw(&[GS, b'!', if *on { 0x11 } else { 0x00 }])
+            }
+            PrintCommand::SetAlignment(align) => {
+                self.text_state.alignment = *align;
+                self.raw(&[ESC, b'a', alignment_code(*align)])
+            }
+            PrintCommand::Write(text) => {
+                self.unit_prints = true;
+                self.raw(text.as_bytes())
+            }
+            PrintCommand::Feed => self.feed(1),
+            PrintCommand::FeedLines(n) => self.feed(*n),
+            PrintCommand::Image { data, bright } => {
+                let width = self.region_dots.unwrap_or(self.dialect.dots_per_line);
+                let rotation = Rotation::None.flipped_if(self.reversing());
+                let raster = image_proc::prepare_raster(data, *bright, rotation, width as u32, self.dialect.dither)?;
+                self.raster(&raster)
+            }
+            PrintCommand::Cut(mode) => self.cut(*mode),
+            PrintCommand::Barcode { symbology, data } => {
+                self.begin_standalone();
+                self.barcode(*symbology, data)?;
+                self.end_standalone();
+                &mut *self
+            }
+            PrintCommand::OpenDrawer { pin, on_ms, off_ms } => {
+                let m = if *pin == 5 { 1 } else { 0 };
+                let steps = |ms: u16| (ms / 2).min(255) as u8;
+                self.raw(&[ESC, b'p', m, steps(*on_ms), steps(*off_ms)])
+            }
+            PrintCommand::Beep { times, duration } => match self.dialect.buzzer {
+                Buzzer::EscB => self.raw(&[ESC, b'B', *times, *duration]),
+                // Function 48: beep pattern `duration`, `times` times, a second apart
+                Buzzer::EscA => {
+                    let pattern = b'0' + (*duration).clamp(1, 9);
+                    self.raw(&[ESC, b'(', b'A', 4, 0, 48, pattern, *times, 10])
+                }
+                Buzzer::None => return Err("This printer has no buzzer".into()),
+            },
+            PrintCommand::NvLogo(key) => {
+                let cmd = logo::print_command(key)?;
+                self.begin_standalone();
+                self.mirror_alignment();
+                self.raw(&cmd);
+                self.end_standalone();
+                &mut *self
+            }
+            PrintCommand::Page(page) => self.page(page)?,
+        };
+        Ok(self)
+    }
+
+    /// `ESC L`, each region's area and direction followed by its content,
+    /// then `FF` to print the page. Upside down, each region is moved to
+    /// the opposite corner of the page and turned 180°.
+    fn page(&mut self, page: &Page<PrintCommand>) -> Result<&mut Self, String> {
+        if !self.dialect.page_mode {
+            return Err("Page mode is not supported by this printer".into());
+        }
+        if self.region_dots.is_some() {
+            return Err("Pages can't be nested".into());
+        }
+        let width = self.dialect.dots_per_line;
+        page.validate(width)?;
+
+        self.begin_standalone();
+        self.raw(&page::BEGIN);
+        self.raw(&page.area_command(width));
+        for region in &page.regions {
+            let mut area = region.map(|_, _| Vec::<()>::new());
+            if self.dialect.upside_down {
+                area.x = width - region.x - region.width;
+                area.y = page.height - region.y - region.height;
+                area.direction = PageDirection::from_code(region.direction.code() + 2);
+            }
+            self.raw(&area.area_command());
+            self.region_dots = Some(region.line_dots());
+            let result = self.commands(&region.content).map(|_| ());
+            self.region_dots = None;
+            result?;
+        }
+        self.raw(&page::END);
+        self.end_standalone();
+        Ok(self)
+    }
+
+    pub fn commands(&mut self, commands: &[PrintCommand]) -> Result<&mut Self, String> {
+        for cmd in commands {
+            self.command(cmd)?;
+        }
+        Ok(self)
+    }
+
+    /// Encode commands generated from text layout, which never fail.
+    fn text_commands(&mut self, commands: &[PrintCommand]) {
+        for cmd in commands {
+            if let Err(e) = self.command(cmd) {
+                tracing::warn!("Skipping unencodable command: {e}");
+            }
+        }
+    }
+
+    /// `GS v 0` raster image, split into bands of `raster_band_rows`.
+    /// Upside down, the image is expected to be rotated already.
+    pub fn raster(&mut self, raster: &RasterImage) -> &mut Self {
+        let width_bytes = raster.width_bytes;
+        let band_rows = self.dialect.raster_band_rows.max(1) as usize;
+        self.begin_standalone();
+        self.mirror_alignment();
+
+        for band_start in (0..raster.height).step_by(band_rows) {
+            let band_end = (band_start + band_rows).min(raster.height);
+            let band_h = band_end - band_start;
+
+            self.flush_current();
+            let mut bytes = Vec::with_capacity(8 + band_h * width_bytes);
+            bytes.extend_from_slice(&[
+                GS,
+                b'v',
+                b'0',
+                0x00,
+                (width_bytes & 0xFF) as u8,
+                ((width_bytes >> 8) & 0xFF) as u8,
+                (band_h & 0xFF) as u8,
+                ((band_h >> 8) & 0xFF) as u8,
+            ]);
+            bytes.extend_from_slice(&raster.data[band_start * width_bytes..band_end * width_bytes]);
+            self.chunks.push(Chunk {
+                bytes,
+                raster_rows: band_h,
+            });
+        }
+        self.end_standalone();
+        self
+    }
+
+    fn barcode(&mut self, symbology: Symbology, data: &str) -> Result<&mut Self, String> {
+        symbology.validate(data)?;
+
+        if symbology == Symbology::Qr {
+            if !self.dialect.qr_codes {
+                return Err("QR codes are not supported by this printer".into());
+            }
+            return Ok(self.qr_code(data));
+        }
+        if !self.dialect.barcodes {
+            return Err("Barcodes are not supported by this printer".into());
+        }
+
+        let m = match symbology {
+            Symbology::UpcA => 65,
+            Symbology::UpcE => 66,
+            Symbology::Ean13 => 67,
+            Symbology::Ean8 => 68,
+            Symbology::Code39 => 69,
+            Symbology::Itf => 70,
+            Symbology::Codabar => 71,
+            Symbology::Qr => unreachable!(),
+        };
+        // HRI below, 80-dot height, module width 3
+        self.raw(&[GS, b'H', 2, GS, b'h', 80, GS, b'w', 3]);
+        self.raw(&[GS, b'k', m, data.len() as u8]);
+        self.raw(data.as_bytes());
+        Ok(self)
+    }
+
+    /// `GS ( k` QR code: model 2, module size 6, error correction M.
+    fn qr_code(&mut self, data: &str) -> &mut Self {
+        let len = data.len() + 3;
+        self.raw(&[GS, b'(', b'k', 4, 0, 49, 65, 50, 0]);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 67, 6]);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 69, 49]);
+        self.raw(&[GS, b'(', b'k', (len & 0xFF) as u8, (len >> 8) as u8, 49, 80, 48]);
+        self.raw(data.as_bytes());
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 81, 48])
+    }
+
+    fn flush_current(&mut self) {
+        if !self.current.is_empty() {
+            self.chunks.push(Chunk {
+                bytes: std::mem::take(&mut self.current),
+                raster_rows: 0,
+            });
+        }
+    }
+
+    pub fn finish(mut self) -> EncodedJob {
+        self.end_document();
+        self.flush_current();
+        let mut chunks = self.done;
+        chunks.append(&mut self.chunks);
+        EncodedJob { chunks }
+    }
+}
+
+fn alignment_code(alignment: Alignment) -> u8 {
+    match alignment {
+        Alignment::Left => 0,
+        Alignment::Center => 1,
+        Alignment::Right => 2,
+    }
+}
+
+/// Encode a command list to a single byte stream (no implicit init).
+pub fn encode_commands(commands: &[PrintCommand], dialect: Dialect) -> Result<Vec<u8>, String> {
+    let mut enc = Encoder::new(dialect);
+    enc.commands(commands)?;
+    Ok(enc.finish().to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt_markdown::parse_receipt_markdown;
+
+    #[test]
+    fn golden_bold_line() {
+        let cmds = vec![
+            PrintCommand::SetBold(true),
+            PrintCommand::Write("TOTAL".into()),
+            PrintCommand::SetBold(false),
+            PrintCommand::Feed,
+        ];
+        let bytes = encode_commands(&cmds, Dialect::EPSON).unwrap();
+        assert_eq!(
+            bytes,
+            b"\x1bE\x01TOTAL\x1bE\x00\x1bd\x01".to_vec()
+        );
+    }
+
+    #[test]
+    fn golden_heading_receipt() {
+        let blocks = parse_receipt_markdown("# HI");
+        let bytes = EncodedJob::receipt(&blocks, 42, true, Dialect::EPSON).to_bytes();
+        let expected: &[u8] = b"\x1b@\
+\x1ba\x01\x1bE\x01\x1d!\x11HI\x1bE\x00\x1d!\x00\x1bd\x01\x1ba\x00\
+\x1bd\x03\x1dVA\x00";
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn cut_follows_dialect() {
+        let generic = Dialect {
+            partial_cut: false,
+            feed_and_cut: false,
+            ..Dialect::EPSON
+        };
+        let cut = [PrintCommand::Cut(CutMode::Partial)];
+        assert_eq!(encode_commands(&cut, Dialect::EPSON).unwrap(), vec![0x1d, b'V', 66, 0]);
+        assert_eq!(encode_commands(&cut, generic).unwrap(), vec![0x1d, b'V', 0]);
+    }
+
+    #[test]
+    fn raster_is_split_into_bands() {
+        let raster = RasterImage {
+            width_bytes: 2,
+            height: 30,
+            data: vec![0xAA; 60],
+        };
+        let mut enc = Encoder::new(Dialect::EPSON);
+        enc.init().raster(&raster);
+        let job = enc.finish();
+
+        // init chunk + 24-row band + 6-row band
+        assert_eq!(job.chunks.len(), 3);
+        assert_eq!(job.chunks[1].raster_rows, 24);
+        assert_eq!(&job.chunks[1].bytes[..8], &[0x1d, b'v', b'0', 0, 2, 0, 24, 0]);
+        assert_eq!(job.chunks[1].bytes.len(), 8 + 48);
+        assert_eq!(job.chunks[2].raster_rows, 6);
+        assert_eq!(job.len(), 2 + 56 + 20);
+    }
+
+    #[test]
+    fn qr_code_bytes() {
+        let cmds = [PrintCommand::Barcode {
+            symbology: Symbology::Qr,
+            data: "hi".into(),
+        }];
+        let bytes = encode_commands(&cmds, Dialect::EPSON).unwrap();
+        // Store command carries length = data + 3
+        let store = [0x1d, b'(', b'k', 5, 0, 49, 80, 48, b'h', b'i'];
+        assert!(bytes.windows(store.len()).any(|w| w == store));
+        assert!(bytes.ends_with(&[0x1d, b'(', b'k', 3, 0, 49, 81, 48]));
+
+        let no_qr = Dialect {
+            qr_codes: false,
+            ..Dialect::EPSON
+        };
+        assert!(encode_commands(&cmds, no_qr).is_err());
+    }
+
+    #[test]
+    fn drawer_kick_and_beep_bytes() {
+        let cmds = [
+            PrintCommand::OpenDrawer {
+                pin: 5,
+                on_ms: 100,
+                off_ms: 200,
+            },
+            PrintCommand::Beep { times: 3, duration: 2 },
+        ];
+        let bytes = encode_commands(&cmds, Dialect::EPSON).unwrap();
+        assert_eq!(bytes, b"\x1bp\x01\x32\x64\x1b(A\x04\x00\x30\x32\x03\x0a");
+
+        let beep = &cmds[1..];
+        assert_eq!(encode_commands(beep, Dialect::GENERIC).unwrap(), b"\x1bB\x03\x02");
+        let silent = Dialect {
+            buzzer: Buzzer::None,
+            ..Dialect::EPSON
+        };
+        assert!(encode_commands(beep, silent).is_err());
+    }
+
+    #[test]
+    fn print_options_follow_dialect() {
+        let options = PrintOptions {
+            density: Some(-2),
+            speed: Some(9),
+            dither: Some(Dither::Atkinson),
+        };
+        assert_eq!(
+            options.control_bytes(Dialect::EPSON),
+            vec![0x1d, b'(', b'K', 2, 0, 49, 0xfe, 0x1d, b'(', b'K', 2, 0, 50, 9]
+        );
+        let dc2 = Dialect {
+            print_control: PrintControl::Dc2,
+            ..Dialect::EPSON
+        };
+        assert_eq!(options.control_bytes(dc2), vec![0x12, b'#', 8]);
+        assert!(options.control_bytes(Dialect::GENERIC).is_empty());
+
+        assert!(PrintOptions { density: Some(7), ..Default::default() }.validate().is_err());
+        assert!(PrintOptions { speed: Some(0), ..Default::default() }.validate().is_err());
+        // Out-of-range densities clamp instead of overflowing
+        let extreme = PrintOptions { density: Some(120), ..Default::default() };
+        assert_eq!(extreme.control_bytes(dc2), vec![0x12, b'#', 130]);
+        let mut bad = PrintOptions { density: Some(120), speed: Some(200), dither: None };
+        assert_eq!(bad.drop_invalid().len(), 2);
+        assert!(bad.is_empty());
+        let defaults = PrintOptions { density: Some(3), speed: Some(2), dither: None };
+        assert_eq!(PrintOptions { speed: Some(5), ..Default::default() }.or(defaults).density, Some(3));
+        assert_eq!(options.or(defaults).dither, Some(Dither::Atkinson));
+    }
+
+    #[test]
+    fn settings_follow_every_reset() {
+        let raster = RasterImage {
+            width_bytes: 1,
+            height: 2,
+            data: vec![0xFF; 2],
+        };
+        let mut enc = Encoder::new(Dialect::EPSON);
+        enc.init().raster(&raster).init().feed(1);
+        let mut job = enc.finish();
+        job.apply_settings(&[0x12, b'#', 10]);
+        assert_eq!(&job.chunks[0].bytes, b"\x1b@\x12#\x0a");
+        assert_eq!(&job.chunks[2].bytes, b"\x1b@\x12#\x0a\x1bd\x01");
+
+        let mut bare = EncodedJob::default();
+        bare.apply_settings(&[1, 2]);
+        assert_eq!(bare.to_bytes(), vec![1, 2]);
+    }
+
+    #[test]
+    fn page_mode_bytes() {
+        let page = Page::side_by_side(
+            512,
+            60,
+            256,
+            vec![PrintCommand::Write("L".into())],
+            vec![PrintCommand::Write("R".into())],
+        );
+        let bytes = encode_commands(&[PrintCommand::Page(page.clone())], Dialect::EPSON).unwrap();
+        let expected = [
+            &b"\x1bL"[..],
+            b"\x1bT\x00\x1bW\x00\x00\x00\x00\x00\x02\x3c\x00",
+            b"\x1bT\x00\x1bW\x00\x00\x00\x00\x00\x01\x3c\x00L",
+            b"\x1bT\x00\x1bW\x00\x01\x00\x00\x00\x01\x3c\x00R",
+            b"\x0c",
+        ]
+        .concat();
+        assert_eq!(bytes, expected);
+
+        assert!(encode_commands(&[PrintCommand::Page(page)], Dialect::GENERIC).is_err());
+    }
+
+    #[test]
+    fn ean13_barcode_bytes() {
+        let cmds = [PrintCommand::Barcode {
+            symbology: Symbology::Ean13,
+            data: "400638133393".into(),
+        }];
+        let bytes = encode_commands(&cmds, Dialect::EPSON).unwrap();
+        assert!(bytes.ends_with(b"\x1dkC\x0c400638133393"));
+    }
+
+    #[test]
+    fn upside_down_lines_are_sent_last_first() {
+        let dialect = Dialect {
+            upside_down: true,
+            ..Dialect::EPSON
+        };
+        let cmds = vec![
+            PrintCommand::Write("ONE".into()),
+            PrintCommand::Feed,
+            PrintCommand::SetBold(true),
+            PrintCommand::Write("TWO".into()),
+            PrintCommand::FeedLines(3),
+            PrintCommand::Cut(CutMode::Full),
+        ];
+        let bytes = encode_commands(&cmds, dialect).unwrap();
+        // Each line restates its formatting, then the feed before the cut
+        // stays last
+        let state = |bold: u8| [ESC, b'E', bold, ESC, b'-', 0, GS, b'!', 0, ESC, b'a', 0, ESC, b'{', 1];
+        let expected = [
+            &state(1)[..],
+            b"\x1bE\x01TWO\x1bd\x01",
+            &state(0),
+            b"ONE\x1bd\x01",
+            &state(1),
+            b"\x1bd\x02",
+            b"\x1dVA\x00",
+        ]
+        .concat();
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,196 @@
+//! Flow control for raster data.
+//!
+//! A raster band is only sent once the printer has room for it. Printers that
+//! answer `GS ( H` say when each band has been processed, so the connection
+//! waits for those replies whenever a buffer's worth is outstanding. Others
+//! are paced from the model's print speed, assuming the buffer drains as fast
+//! as the paper moves. The same estimate says how long a printer is still
+//! busy after a job, which is all the next job has to wait for.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::printer::models::PrinterModel;
+
+/// Time allowed for a `GS ( H` reply beyond printing what's ahead of it.
+pub const RESPONSE_SLACK: Duration = Duration::from_secs(2);
+
+/// `GS ( H` fn 48: ask for a transmission response carrying `id`. IDs are
+/// four printable characters; this uses the decimal digits of a counter.
+pub fn response_request(id: u32) -> [u8; 11] {
+    let [d1, d2, d3, d4] = response_id(id);
+    [0x1d, b'(', b'H', 6, 0, 48, 48, d1, d2, d3, d4]
+}
+
+/// The four ID bytes a request for `id` carries, and its reply echoes.
+pub fn response_id(id: u32) -> [u8; 4] {
+    let digits = format!("{:04}", id % 10_000);
+    let b = digits.as_bytes();
+    [b[0], b[1], b[2], b[3]]
+}
+
+/// The ID in a complete `GS ( H` reply: `37h 22h d1 d2 d3 d4 00h`.
+pub fn parse_response(reply: &[u8]) -> Option<[u8; 4]> {
+    match reply {
+        [0x37, 0x22, d1, d2, d3, d4, 0x00] => Some([*d1, *d2, *d3, *d4]),
+        _ => None,
+    }
+}
+
+/// Sleep until `at`, if it's still ahead.
+pub fn sleep_until(at: Instant) {
+    let now = Instant::now();
+    if at > now {
+        std::thread::sleep(at - now);
+    }
+}
+
+/// How fast a printer empties its receive buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pace {
+    dpi: u16,
+    mm_per_s: f64,
+    pub buffer_bytes: usize,
+}
+
+impl Pace {
+    pub fn for_model(model: &PrinterModel) -> Self {
+        Self {
+            dpi: model.dpi,
+            mm_per_s: model.print_speed_mm_s.max(1) as f64,
+            buffer_bytes: model.receive_buffer_bytes as usize,
+        }
+    }
+
+    /// The pace at a `GS ( K` speed level (1 fastest to 13 slowest). The
+    /// model's speed is taken as level 1, slowing linearly to about a third
+    /// at 13.
+    pub fn at_level(self, level: Option<u8>) -> Self {
+        let level = level.unwrap_or(1).clamp(1, 13) as f64;
+        Self {
+            mm_per_s: self.mm_per_s * (20.0 - level) / 19.0,
+            ..self
+        }
+    }
+
+    /// How long `rows` raster rows take to print.
+    pub fn print_time(&self, rows: usize) -> Duration {
+        let mm = rows as f64 * 25.4 / self.dpi.max(1) as f64;
+        Duration::from_secs_f64(mm / self.mm_per_s)
+    }
+}
+
+/// Estimates what the printer still holds, band by band.
+#[derive(Debug)]
+pub struct Pacer {
+    pace: Pace,
+    /// When each band still in the buffer will have printed, and its size.
+    queued: VecDeque<(Instant, usize)>,
+    idle_at: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new(pace: Pace) -> Self {
+        Self {
+            pace,
+            queued: VecDeque::new(),
+            idle_at: None,
+        }
+    }
+
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+
+    /// Change speed for the next bands, e.g. for a job's speed level.
+    pub fn set_pace(&mut self, pace: Pace) {
+        self.pace = pace;
+    }
+
+    /// The earliest time a band of `bytes` fits in the buffer.
+    pub fn ready_at(&mut self, bytes: usize, now: Instant) -> Instant {
+        self.queued.retain(|&(done, _)| done > now);
+        let limit = self.pace.buffer_bytes.saturating_sub(bytes);
+        let mut held: usize = self.queued.iter().map(|&(_, b)| b).sum();
+        let mut at = now;
+        for &(done, b) in &self.queued {
+            if held <= limit {
+                break;
+            }
+            held -= b;
+            at = done;
+        }
+        at
+    }
+
+    /// Record a band of `rows` handed to the printer at `now`.
+    pub fn sent(&mut self, rows: usize, bytes: usize, now: Instant) {
+        let start = self.idle_at.map_or(now, |at| at.max(now));
+        let done = start + self.pace.print_time(rows);
+        self.queued.push_back((done, bytes));
+        self.idle_at = Some(done);
+    }
+
+    /// The printer confirmed it has processed everything sent.
+    pub fn drained(&mut self, now: Instant) {
+        self.queued.clear();
+        self.idle_at = Some(now);
+    }
+
+    /// When everything sent so far should have printed.
+    pub fn idle_at(&self) -> Option<Instant> {
+        self.idle_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pace() -> Pace {
+        Pace {
+            dpi: 180,
+            mm_per_s: 127.0,
+            buffer_bytes: 3 * 1000,
+        }
+    }
+
+    #[test]
+    fn print_time_follows_speed_level() {
+        // 180 rows at 180 dpi is 25.4 mm: 0.2 s at 127 mm/s
+        let ms = pace().print_time(180).as_secs_f64() * 1000.0;
+        assert_eq!(ms.round(), 200.0);
+        let slowest = pace().at_level(Some(13));
+        assert!(slowest.print_time(180) > 2 * pace().print_time(180));
+    }
+
+    #[test]
+    fn bands_wait_for_room_in_the_buffer() {
+        let now = Instant::now();
+        let mut pacer = Pacer::new(pace());
+        // Three 1000-byte bands fill the buffer without waiting
+        for _ in 0..3 {
+            assert_eq!(pacer.ready_at(1000, now), now);
+            pacer.sent(18, 1000, now);
+        }
+        // The fourth waits for the first to print (18 rows, about 20 ms)
+        let band = pace().print_time(18);
+        let first_done = now + band;
+        assert_eq!(pacer.ready_at(1000, now), first_done);
+        assert_eq!(pacer.idle_at(), Some(now + band * 3));
+
+        // Once the first has printed there's room again
+        assert_eq!(pacer.ready_at(1000, first_done), first_done);
+        pacer.drained(first_done);
+        assert_eq!(pacer.idle_at(), Some(first_done));
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let request = response_request(42);
+        assert_eq!(&request[..7], &[0x1d, b'(', b'H', 6, 0, 48, 48]);
+        assert_eq!(&request[7..], b"0042");
+        assert_eq!(parse_response(&[0x37, 0x22, b'0', b'0', b'4', b'2', 0]), Some(*b"0042"));
+        assert_eq!(parse_response(&[0x37, 0x22, b'0', 0]), None);
+    }
+}
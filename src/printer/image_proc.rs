@@ -1,22 +1,152 @@
+use std::fmt;
+use std::sync::LazyLock;
+
 use image::{DynamicImage, GrayImage};
+use serde::{Deserialize, Serialize};
+
+/// How a grayscale image is reduced to black and white dots.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dither {
+    /// Error diffusion to four neighbours; the all-round default.
+    #[default]
+    FloydSteinberg,
+    /// Floyd-Steinberg scanning every other row right to left, which breaks
+    /// up the diagonal "worms" in flat areas.
+    FloydSteinbergSerpentine,
+    /// Diffuses only 3/4 of the error: crisper, with clean highlights and
+    /// shadows. Good for faces on thermal paper.
+    Atkinson,
+    /// Error diffusion over two rows below: smooth, slightly soft.
+    JarvisJudiceNinke,
+    /// Like Jarvis-Judice-Ninke, a little sharper.
+    Stucki,
+    /// Three-row Sierra: close to Jarvis-Judice-Ninke, cheaper.
+    Sierra,
+    /// Ordered dither with a 4x4 Bayer matrix: a regular crosshatch.
+    Bayer4x4,
+    /// Ordered dither with an 8x8 Bayer matrix: finer crosshatch, more tones.
+    Bayer8x8,
+    /// Ordered dither with a blue-noise mask: grainy like film, no pattern.
+    BlueNoise,
+    /// Plain 50% threshold, for line art and text.
+    Threshold,
+}
 
-/// 512px wide — leaves margin for TM-T88VI's non-printable edges on 80mm paper.
-const PRINTER_WIDTH_PX: u32 = 512;
+impl Dither {
+    pub const ALL: [Dither; 10] = [
+        Dither::FloydSteinberg,
+        Dither::FloydSteinbergSerpentine,
+        Dither::Atkinson,
+        Dither::JarvisJudiceNinke,
+        Dither::Stucki,
+        Dither::Sierra,
+        Dither::Bayer4x4,
+        Dither::Bayer8x8,
+        Dither::BlueNoise,
+        Dither::Threshold,
+    ];
+
+    /// Reduce a grayscale image to 1-bit (0 or 255) in place.
+    pub fn apply(self, img: &mut GrayImage) {
+        match self {
+            Dither::FloydSteinberg => diffuse(img, &FLOYD_STEINBERG, false),
+            Dither::FloydSteinbergSerpentine => diffuse(img, &FLOYD_STEINBERG, true),
+            Dither::Atkinson => diffuse(img, &ATKINSON, false),
+            Dither::JarvisJudiceNinke => diffuse(img, &JARVIS_JUDICE_NINKE, false),
+            Dither::Stucki => diffuse(img, &STUCKI, false),
+            Dither::Sierra => diffuse(img, &SIERRA, false),
+            Dither::Bayer4x4 => ordered(img, &BAYER_4X4, 4),
+            Dither::Bayer8x8 => ordered(img, &BAYER_8X8, 8),
+            Dither::BlueNoise => ordered(img, &BLUE_NOISE, BLUE_NOISE_SIZE),
+            Dither::Threshold => {
+                for pixel in img.pixels_mut() {
+                    pixel[0] = if pixel[0] > 127 { 255 } else { 0 };
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Dither {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dither::FloydSteinberg => "Floyd-Steinberg",
+            Dither::FloydSteinbergSerpentine => "Floyd-Steinberg (serpentine)",
+            Dither::Atkinson => "Atkinson",
+            Dither::JarvisJudiceNinke => "Jarvis-Judice-Ninke",
+            Dither::Stucki => "Stucki",
+            Dither::Sierra => "Sierra",
+            Dither::Bayer4x4 => "Bayer 4x4",
+            Dither::Bayer8x8 => "Bayer 8x8",
+            Dither::BlueNoise => "Blue noise",
+            Dither::Threshold => "Threshold",
+        })
+    }
+}
+
+/// An error diffusion kernel: where the error of a pixel goes, as
+/// (dx, dy, weight), and what the weights are divided by.
+struct Kernel {
+    taps: &'static [(i32, i32, i32)],
+    divisor: i32,
+}
+
+const FLOYD_STEINBERG: Kernel = Kernel {
+    taps: &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)],
+    divisor: 16,
+};
+
+/// Six neighbours an eighth each; the remaining quarter is dropped.
+const ATKINSON: Kernel = Kernel {
+    taps: &[(1, 0, 1), (2, 0, 1), (-1, 1, 1), (0, 1, 1), (1, 1, 1), (0, 2, 1)],
+    divisor: 8,
+};
+
+#[rustfmt::skip]
+const JARVIS_JUDICE_NINKE: Kernel = Kernel {
+    taps: &[
+        (1, 0, 7), (2, 0, 5),
+        (-2, 1, 3), (-1, 1, 5), (0, 1, 7), (1, 1, 5), (2, 1, 3),
+        (-2, 2, 1), (-1, 2, 3), (0, 2, 5), (1, 2, 3), (2, 2, 1),
+    ],
+    divisor: 48,
+};
+
+#[rustfmt::skip]
+const STUCKI: Kernel = Kernel {
+    taps: &[
+        (1, 0, 8), (2, 0, 4),
+        (-2, 1, 2), (-1, 1, 4), (0, 1, 8), (1, 1, 4), (2, 1, 2),
+        (-2, 2, 1), (-1, 2, 2), (0, 2, 4), (1, 2, 2), (2, 2, 1),
+    ],
+    divisor: 42,
+};
+
+#[rustfmt::skip]
+const SIERRA: Kernel = Kernel {
+    taps: &[
+        (1, 0, 5), (2, 0, 3),
+        (-2, 1, 2), (-1, 1, 4), (0, 1, 5), (1, 1, 4), (2, 1, 2),
+        (-1, 2, 2), (0, 2, 3), (1, 2, 2),
+    ],
+    divisor: 32,
+};
 
 /// Preprocess an image for thermal printing:
 /// 1. Decode from raw bytes (PNG, JPEG, etc.)
-/// 2. Resize to printer width (512px), maintaining aspect ratio
+/// 2. Resize to the printer width in dots, maintaining aspect ratio
 /// 3. Convert to grayscale
 /// 4. Adaptive contrast + gamma based on image brightness
 /// 5. Floyd-Steinberg dithering to 1-bit
 /// 6. Re-encode as PNG for escpos bit_image_from_bytes_option
-pub fn preprocess_for_thermal(raw_bytes: &[u8]) -> Result<Vec<u8>, String> {
+pub fn preprocess_for_thermal(raw_bytes: &[u8], width: u32) -> Result<Vec<u8>, String> {
     let img =
         image::load_from_memory(raw_bytes).map_err(|e| format!("Image decode failed: {e}"))?;
 
     // Resize to printer width, maintaining aspect ratio
     let img = img.resize(
-        PRINTER_WIDTH_PX,
+        width,
         u32::MAX,
         image::imageops::FilterType::Lanczos3,
     );
@@ -25,7 +155,7 @@ pub fn preprocess_for_thermal(raw_bytes: &[u8]) -> Result<Vec<u8>, String> {
     let mut gray = img.to_luma8();
 
     // Full thermal preprocessing pipeline (adaptive)
-    thermal_pipeline(&mut gray);
+    thermal_pipeline(&mut gray, Dither::default());
 
     // Re-encode as PNG
     let dithered = DynamicImage::ImageLuma8(gray);
@@ -39,13 +169,13 @@ pub fn preprocess_for_thermal(raw_bytes: &[u8]) -> Result<Vec<u8>, String> {
 
 /// Full thermal print preprocessing: auto-levels → adaptive contrast/gamma → sharpen → dither.
 /// Call this on an already-resized `GrayImage` before encoding to PNG for escpos.
-pub fn dither_for_thermal(img: &mut GrayImage) {
-    thermal_pipeline(img);
+pub fn dither_for_thermal(img: &mut GrayImage, dither: Dither) {
+    thermal_pipeline(img, dither);
 }
 
 /// Indoor-bright thermal pipeline. Forces aggressive gamma lift and gentler
 /// contrast so dim indoor photos come out readable on thermal paper.
-pub fn dither_for_thermal_bright(img: &mut GrayImage) {
+pub fn dither_for_thermal_bright(img: &mut GrayImage, dither: Dither) {
     auto_levels(img);
     let mean = mean_brightness(img);
     // Indoor override: always use bright settings regardless of measured brightness
@@ -56,13 +186,138 @@ pub fn dither_for_thermal_bright(img: &mut GrayImage) {
     apply_contrast(img, contrast);
     apply_gamma(img, gamma);
     unsharp_mask(img, 0.5);
-    floyd_steinberg_dither(img);
+    dither.apply(img);
+}
+
+/// A 1-bit packed raster image, row-major, MSB = leftmost dot.
+/// This is the payload format of ESC/POS `GS v 0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RasterImage {
+    pub width_bytes: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+/// Clockwise rotation applied to an image before it is dithered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    /// 90°: portrait photos print upright along receipt paper.
+    Quarter,
+    Half,
+    ThreeQuarter,
+}
+
+impl Rotation {
+    /// The same rotation turned a further 180° if `upside_down`, for
+    /// printers mounted face-down.
+    pub fn flipped_if(self, upside_down: bool) -> Self {
+        if !upside_down {
+            return self;
+        }
+        match self {
+            Rotation::None => Rotation::Half,
+            Rotation::Quarter => Rotation::ThreeQuarter,
+            Rotation::Half => Rotation::None,
+            Rotation::ThreeQuarter => Rotation::Quarter,
+        }
+    }
+
+    fn is_sideways(self) -> bool {
+        matches!(self, Rotation::Quarter | Rotation::ThreeQuarter)
+    }
+
+    fn apply(self, img: DynamicImage) -> DynamicImage {
+        match self {
+            Rotation::None => img,
+            Rotation::Quarter => img.rotate90(),
+            Rotation::Half => img.rotate180(),
+            Rotation::ThreeQuarter => img.rotate270(),
+        }
+    }
+}
+
+/// Decode, resize, dither and pack an image for raster printing.
+///
+/// A sideways `rotation` (portrait photos printed along the paper) also
+/// scales the image to fill `width` dots. Otherwise images narrower than the
+/// printer width are left at their native size; wider ones are scaled down.
+pub fn prepare_raster(
+    image_bytes: &[u8],
+    bright: bool,
+    rotation: Rotation,
+    width: u32,
+    dither: Dither,
+) -> Result<RasterImage, String> {
+    let img =
+        image::load_from_memory(image_bytes).map_err(|e| format!("Image decode failed: {e}"))?;
+    let img = rotation.apply(img);
+
+    let img = if rotation.is_sideways() || img.width() > width {
+        img.resize(
+            width,
+            u32::MAX,
+            image::imageops::FilterType::Lanczos3,
+        )
+    } else {
+        img
+    };
+
+    // Grayscale → gamma correction → dither
+    let mut gray = img.to_luma8();
+    if bright {
+        dither_for_thermal_bright(&mut gray, dither);
+    } else {
+        dither_for_thermal(&mut gray, dither);
+    }
+
+    Ok(pack_raster(&gray))
+}
+
+/// Rows of raster `prepare_raster` would produce, read from the image
+/// header without decoding it.
+pub fn raster_height(image_bytes: &[u8], rotation: Rotation, width: u32) -> Result<u32, String> {
+    let (w, h) = image::ImageReader::new(std::io::Cursor::new(image_bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Image decode failed: {e}"))?
+        .into_dimensions()
+        .map_err(|e| format!("Image decode failed: {e}"))?;
+    let (w, h) = if rotation.is_sideways() { (h, w) } else { (w, h) };
+    if rotation.is_sideways() || w > width {
+        Ok((h as f64 * width as f64 / w.max(1) as f64).round().max(1.0) as u32)
+    } else {
+        Ok(h)
+    }
+}
+
+/// Convert an 8-bit dithered image (0 or 255) to a 1-bit packed raster.
+/// Pixels at 0 become black dots; everything else is left blank.
+pub fn pack_raster(gray: &GrayImage) -> RasterImage {
+    let width = gray.width() as usize;
+    let height = gray.height() as usize;
+    let width_bytes = width.div_ceil(8);
+
+    let mut data = vec![0u8; width_bytes * height];
+    for y in 0..height {
+        for x in 0..width {
+            if gray.get_pixel(x as u32, y as u32)[0] == 0 {
+                data[y * width_bytes + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+
+    RasterImage {
+        width_bytes,
+        height,
+        data,
+    }
 }
 
 /// Adaptive thermal pipeline. Measures brightness after auto-levels to choose
 /// contrast and gamma parameters — dark images get gentler contrast and more
 /// aggressive gamma lift so shadow detail survives dithering.
-fn thermal_pipeline(img: &mut GrayImage) {
+fn thermal_pipeline(img: &mut GrayImage, dither: Dither) {
     auto_levels(img);
 
     let mean = mean_brightness(img);
@@ -83,7 +338,7 @@ fn thermal_pipeline(img: &mut GrayImage) {
     apply_contrast(img, contrast);
     apply_gamma(img, gamma);
     unsharp_mask(img, 0.5);
-    floyd_steinberg_dither(img);
+    dither.apply(img);
 }
 
 /// Average pixel brightness (0–255).
@@ -211,34 +466,31 @@ fn apply_gamma(img: &mut GrayImage, gamma: f32) {
     }
 }
 
-/// Floyd-Steinberg error-diffusion dithering.
-/// Converts a grayscale image to 1-bit (0 or 255) in-place.
-fn floyd_steinberg_dither(img: &mut GrayImage) {
+/// Error-diffusion dithering with `kernel`. Serpentine scanning runs odd
+/// rows right to left, mirroring the kernel.
+fn diffuse(img: &mut GrayImage, kernel: &Kernel, serpentine: bool) {
     let width = img.width() as i32;
     let height = img.height() as i32;
 
-    // Work with i16 buffer to handle error diffusion overflow
-    let mut buf: Vec<i16> = img.pixels().map(|p| p[0] as i16).collect();
+    // Work with a wider buffer to handle error diffusion overflow
+    let mut buf: Vec<i32> = img.pixels().map(|p| p[0] as i32).collect();
 
     for y in 0..height {
-        for x in 0..width {
+        let reverse = serpentine && y % 2 == 1;
+        let dir = if reverse { -1 } else { 1 };
+        for i in 0..width {
+            let x = if reverse { width - 1 - i } else { i };
             let idx = (y * width + x) as usize;
             let old = buf[idx].clamp(0, 255);
-            let new = if old > 127 { 255i16 } else { 0i16 };
+            let new = if old > 127 { 255 } else { 0 };
             let err = old - new;
             buf[idx] = new;
 
             // Distribute error to neighbors
-            if x + 1 < width {
-                buf[(y * width + x + 1) as usize] += err * 7 / 16;
-            }
-            if y + 1 < height {
-                if x > 0 {
-                    buf[((y + 1) * width + x - 1) as usize] += err * 3 / 16;
-                }
-                buf[((y + 1) * width + x) as usize] += err * 5 / 16;
-                if x + 1 < width {
-                    buf[((y + 1) * width + x + 1) as usize] += err / 16;
+            for &(dx, dy, weight) in kernel.taps {
+                let (nx, ny) = (x + dx * dir, y + dy);
+                if (0..width).contains(&nx) && ny < height {
+                    buf[(ny * width + nx) as usize] += err * weight / kernel.divisor;
                 }
             }
         }
@@ -250,6 +502,131 @@ fn floyd_steinberg_dither(img: &mut GrayImage) {
     }
 }
 
+/// Ordered dithering: each pixel is compared with a threshold from a
+/// `size` x `size` matrix of ranks, tiled over the image.
+fn ordered(img: &mut GrayImage, ranks: &[u16], size: usize) {
+    let levels = (size * size) as f32;
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let rank = ranks[(y as usize % size) * size + x as usize % size];
+        let threshold = (rank as f32 + 0.5) / levels * 255.0;
+        pixel[0] = if pixel[0] as f32 > threshold { 255 } else { 0 };
+    }
+}
+
+static BAYER_4X4: LazyLock<Vec<u16>> = LazyLock::new(|| bayer_matrix(4));
+static BAYER_8X8: LazyLock<Vec<u16>> = LazyLock::new(|| bayer_matrix(8));
+
+/// The Bayer index matrix of a power-of-two `size`, built up from 2x2:
+/// each cell `m` of the half-size matrix becomes `4m, 4m+2 / 4m+3, 4m+1`.
+fn bayer_matrix(size: usize) -> Vec<u16> {
+    let mut matrix = vec![0u16];
+    let mut n = 1;
+    while n < size {
+        let mut next = vec![0u16; 4 * n * n];
+        for y in 0..n {
+            for x in 0..n {
+                let m = 4 * matrix[y * n + x];
+                next[y * 2 * n + x] = m;
+                next[y * 2 * n + x + n] = m + 2;
+                next[(y + n) * 2 * n + x] = m + 3;
+                next[(y + n) * 2 * n + x + n] = m + 1;
+            }
+        }
+        matrix = next;
+        n *= 2;
+    }
+    matrix
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+static BLUE_NOISE: LazyLock<Vec<u16>> = LazyLock::new(|| blue_noise_mask(BLUE_NOISE_SIZE));
+
+/// A blue-noise threshold mask by Ulichney's void-and-cluster method: dots
+/// are ranked by repeatedly taking the tightest cluster out of, or filling
+/// the largest void in, a pattern, so every threshold level is spread
+/// evenly with no low-frequency structure.
+fn blue_noise_mask(size: usize) -> Vec<u16> {
+    let n = size * size;
+    // Gaussian weight of each toroidal offset, sigma 1.5
+    let weights: Vec<f32> = (0..n)
+        .map(|i| {
+            let wrap = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (wrap(i % size), wrap(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * 1.5 * 1.5)).exp()
+        })
+        .collect();
+    let spread = |energy: &mut [f32], at: usize, sign: f32| {
+        let (ax, ay) = (at % size, at / size);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % size + size - ax) % size;
+            let dy = (i / size + size - ay) % size;
+            *e += sign * weights[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+
+    // A tenth of the cells set at (deterministic) random, then evened out
+    // by moving the tightest cluster into the largest void until stable
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0f32; n];
+    let mut seed: u32 = 0x2545_f491;
+    let mut placed = 0;
+    while placed < n / 10 {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let at = (seed >> 8) as usize % n;
+        if !pattern[at] {
+            pattern[at] = true;
+            spread(&mut energy, at, 1.0);
+            placed += 1;
+        }
+    }
+    for _ in 0..n {
+        let Some(cluster) = tightest_cluster(&pattern, &energy) else {
+            break;
+        };
+        pattern[cluster] = false;
+        spread(&mut energy, cluster, -1.0);
+        let Some(void) = largest_void(&pattern, &energy) else {
+            break;
+        };
+        pattern[void] = true;
+        spread(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0u16; n];
+    // Ranks below the initial pattern: take its clusters out one by one
+    let (mut prototype, mut prototype_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..placed).rev() {
+        if let Some(cluster) = tightest_cluster(&prototype, &prototype_energy) {
+            prototype[cluster] = false;
+            spread(&mut prototype_energy, cluster, -1.0);
+            ranks[cluster] = rank as u16;
+        }
+    }
+    // Ranks above it: fill the largest void until every cell is set
+    for rank in placed..n {
+        if let Some(void) = largest_void(&pattern, &energy) {
+            pattern[void] = true;
+            spread(&mut energy, void, 1.0);
+            ranks[void] = rank as u16;
+        }
+    }
+    ranks
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -279,7 +656,7 @@ mod tests {
         for x in 0..100 {
             img.put_pixel(x, 0, image::Luma([(x as f32 * 2.55) as u8]));
         }
-        floyd_steinberg_dither(&mut img);
+        Dither::FloydSteinberg.apply(&mut img);
         for pixel in img.pixels() {
             assert!(
                 pixel[0] == 0 || pixel[0] == 255,
@@ -292,7 +669,7 @@ mod tests {
     #[test]
     fn dither_white_stays_white() {
         let mut img = GrayImage::from_pixel(10, 10, image::Luma([255u8]));
-        floyd_steinberg_dither(&mut img);
+        Dither::FloydSteinberg.apply(&mut img);
         for pixel in img.pixels() {
             assert_eq!(pixel[0], 255);
         }
@@ -301,12 +678,52 @@ mod tests {
     #[test]
     fn dither_black_stays_black() {
         let mut img = GrayImage::from_pixel(10, 10, image::Luma([0u8]));
-        floyd_steinberg_dither(&mut img);
+        Dither::FloydSteinberg.apply(&mut img);
         for pixel in img.pixels() {
             assert_eq!(pixel[0], 0);
         }
     }
 
+    #[test]
+    fn every_dither_keeps_tone_in_black_and_white() {
+        for dither in Dither::ALL {
+            let mut img = GrayImage::from_pixel(64, 64, image::Luma([64u8]));
+            dither.apply(&mut img);
+            assert!(img.pixels().all(|p| p[0] == 0 || p[0] == 255), "{dither}");
+            let white = img.pixels().filter(|p| p[0] == 255).count();
+            match dither {
+                Dither::Threshold => assert_eq!(white, 0),
+                // Drops a quarter of the error, so dark tones come out darker
+                Dither::Atkinson => assert!(white < 64 * 64 / 4, "{dither}: {white}"),
+                _ => assert!(
+                    (64 * 64 * 20 / 100..64 * 64 * 30 / 100).contains(&white),
+                    "{dither}: {white} white"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn threshold_masks_rank_every_cell_once() {
+        assert_eq!(bayer_matrix(4)[..4], [0, 8, 2, 10]);
+        for mask in [bayer_matrix(8), blue_noise_mask(16)] {
+            let mut sorted = mask.clone();
+            sorted.sort_unstable();
+            assert!(sorted.iter().enumerate().all(|(i, &r)| r as usize == i));
+        }
+    }
+
+    #[test]
+    fn pack_raster_sets_msb_first() {
+        let mut img = GrayImage::from_pixel(10, 2, image::Luma([255u8]));
+        img.put_pixel(0, 0, image::Luma([0u8]));
+        img.put_pixel(9, 1, image::Luma([0u8]));
+        let raster = pack_raster(&img);
+        assert_eq!(raster.width_bytes, 2);
+        assert_eq!(raster.height, 2);
+        assert_eq!(raster.data, vec![0x80, 0x00, 0x00, 0x40]);
+    }
+
     #[test]
     fn mean_brightness_correct() {
         let img = GrayImage::from_pixel(10, 10, image::Luma([100u8]));
@@ -348,7 +765,7 @@ mod tests {
     fn dither_midtone_has_mix() {
         // 50% gray should produce roughly 50% black/white pixels
         let mut img = GrayImage::from_pixel(100, 100, image::Luma([128u8]));
-        floyd_steinberg_dither(&mut img);
+        Dither::FloydSteinberg.apply(&mut img);
         let black_count = img.pixels().filter(|p| p[0] == 0).count();
         let total = 10_000;
         // Should be roughly 50% ± 10%
@@ -358,4 +775,19 @@ mod tests {
             black_count * 100 / total
         );
     }
+
+    #[test]
+    fn raster_fills_58mm_width() {
+        let img = DynamicImage::ImageLuma8(GrayImage::from_pixel(800, 200, image::Luma([90u8])));
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+        let raster = prepare_raster(png.get_ref(), false, Rotation::None, 384, Dither::default()).unwrap();
+        assert_eq!(raster.width_bytes, 48);
+        assert_eq!(raster.height, 96);
+        assert_eq!(raster_height(png.get_ref(), Rotation::None, 384).unwrap(), 96);
+
+        let rotated = prepare_raster(png.get_ref(), false, Rotation::Quarter, 384, Dither::Atkinson).unwrap();
+        assert_eq!(raster_height(png.get_ref(), Rotation::Quarter, 384).unwrap(), rotated.height as u32);
+    }
 }
@@ -0,0 +1,159 @@
+//! NV graphics logos (`GS ( L`).
+//!
+//! A logo is stored once in the printer's non-volatile graphics memory under
+//! a two-character key code, then printed by key with an 11-byte command
+//! instead of re-sending the raster on every receipt. Keys are printable
+//! ASCII, e.g. `LG`.
+//!
+//! NV memory has a limited number of rewrites — store logos when they
+//! change, not per job.
+
+use std::fmt;
+
+use crate::printer::image_proc::{self, Dither, RasterImage, Rotation};
+
+const GS: u8 = 0x1d;
+
+/// `GS ( L` fn 64: transmit the key code list.
+pub const LIST_KEYS: [u8; 9] = [GS, b'(', b'L', 4, 0, 48, 64, b'K', b'C'];
+/// Reply header for the key code list: `37h 72h status d1..dk NUL`.
+pub const KEY_LIST_HEADER: [u8; 2] = [0x37, 0x72];
+/// Key list status: more blocks follow; ACK to receive the next one.
+pub const KEY_LIST_MORE: u8 = 0x41;
+pub const ACK: u8 = 0x06;
+
+/// Largest NV graphic the printers accept.
+const MAX_WIDTH: usize = 8192;
+const MAX_HEIGHT: usize = 2304;
+
+/// What to do with the printer's NV logos.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogoOp {
+    List,
+    /// Store an encoded image (PNG, JPEG, ...) under `key`.
+    Store { key: String, image: Vec<u8> },
+    Delete { key: String },
+}
+
+impl fmt::Display for LogoOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogoOp::List => write!(f, "list"),
+            LogoOp::Store { key, image } => write!(f, "store {key} ({} bytes)", image.len()),
+            LogoOp::Delete { key } => write!(f, "delete {key}"),
+        }
+    }
+}
+
+/// Check a key code and return its two bytes.
+pub fn key_bytes(key: &str) -> Result<[u8; 2], String> {
+    match key.as_bytes() {
+        &[a, b] if (32..=126).contains(&a) && (32..=126).contains(&b) => Ok([a, b]),
+        _ => Err(format!(
+            "Invalid logo key {key:?}: use two printable ASCII characters, e.g. \"LG\""
+        )),
+    }
+}
+
+/// Decode, scale down to `width` dots and dither an image for storing.
+/// Logos for a printer mounted upside down are stored rotated 180°, since
+/// the printer prints NV graphics as stored.
+pub fn prepare(image_bytes: &[u8], width: u32, upside_down: bool) -> Result<RasterImage, String> {
+    let rotation = Rotation::None.flipped_if(upside_down);
+    let raster = image_proc::prepare_raster(image_bytes, false, rotation, width, Dither::default())?;
+    if raster.height > MAX_HEIGHT {
+        return Err(format!(
+            "Logo is {} dots tall (max {MAX_HEIGHT})",
+            raster.height
+        ));
+    }
+    Ok(raster)
+}
+
+/// `GS ( L` fn 67 (or `GS 8 L` when over 64 KB): define a raster NV graphic.
+pub fn store_command(key: &str, raster: &RasterImage) -> Result<Vec<u8>, String> {
+    let [k1, k2] = key_bytes(key)?;
+    let width = raster.width_bytes * 8;
+    if width > MAX_WIDTH || raster.height > MAX_HEIGHT {
+        return Err(format!("Logo is too large: {width}x{} dots", raster.height));
+    }
+
+    // a=48 (raster), b=1 colour, c=49 (colour 1)
+    let mut params = vec![48, 67, 48, k1, k2, 1];
+    params.extend_from_slice(&(width as u16).to_le_bytes());
+    params.extend_from_slice(&(raster.height as u16).to_le_bytes());
+    params.push(49);
+    params.extend_from_slice(&raster.data);
+
+    let mut cmd = Vec::with_capacity(params.len() + 7);
+    match u16::try_from(params.len()) {
+        Ok(len) => {
+            cmd.extend_from_slice(&[GS, b'(', b'L']);
+            cmd.extend_from_slice(&len.to_le_bytes());
+        }
+        Err(_) => {
+            cmd.extend_from_slice(&[GS, b'8', b'L']);
+            cmd.extend_from_slice(&(params.len() as u32).to_le_bytes());
+        }
+    }
+    cmd.extend_from_slice(&params);
+    Ok(cmd)
+}
+
+/// `GS ( L` fn 66: delete one NV graphic.
+pub fn delete_command(key: &str) -> Result<Vec<u8>, String> {
+    let [k1, k2] = key_bytes(key)?;
+    Ok(vec![GS, b'(', b'L', 4, 0, 48, 66, k1, k2])
+}
+
+/// `GS ( L` fn 69: print an NV graphic at normal size.
+pub fn print_command(key: &str) -> Result<Vec<u8>, String> {
+    let [k1, k2] = key_bytes(key)?;
+    Ok(vec![GS, b'(', b'L', 6, 0, 48, 69, k1, k2, 1, 1])
+}
+
+/// Split the data bytes of a key list block into key codes.
+pub fn parse_keys(data: &[u8]) -> Vec<String> {
+    data.chunks_exact(2)
+        .map(|pair| String::from_utf8_lossy(pair).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_command_layout() {
+        let raster = RasterImage {
+            width_bytes: 2,
+            height: 3,
+            data: vec![0xFF; 6],
+        };
+        let cmd = store_command("LG", &raster).unwrap();
+        // p = 11 parameter bytes + 6 data bytes
+        assert_eq!(
+            &cmd[..16],
+            &[0x1d, b'(', b'L', 17, 0, 48, 67, 48, b'L', b'G', 1, 16, 0, 3, 0, 49]
+        );
+        assert_eq!(cmd.len(), 5 + 17);
+
+        let big = RasterImage {
+            width_bytes: 64,
+            height: 1100,
+            data: vec![0; 64 * 1100],
+        };
+        let cmd = store_command("LG", &big).unwrap();
+        assert_eq!(&cmd[..3], &[0x1d, b'8', b'L']);
+        assert_eq!(&cmd[3..7], &(11 + 64 * 1100u32).to_le_bytes());
+    }
+
+    #[test]
+    fn keys_must_be_two_printable_characters() {
+        assert_eq!(key_bytes("LG"), Ok([b'L', b'G']));
+        assert!(key_bytes("L").is_err());
+        assert!(key_bytes("LGO").is_err());
+        assert!(key_bytes("L\n").is_err());
+        assert_eq!(parse_keys(b"LGA1"), vec!["LG".to_string(), "A1".to_string()]);
+    }
+}
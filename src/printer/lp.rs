@@ -0,0 +1,190 @@
+//! Kernel line-printer devices (`/dev/usb/lp*`).
+//!
+//! When the `usblp` driver is bound to a printer, libusb can't claim the
+//! interface and `NativeUsbDriver::open` fails as busy. The character device
+//! still works: writes go straight to the printer, and bidirectional printers
+//! answer status requests on read. The device is opened non-blocking so a
+//! printer that never answers can't hang the print worker.
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use escpos::driver::Driver;
+use escpos::errors::{PrinterError, Result as DriverResult};
+
+const LP_DIR: &str = "/dev/usb";
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct LpBackend {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// A second handle for reads, so a status read waiting for its timeout
+    /// never holds up writes. None when the device is write-only.
+    reader: Option<File>,
+}
+
+impl LpBackend {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let (file, readable) = match open_device(path, true) {
+            Ok(file) => (file, true),
+            Err(_) => (open_device(path, false).map_err(|e| lp_open_error(path, e))?, false),
+        };
+        let reader = if readable { file.try_clone().ok() } else { None };
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            reader,
+        })
+    }
+}
+
+fn open_device(path: &Path, read: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(read).write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NONBLOCK);
+    }
+    options.open(path)
+}
+
+fn lp_open_error(path: &Path, e: std::io::Error) -> String {
+    if e.kind() == ErrorKind::PermissionDenied {
+        format!(
+            "Permission denied opening {}. Add your user to the 'lp' group: \
+             sudo usermod -aG lp $USER (then log out and back in)",
+            path.display()
+        )
+    } else {
+        format!("Failed to open {}: {e}", path.display())
+    }
+}
+
+impl Driver for LpBackend {
+    fn name(&self) -> String {
+        format!("Line printer ({})", self.path.display())
+    }
+
+    fn write(&self, data: &[u8]) -> DriverResult<()> {
+        let mut file = self.file.lock()?;
+        let deadline = Instant::now() + WRITE_TIMEOUT;
+        let mut written = 0;
+        // Non-blocking: usblp returns EAGAIN while its buffer is full
+        while written < data.len() {
+            match file.write(&data[written..]) {
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => {
+                    std::thread::sleep(RETRY_INTERVAL);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8]) -> DriverResult<usize> {
+        let Some(mut file) = self.reader.as_ref() else {
+            return Ok(0);
+        };
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            match file.read(buf) {
+                Ok(n) => return Ok(n),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err(PrinterError::Io(format!(
+                            "No reply from {}",
+                            self.path.display()
+                        )));
+                    }
+                    std::thread::sleep(RETRY_INTERVAL);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn flush(&self) -> DriverResult<()> {
+        self.file.lock()?.flush()?;
+        Ok(())
+    }
+}
+
+/// An lp device node and the USB device behind it (from sysfs).
+#[derive(Debug, Clone)]
+pub struct LpDevice {
+    pub path: PathBuf,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+}
+
+/// Enumerate `/dev/usb/lp*`. Empty on systems without usblp.
+pub fn list_lp_devices() -> Vec<LpDevice> {
+    let Ok(entries) = std::fs::read_dir(LP_DIR) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<LpDevice> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("lp"))
+        .map(|e| describe(e.path()))
+        .collect();
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    devices
+}
+
+/// Look up the USB device for `/dev/usb/lpN` via
+/// `/sys/class/usbmisc/lpN/device` (the interface; its parent is the device).
+fn describe(path: PathBuf) -> LpDevice {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let usb_dir = Path::new("/sys/class/usbmisc")
+        .join(&name)
+        .join("device")
+        .canonicalize()
+        .ok()
+        .and_then(|iface| iface.parent().map(Path::to_path_buf));
+
+    let attr = |file: &str| -> Option<String> {
+        let dir = usb_dir.as_ref()?;
+        std::fs::read_to_string(dir.join(file))
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+    let hex = |file: &str| attr(file).and_then(|s| u16::from_str_radix(&s, 16).ok());
+
+    LpDevice {
+        vendor_id: hex("idVendor"),
+        product_id: hex("idProduct"),
+        product: attr("product"),
+        serial: attr("serial"),
+        path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_file_works_as_lp_device() {
+        let path = std::env::temp_dir().join(format!("receipts-lp-{}", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+
+        let backend = LpBackend::open(&path).unwrap();
+        backend.write(b"\x1b@hello").unwrap();
+        backend.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"\x1b@hello");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
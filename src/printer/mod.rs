@@ -1,6 +1,21 @@
+pub mod actor;
+pub mod asb;
+pub mod backend;
 pub mod connection;
 pub mod discovery;
+pub mod encoder;
+pub mod flow;
 pub mod image_proc;
+pub mod logo;
+pub mod lp;
 pub mod models;
+pub mod network;
+pub mod page;
+pub mod paper;
+pub mod queue;
 pub mod rich_print;
+pub mod router;
 pub mod status;
+pub mod usage;
+pub mod usb;
+pub mod virtual_printer;
@@ -1,37 +1,510 @@
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+
 pub const EPSON_VENDOR_ID: u16 = 0x04b8;
+pub const STAR_VENDOR_ID: u16 = 0x0519;
+pub const BIXOLON_VENDOR_ID: u16 = 0x1504;
+pub const CITIZEN_VENDOR_ID: u16 = 0x1d90;
+pub const RONGTA_VENDOR_ID: u16 = 0x0fe6;
+
+/// USB interface class for printers (bulk-out ESC/POS or similar).
+pub const USB_CLASS_PRINTER: u8 = 0x07;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cutter {
+    /// Tear-off bar only.
+    None,
+    Full,
+    /// Full and partial cuts.
+    Partial,
+}
+
+/// How a printer takes hardware print density and speed settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrintControl {
+    /// Not adjustable from the host; job settings are ignored.
+    None,
+    /// Epson `GS ( K`: density and speed, until the next reset.
+    GsK,
+    /// `DC2 # n` on many clone mechanisms: density only.
+    Dc2,
+}
+
+/// How a printer sounds its buzzer, if it has one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Buzzer {
+    /// No buzzer; jobs that beep are refused.
+    None,
+    /// `ESC B n t` on Star (in ESC/POS mode) and Xprinter-style clones.
+    EscB,
+    /// Epson `ESC ( A`, on TM printers with a buzzer fitted.
+    EscA,
+}
+
+/// How raster data is paced so it never overruns the receive buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowControl {
+    /// Epson `GS ( H`: the printer answers each band's request once it has
+    /// processed that band, so only a buffer's worth is ever outstanding.
+    Response,
+    /// No read-back: bands are sent at the estimated print speed.
+    Paced,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PrinterModel {
-    pub name: &'static str,
-    pub product_ids: &'static [u16],
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_ids: Vec<u16>,
+    pub paper_width_mm: u8,
+    /// Printable width in dots; raster images are scaled to fit.
+    pub dots_per_line: u16,
+    /// Print head resolution, for converting dots to paper length.
+    pub dpi: u16,
+    /// Font A characters per line.
     pub max_chars_per_line: u8,
-    pub supports_partial_cut: bool,
-}
-
-pub static KNOWN_MODELS: &[PrinterModel] = &[
-    PrinterModel {
-        name: "TM-T88VI",
-        product_ids: &[0x0202, 0x0e15, 0x0e28],
-        max_chars_per_line: 42,
-        supports_partial_cut: true,
-    },
-    PrinterModel {
-        name: "TM-M50",
-        product_ids: &[0x0e36],
-        max_chars_per_line: 42,
-        supports_partial_cut: true,
-    },
-];
+    /// Font B characters per line.
+    pub max_chars_per_line_font_b: u8,
+    pub cutter: Cutter,
+    /// Cutter understands `GS V 65/66 n` (feed to cut position, then cut).
+    pub feed_and_cut: bool,
+    pub qr_codes: bool,
+    pub barcodes: bool,
+    /// Page mode (`ESC L`) for side-by-side and rotated layouts.
+    pub page_mode: bool,
+    /// Rows per `GS v 0` band, sized to the printer's receive buffer.
+    pub raster_band_rows: u16,
+    /// Raster print speed at the standard speed level, in mm/s.
+    pub print_speed_mm_s: u16,
+    /// Receive buffer size; at most this much raster data is in flight.
+    pub receive_buffer_bytes: u32,
+    pub flow_control: FlowControl,
+    pub print_control: PrintControl,
+    pub buzzer: Buzzer,
+}
 
-pub fn find_known_model(vendor_id: u16, product_id: u16) -> Option<&'static PrinterModel> {
-    if vendor_id != EPSON_VENDOR_ID {
-        return None;
+/// Dot and character widths of one line on the loaded paper roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintWidth {
+    pub dots: u16,
+    /// Font A characters per line.
+    pub chars: u8,
+    /// Font B characters per line.
+    pub chars_font_b: u8,
+}
+
+impl PrintWidth {
+    pub const MM_80: PrintWidth = PrintWidth {
+        dots: 512,
+        chars: 42,
+        chars_font_b: 56,
+    };
+
+    /// 58mm rolls: 48mm printable at 203 dpi.
+    pub const MM_58: PrintWidth = PrintWidth {
+        dots: 384,
+        chars: 32,
+        chars_font_b: 42,
+    };
+
+    /// Standard widths for a paper roll. Anything narrower than 80mm is
+    /// treated as 58mm.
+    pub fn for_paper(paper_width_mm: u8) -> PrintWidth {
+        if paper_width_mm < 80 {
+            PrintWidth::MM_58
+        } else {
+            PrintWidth::MM_80
+        }
     }
+}
+
+impl PrinterModel {
+    /// The model's own line width, before any per-printer roll settings.
+    pub fn print_width(&self) -> PrintWidth {
+        PrintWidth {
+            dots: self.dots_per_line,
+            chars: self.max_chars_per_line,
+            chars_font_b: self.max_chars_per_line_font_b,
+        }
+    }
+
+    /// An 80mm Epson TM-class printer: the reference ESC/POS feature set.
+    fn epson_80mm(name: &str, vendor_id: u16, product_ids: &[u16]) -> Self {
+        Self {
+            name: name.to_string(),
+            vendor_id,
+            product_ids: product_ids.to_vec(),
+            paper_width_mm: 80,
+            dots_per_line: 512,
+            dpi: 180,
+            max_chars_per_line: 42,
+            max_chars_per_line_font_b: 56,
+            cutter: Cutter::Partial,
+            feed_and_cut: true,
+            qr_codes: true,
+            barcodes: true,
+            page_mode: true,
+            raster_band_rows: 24,
+            print_speed_mm_s: 200,
+            receive_buffer_bytes: 16 * 1024,
+            flow_control: FlowControl::Response,
+            print_control: PrintControl::GsK,
+            buzzer: Buzzer::EscA,
+        }
+    }
+
+    /// A generic 80mm clone: plain `GS V` cuts, no QR, small receive buffer.
+    fn generic_80mm(name: &str, vendor_id: u16) -> Self {
+        Self {
+            max_chars_per_line: 48,
+            max_chars_per_line_font_b: 64,
+            dots_per_line: 576,
+            dpi: 203,
+            cutter: Cutter::Full,
+            feed_and_cut: false,
+            qr_codes: false,
+            page_mode: false,
+            raster_band_rows: 16,
+            print_speed_mm_s: 100,
+            receive_buffer_bytes: 4 * 1024,
+            flow_control: FlowControl::Paced,
+            print_control: PrintControl::None,
+            buzzer: Buzzer::EscB,
+            ..Self::epson_80mm(name, vendor_id, &[])
+        }
+    }
+}
+
+/// Built-in models merged with `[[model]]` entries from the config file.
+static KNOWN_MODELS: LazyLock<Vec<PrinterModel>> = LazyLock::new(|| {
+    let specs = &crate::config::file_config().model;
+    merge_models(built_in_models(), specs)
+});
+
+fn built_in_models() -> Vec<PrinterModel> {
+    vec![
+        PrinterModel::epson_80mm("TM-T88VI", EPSON_VENDOR_ID, &[0x0202, 0x0e15, 0x0e28]),
+        PrinterModel::epson_80mm("TM-M50", EPSON_VENDOR_ID, &[0x0e36]),
+    ]
+}
+
+/// A printer maker, recognised by USB vendor ID or (for brands that ship
+/// on generic USB bridge chips) by the manufacturer string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrinterVendor {
+    pub name: &'static str,
+    pub vendor_ids: &'static [u16],
+    /// Case-insensitive prefixes of the USB manufacturer string.
+    pub manufacturer_prefixes: &'static [&'static str],
+    /// Descriptor used for this vendor's models that aren't in the registry.
+    pub default_model: PrinterModel,
+}
+
+pub static KNOWN_VENDORS: LazyLock<Vec<PrinterVendor>> = LazyLock::new(|| {
+    vec![
+        PrinterVendor {
+            name: "Epson",
+            vendor_ids: &[EPSON_VENDOR_ID],
+            manufacturer_prefixes: &["epson", "seiko epson"],
+            default_model: PrinterModel::epson_80mm("Epson", EPSON_VENDOR_ID, &[]),
+        },
+        PrinterVendor {
+            // TSP100/TSP650/mC-Print in ESC/POS emulation mode
+            name: "Star",
+            vendor_ids: &[STAR_VENDOR_ID],
+            manufacturer_prefixes: &["star"],
+            default_model: PrinterModel {
+                max_chars_per_line: 48,
+                max_chars_per_line_font_b: 64,
+                dots_per_line: 576,
+                dpi: 203,
+                buzzer: Buzzer::EscB,
+                ..PrinterModel::epson_80mm("Star", STAR_VENDOR_ID, &[])
+            },
+        },
+        PrinterVendor {
+            name: "Bixolon",
+            vendor_ids: &[BIXOLON_VENDOR_ID],
+            manufacturer_prefixes: &["bixolon"],
+            default_model: PrinterModel::epson_80mm("Bixolon", BIXOLON_VENDOR_ID, &[]),
+        },
+        PrinterVendor {
+            name: "Citizen",
+            vendor_ids: &[CITIZEN_VENDOR_ID, 0x2730],
+            manufacturer_prefixes: &["citizen"],
+            default_model: PrinterModel {
+                max_chars_per_line: 48,
+                max_chars_per_line_font_b: 64,
+                dots_per_line: 576,
+                dpi: 203,
+                ..PrinterModel::epson_80mm("Citizen", CITIZEN_VENDOR_ID, &[])
+            },
+        },
+        PrinterVendor {
+            name: "Rongta",
+            vendor_ids: &[RONGTA_VENDOR_ID],
+            manufacturer_prefixes: &["rongta"],
+            default_model: PrinterModel::generic_80mm("Rongta", RONGTA_VENDOR_ID),
+        },
+        PrinterVendor {
+            // Xprinter units enumerate with the bridge chip's vendor ID
+            // (Winbond, STMicro, ...), so only the manufacturer string identifies them.
+            name: "Xprinter",
+            vendor_ids: &[],
+            manufacturer_prefixes: &["xprinter"],
+            default_model: PrinterModel::generic_80mm("Xprinter", 0),
+        },
+    ]
+});
+
+/// Any other device exposing a USB printer-class interface. Keeps the
+/// conservative 42-column layout so text never wraps unexpectedly.
+pub static GENERIC_MODEL: LazyLock<PrinterModel> = LazyLock::new(|| PrinterModel {
+    max_chars_per_line: 42,
+    max_chars_per_line_font_b: 56,
+    dots_per_line: 512,
+    ..PrinterModel::generic_80mm("ESC/POS printer", 0)
+});
+
+/// A `[[model]]` entry in the config file. Unset fields are taken from the
+/// built-in model it overrides, or else from the vendor's defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelSpec {
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_ids: Vec<u16>,
+    pub paper_width_mm: Option<u8>,
+    pub dots_per_line: Option<u16>,
+    pub dpi: Option<u16>,
+    pub chars_per_line: Option<u8>,
+    pub chars_per_line_font_b: Option<u8>,
+    pub cutter: Option<Cutter>,
+    pub feed_and_cut: Option<bool>,
+    pub qr_codes: Option<bool>,
+    pub barcodes: Option<bool>,
+    pub page_mode: Option<bool>,
+    pub max_raster_band_rows: Option<u16>,
+    pub print_speed_mm_s: Option<u16>,
+    pub receive_buffer_bytes: Option<u32>,
+    pub flow_control: Option<FlowControl>,
+    pub print_control: Option<PrintControl>,
+    pub buzzer: Option<Buzzer>,
+}
+
+impl ModelSpec {
+    fn apply(&self, base: &PrinterModel) -> PrinterModel {
+        let paper_width_mm = self.paper_width_mm.unwrap_or(base.paper_width_mm);
+        // A different paper width invalidates the base's dot and char widths
+        let width = if paper_width_mm == base.paper_width_mm {
+            base.print_width()
+        } else {
+            PrintWidth::for_paper(paper_width_mm)
+        };
+        PrinterModel {
+            name: self.name.clone(),
+            vendor_id: self.vendor_id,
+            product_ids: self.product_ids.clone(),
+            paper_width_mm,
+            dots_per_line: self.dots_per_line.unwrap_or(width.dots),
+            dpi: self.dpi.unwrap_or(base.dpi),
+            max_chars_per_line: self.chars_per_line.unwrap_or(width.chars),
+            max_chars_per_line_font_b: self.chars_per_line_font_b.unwrap_or(width.chars_font_b),
+            cutter: self.cutter.unwrap_or(base.cutter),
+            feed_and_cut: self.feed_and_cut.unwrap_or(base.feed_and_cut),
+            qr_codes: self.qr_codes.unwrap_or(base.qr_codes),
+            barcodes: self.barcodes.unwrap_or(base.barcodes),
+            page_mode: self.page_mode.unwrap_or(base.page_mode),
+            raster_band_rows: self.max_raster_band_rows.unwrap_or(base.raster_band_rows),
+            print_speed_mm_s: self.print_speed_mm_s.unwrap_or(base.print_speed_mm_s),
+            receive_buffer_bytes: self.receive_buffer_bytes.unwrap_or(base.receive_buffer_bytes),
+            flow_control: self.flow_control.unwrap_or(base.flow_control),
+            print_control: self.print_control.unwrap_or(base.print_control),
+            buzzer: self.buzzer.unwrap_or(base.buzzer),
+        }
+    }
+}
+
+/// Merge config entries over the built-ins. An entry replaces the built-in
+/// with the same vendor and an overlapping product ID (or the same name),
+/// keeping the built-in's other product IDs; anything else is added.
+pub fn merge_models(mut models: Vec<PrinterModel>, specs: &[ModelSpec]) -> Vec<PrinterModel> {
+    for spec in specs {
+        if spec.product_ids.is_empty() {
+            tracing::warn!("Ignoring model '{}': no product_ids", spec.name);
+            continue;
+        }
+
+        let existing = models.iter().position(|m| {
+            m.vendor_id == spec.vendor_id
+                && (m.name.eq_ignore_ascii_case(&spec.name)
+                    || m.product_ids.iter().any(|p| spec.product_ids.contains(p)))
+        });
+
+        match existing {
+            Some(i) => {
+                tracing::info!("Config overrides model {}", models[i].name);
+                let mut model = spec.apply(&models[i]);
+                for pid in &models[i].product_ids {
+                    if !model.product_ids.contains(pid) {
+                        model.product_ids.push(*pid);
+                    }
+                }
+                models[i] = model;
+            }
+            None => {
+                let base = find_vendor(spec.vendor_id, None)
+                    .map(|v| &v.default_model)
+                    .unwrap_or(&GENERIC_MODEL);
+                tracing::info!("Config adds model {}", spec.name);
+                models.push(spec.apply(base));
+            }
+        }
+    }
+    models
+}
+
+pub fn find_known_model(vendor_id: u16, product_id: u16) -> Option<&'static PrinterModel> {
     KNOWN_MODELS
         .iter()
-        .find(|m| m.product_ids.contains(&product_id))
+        .find(|m| m.vendor_id == vendor_id && m.product_ids.contains(&product_id))
+}
+
+/// Look up a model by name (case-insensitive), e.g. from a `[[printer]]`
+/// entry for a printer whose address doesn't identify it.
+pub fn find_model_by_name(name: &str) -> Option<&'static PrinterModel> {
+    KNOWN_MODELS
+        .iter()
+        .find(|m| m.name.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            KNOWN_VENDORS
+                .iter()
+                .map(|v| &v.default_model)
+                .find(|m| m.name.eq_ignore_ascii_case(name))
+        })
+}
+
+/// Match a vendor by USB vendor ID, falling back to the manufacturer string.
+pub fn find_vendor(vendor_id: u16, manufacturer: Option<&str>) -> Option<&'static PrinterVendor> {
+    KNOWN_VENDORS
+        .iter()
+        .find(|v| v.vendor_ids.contains(&vendor_id))
+        .or_else(|| {
+            let manufacturer = manufacturer?.trim().to_ascii_lowercase();
+            KNOWN_VENDORS.iter().find(|v| {
+                v.manufacturer_prefixes
+                    .iter()
+                    .any(|p| manufacturer.starts_with(p))
+            })
+        })
 }
 
-pub fn is_epson_device(vendor_id: u16) -> bool {
-    vendor_id == EPSON_VENDOR_ID
+/// The best descriptor for a device: the exact model if known, else the
+/// vendor's defaults, else the generic ESC/POS profile.
+pub fn model_for(
+    vendor_id: u16,
+    product_id: u16,
+    manufacturer: Option<&str>,
+) -> &'static PrinterModel {
+    find_known_model(vendor_id, product_id)
+        .or_else(|| find_vendor(vendor_id, manufacturer).map(|v| &v.default_model))
+        .unwrap_or(&GENERIC_MODEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_model_needs_matching_vendor() {
+        assert_eq!(find_known_model(EPSON_VENDOR_ID, 0x0e28).unwrap().name, "TM-T88VI");
+        assert!(find_known_model(STAR_VENDOR_ID, 0x0e28).is_none());
+    }
+
+    #[test]
+    fn vendor_by_id_or_manufacturer_string() {
+        assert_eq!(find_vendor(BIXOLON_VENDOR_ID, None).unwrap().name, "Bixolon");
+        assert_eq!(find_vendor(0x0416, Some("Xprinter ")).unwrap().name, "Xprinter");
+        assert!(find_vendor(0x0416, Some("Winbond")).is_none());
+    }
+
+    #[test]
+    fn model_falls_back_to_vendor_then_generic() {
+        assert_eq!(model_for(STAR_VENDOR_ID, 0x0001, None).name, "Star");
+        // Epson TMs beep with ESC ( A, Star and the clones with ESC B
+        assert_eq!(model_for(EPSON_VENDOR_ID, 0x0e36, None).buzzer, Buzzer::EscA);
+        assert_eq!(model_for(STAR_VENDOR_ID, 0x0001, None).buzzer, Buzzer::EscB);
+        assert_eq!(model_for(0x0416, 0x5011, None), &*GENERIC_MODEL);
+        assert!(!model_for(0x0416, 0x5011, None).feed_and_cut);
+    }
+
+    #[test]
+    fn config_models_merge_over_built_ins() {
+        let config = crate::config::parse(
+            r#"
+            [[model]]
+            name = "TM-T88VI"
+            vendor_id = 0x04b8
+            product_ids = [0x0e28]
+            print_speed_mm_s = 150
+            flow_control = "paced"
+
+            [[model]]
+            name = "TSP143IIIU"
+            vendor_id = 0x0519
+            product_ids = [0x0003]
+            cutter = "full"
+            "#,
+        )
+        .unwrap();
+        let models = merge_models(built_in_models(), &config.model);
+
+        assert_eq!(models.len(), 3);
+        let t88 = &models[0];
+        assert_eq!((t88.print_speed_mm_s, t88.max_chars_per_line), (150, 42));
+        assert_eq!(t88.flow_control, FlowControl::Paced);
+        // The built-in's other product IDs still match
+        assert_eq!(t88.product_ids, vec![0x0e28, 0x0202, 0x0e15]);
+
+        let star = &models[2];
+        assert_eq!(star.cutter, Cutter::Full);
+        // Unset fields come from the Star vendor defaults
+        assert_eq!((star.max_chars_per_line, star.dots_per_line), (48, 576));
+    }
+
+    #[test]
+    fn paper_width_picks_58mm_layout() {
+        let config = crate::config::parse(
+            r#"
+            [[model]]
+            name = "TM-P20"
+            vendor_id = 0x04b8
+            product_ids = [0x0e1f]
+            paper_width_mm = 58
+            "#,
+        )
+        .unwrap();
+        let models = merge_models(built_in_models(), &config.model);
+        let p20 = models.last().unwrap();
+        assert_eq!(p20.print_width(), PrintWidth::MM_58);
+    }
+
+    #[test]
+    fn unknown_model_fields_are_rejected() {
+        let result = crate::config::parse(
+            r#"
+            [[model]]
+            name = "X"
+            vendor_id = 1
+            product_ids = [2]
+            chars = 48
+            "#,
+        );
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,214 @@
+//! Ethernet printers on the raw TCP port (9100).
+//!
+//! The socket uses TCP keepalive so a printer that is power-cycled or
+//! unplugged is noticed. A failed write is reported, not retried: part of
+//! the job may already have printed, and the printer thread decides whether
+//! to reconnect and resume. Reads (status replies) time out rather than
+//! block the print worker.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use escpos::driver::Driver;
+use escpos::errors::Result as DriverResult;
+
+/// The raw printing port used by Epson (and most other) network printers.
+pub const DEFAULT_PORT: u16 = 9100;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+const KEEPALIVE_IDLE: Duration = Duration::from_secs(30);
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A raw TCP connection to a network printer.
+pub struct NetworkBackend {
+    host: String,
+    port: u16,
+    stream: Mutex<TcpStream>,
+    /// A clone of `stream` for reads, so a blocked read doesn't hold the
+    /// lock against writes.
+    reader: Mutex<TcpStream>,
+}
+
+impl NetworkBackend {
+    /// Connect to `host:port`.
+    pub fn open(host: &str, port: u16) -> Result<Self, String> {
+        let stream = connect(host, port, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+        let reader = stream
+            .try_clone()
+            .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            stream: Mutex::new(stream),
+            reader: Mutex::new(reader),
+        })
+    }
+
+    /// Run `op` on the socket, logging a lost connection.
+    fn with_stream<T>(
+        &self,
+        op: impl FnOnce(&mut TcpStream) -> std::io::Result<T>,
+    ) -> DriverResult<T> {
+        let mut stream = self.stream.lock()?;
+        op(&mut stream).map_err(|e| {
+            if !is_timeout(&e) {
+                tracing::warn!("{}:{} connection lost: {e}", self.host, self.port);
+            }
+            e.into()
+        })
+    }
+}
+
+impl Driver for NetworkBackend {
+    fn name(&self) -> String {
+        format!("Network ({}:{})", self.host, self.port)
+    }
+
+    fn write(&self, data: &[u8]) -> DriverResult<()> {
+        self.with_stream(|s| s.write_all(data))
+    }
+
+    fn read(&self, buf: &mut [u8]) -> DriverResult<usize> {
+        Ok(self.reader.lock()?.read(buf)?)
+    }
+
+    fn flush(&self) -> DriverResult<()> {
+        self.with_stream(|s| s.flush())
+    }
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> std::io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                let keepalive = socket2::TcpKeepalive::new().with_time(KEEPALIVE_IDLE);
+                socket2::SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+                return Ok(stream);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "host did not resolve")
+    }))
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Whether something is listening on `host:port`. Used by discovery so
+/// powered-off printers don't show up in the list.
+pub fn probe(host: &str, port: u16) -> bool {
+    connect(host, port, PROBE_TIMEOUT).is_ok()
+}
+
+/// Split `host`, `host:port` or `[v6addr]:port`, defaulting to port 9100.
+pub fn parse_host_port(s: &str) -> Result<(String, u16), String> {
+    let s = s.trim();
+    let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("Unclosed '[' in {s}"))?;
+        (host, after.strip_prefix(':'))
+    } else {
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host, Some(port)),
+            _ => (s, None),
+        }
+    };
+
+    if host.is_empty() {
+        return Err(format!("Missing host in '{s}'"));
+    }
+    let port = match port {
+        Some(p) => p.parse().map_err(|_| format!("Bad port in '{s}'"))?,
+        None => DEFAULT_PORT,
+    };
+    Ok((host.to_string(), port))
+}
+
+/// Network printers listed in `NETWORK_PRINTERS` (comma-separated
+/// `host[:port]`). Invalid entries are logged and skipped.
+pub fn configured_printers() -> Vec<(String, u16)> {
+    let Ok(list) = std::env::var("NETWORK_PRINTERS") else {
+        return Vec::new();
+    };
+    list.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| match parse_host_port(entry) {
+            Ok(hp) => Some(hp),
+            Err(e) => {
+                tracing::warn!("Ignoring NETWORK_PRINTERS entry: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn host_port_parsing() {
+        assert_eq!(parse_host_port("10.0.0.5").unwrap(), ("10.0.0.5".into(), 9100));
+        assert_eq!(parse_host_port("bar.local:9101").unwrap(), ("bar.local".into(), 9101));
+        assert_eq!(parse_host_port("[fe80::1]:9100").unwrap(), ("fe80::1".into(), 9100));
+        assert_eq!(parse_host_port("fe80::1").unwrap(), ("fe80::1".into(), 9100));
+        assert!(parse_host_port(":9100").is_err());
+        assert!(parse_host_port("host:port").is_err());
+    }
+
+    #[test]
+    fn writes_and_reads_status_over_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let printer = std::thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut req = [0u8; 3];
+            sock.read_exact(&mut req).unwrap();
+            sock.write_all(&[0x16]).unwrap();
+            req
+        });
+
+        let backend = NetworkBackend::open("127.0.0.1", port).unwrap();
+        backend.write(&[0x10, 0x04, 1]).unwrap();
+        backend.flush().unwrap();
+        let mut reply = [0u8; 1];
+        assert_eq!(backend.read(&mut reply).unwrap(), 1);
+        assert_eq!(reply[0], 0x16);
+        assert_eq!(printer.join().unwrap(), [0x10, 0x04, 1]);
+    }
+
+    #[test]
+    fn write_errors_are_reported_without_reconnecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let backend = NetworkBackend::open("127.0.0.1", port).unwrap();
+        // Dropped straight away, like a power cycle
+        drop(listener.accept().unwrap());
+        std::thread::sleep(Duration::from_millis(50));
+
+        // Writes to a closed peer may succeed once before the RST arrives
+        let failed = (0..10).any(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            backend.write(b"hi").is_err()
+        });
+        assert!(failed);
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.accept().is_err(), "the backend reconnected");
+    }
+}
@@ -0,0 +1,220 @@
+//! Page mode (`ESC L` … `FF`).
+//!
+//! In standard mode everything prints line by line down the paper. In page
+//! mode the printer lays a whole page out in its buffer first, so content can
+//! be placed side by side and rotated: a logo on the left, the order number
+//! on the right. A page is a set of rectangular regions (`ESC W`), each with
+//! its own print direction (`ESC T`) and content, printed together on `FF`.
+//!
+//! Within a region, content stacks from the region's top-left corner as it
+//! would on standard paper — text, images and logos can be mixed freely.
+
+use serde::{Deserialize, Serialize};
+
+const ESC: u8 = 0x1b;
+const FF: u8 = 0x0c;
+
+/// `ESC L`: switch to page mode.
+pub const BEGIN: [u8; 2] = [ESC, b'L'];
+/// `FF`: print the page and return to standard mode.
+pub const END: [u8; 1] = [FF];
+
+/// Largest page the printers buffer, in dots.
+pub const MAX_HEIGHT: u16 = 1662;
+
+/// Dots per Font A character, for wrapping text to a region.
+const CHAR_WIDTH: u16 = 12;
+
+/// A page of positioned regions. The page spans the printable width;
+/// `content` is a list of `PrintCommand`s or `ReceiptBlock`s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Page<T> {
+    /// Page height in dots.
+    pub height: u16,
+    pub regions: Vec<PageRegion<T>>,
+}
+
+/// A rectangle on the page, in dots from the page's top-left corner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PageRegion<T> {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default)]
+    pub direction: PageDirection,
+    pub content: Vec<T>,
+}
+
+/// Print direction within a region (`ESC T n`). Anything other than
+/// `left_to_right` rotates the region's content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageDirection {
+    /// Normal orientation.
+    #[default]
+    LeftToRight,
+    /// Rotated 90° counter-clockwise: lines run up the paper.
+    BottomToTop,
+    /// Upside down.
+    RightToLeft,
+    /// Rotated 90° clockwise: lines run down the paper.
+    TopToBottom,
+}
+
+impl PageDirection {
+    /// The `ESC T` parameter.
+    pub fn code(self) -> u8 {
+        match self {
+            PageDirection::LeftToRight => 0,
+            PageDirection::BottomToTop => 1,
+            PageDirection::RightToLeft => 2,
+            PageDirection::TopToBottom => 3,
+        }
+    }
+
+    pub fn from_code(n: u8) -> Self {
+        match n & 0x03 {
+            1 => PageDirection::BottomToTop,
+            2 => PageDirection::RightToLeft,
+            3 => PageDirection::TopToBottom,
+            _ => PageDirection::LeftToRight,
+        }
+    }
+
+    /// Whether lines run across the paper's length instead of its width.
+    pub fn is_sideways(self) -> bool {
+        matches!(self, PageDirection::BottomToTop | PageDirection::TopToBottom)
+    }
+}
+
+impl<T> PageRegion<T> {
+    /// Line length in dots, in the region's own print direction.
+    pub fn line_dots(&self) -> u16 {
+        if self.direction.is_sideways() {
+            self.height
+        } else {
+            self.width
+        }
+    }
+
+    /// Font A characters per line in this region.
+    pub fn chars_per_line(&self) -> u8 {
+        (self.line_dots() / CHAR_WIDTH).min(u8::MAX as u16) as u8
+    }
+
+    /// `ESC T n` then `ESC W xL xH yL yH dxL dxH dyL dyH`.
+    pub fn area_command(&self) -> Vec<u8> {
+        area_command(self.x, self.y, self.width, self.height, self.direction)
+    }
+
+    /// The same region with its content converted.
+    pub fn map<U>(&self, f: impl FnOnce(&[T], &PageRegion<T>) -> Vec<U>) -> PageRegion<U> {
+        PageRegion {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            direction: self.direction,
+            content: f(&self.content, self),
+        }
+    }
+}
+
+impl<T> Page<T> {
+    /// The whole page as one print area. Sent first, so the printed page
+    /// is `height` dots tall however little the regions hold.
+    pub fn area_command(&self, width: u16) -> Vec<u8> {
+        area_command(0, 0, width, self.height, PageDirection::LeftToRight)
+    }
+
+    /// Check that the page fits the printer's buffer and every region lies
+    /// on a page `width` dots wide.
+    pub fn validate(&self, width: u16) -> Result<(), String> {
+        if self.height == 0 || self.height > MAX_HEIGHT {
+            return Err(format!("Page height must be 1–{MAX_HEIGHT} dots, not {}", self.height));
+        }
+        if self.regions.is_empty() {
+            return Err("Page has no regions".to_string());
+        }
+        for (i, r) in self.regions.iter().enumerate() {
+            let fits = r.width > 0
+                && r.height > 0
+                && r.x as u32 + r.width as u32 <= width as u32
+                && r.y as u32 + r.height as u32 <= self.height as u32;
+            if !fits {
+                return Err(format!(
+                    "Region {i} ({}x{} at {},{}) is outside the {width}x{} page",
+                    r.width, r.height, r.x, r.y, self.height
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Two regions side by side across `width` dots: `left` takes
+    /// `left_width` dots, `right` the rest. E.g. a logo and an order number.
+    pub fn side_by_side(width: u16, height: u16, left_width: u16, left: Vec<T>, right: Vec<T>) -> Self {
+        let left_width = left_width.min(width);
+        let region = |x, width, content| PageRegion {
+            x,
+            y: 0,
+            width,
+            height,
+            direction: PageDirection::LeftToRight,
+            content,
+        };
+        Page {
+            height,
+            regions: vec![
+                region(0, left_width, left),
+                region(left_width, width - left_width, right),
+            ],
+        }
+    }
+}
+
+fn area_command(x: u16, y: u16, width: u16, height: u16, direction: PageDirection) -> Vec<u8> {
+    let mut cmd = vec![ESC, b'T', direction.code(), ESC, b'W'];
+    for v in [x, y, width, height] {
+        cmd.extend_from_slice(&v.to_le_bytes());
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_command_layout() {
+        let region = PageRegion::<()> {
+            x: 256,
+            y: 10,
+            width: 256,
+            height: 120,
+            direction: PageDirection::TopToBottom,
+            content: vec![],
+        };
+        assert_eq!(
+            region.area_command(),
+            vec![0x1b, b'T', 3, 0x1b, b'W', 0, 1, 10, 0, 0, 1, 120, 0]
+        );
+        // Sideways regions wrap text to their height
+        assert_eq!(region.chars_per_line(), 10);
+    }
+
+    #[test]
+    fn regions_must_fit_the_page() {
+        let page = Page::side_by_side(512, 100, 200, vec![()], vec![()]);
+        assert!(page.validate(512).is_ok());
+        assert!(page.validate(384).is_err());
+        assert_eq!(page.regions[1].x, 200);
+        assert_eq!(page.regions[1].width, 312);
+
+        let tall = Page { height: 2000, ..page };
+        assert!(tall.validate(512).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Paper length estimates, before a job is encoded.
+//!
+//! Adds up what the printer advances the paper by: text lines at the
+//! default line spacing (taller for double-size lines), feeds, raster image
+//! heights, barcodes and pages, then converts dots to millimetres with the
+//! model's resolution. NV logos and the cutter margin aren't counted, so a
+//! job can come out a little longer — close enough to catch a runaway log.
+
+use crate::printer::discovery::DiscoveredPrinter;
+use crate::printer::image_proc::{self, Rotation};
+use crate::printer::models::PrintWidth;
+use crate::printer::rich_print::{PrintCommand, Symbology};
+use crate::receipt_markdown::ReceiptBlock;
+use crate::word_wrap::{wrap_document, WrappedLine};
+
+/// Font A cell height in dots; double-size text is twice this.
+const CHAR_HEIGHT: u32 = 24;
+/// `GS h` and HRI text as set by the encoder.
+const BARCODE_HEIGHT: u32 = 80 + CHAR_HEIGHT;
+/// `GS ( k` module size set by the encoder.
+const QR_MODULE: u32 = 6;
+/// QR byte capacity at error correction M, versions 1–10.
+const QR_CAPACITY: [usize; 10] = [14, 26, 42, 62, 84, 106, 122, 152, 180, 213];
+
+/// What an estimate needs to know about the printer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaperGeometry {
+    pub width: PrintWidth,
+    pub dpi: u16,
+}
+
+impl Default for PaperGeometry {
+    /// An 80mm Epson, for estimates with no printer connected.
+    fn default() -> Self {
+        Self {
+            width: PrintWidth::MM_80,
+            dpi: 180,
+        }
+    }
+}
+
+impl PaperGeometry {
+    pub fn for_printer(printer: &DiscoveredPrinter) -> Self {
+        Self {
+            width: printer.print_width(),
+            dpi: printer.model().dpi,
+        }
+    }
+
+    /// Default line spacing (`ESC 2`): 1/6 inch.
+    fn line_spacing(&self) -> u32 {
+        self.dpi as u32 / 6
+    }
+}
+
+/// Running total of paper used by a job, in dots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaperEstimate {
+    geometry: PaperGeometry,
+    dots: u32,
+}
+
+impl PaperEstimate {
+    pub fn new(geometry: PaperGeometry) -> Self {
+        Self { geometry, dots: 0 }
+    }
+
+    /// One printed line whose tallest character is `char_height` dots.
+    pub fn text_line(&mut self, char_height: u32) -> &mut Self {
+        self.dots += char_height.max(self.geometry.line_spacing());
+        self
+    }
+
+    /// Wrapped lines, as `generate_commands_from_lines` prints them.
+    pub fn lines(&mut self, lines: &[WrappedLine]) -> &mut Self {
+        for line in lines {
+            let double = line.spans.iter().any(|s| s.format.double_size);
+            self.text_line(if double { 2 * CHAR_HEIGHT } else { CHAR_HEIGHT });
+        }
+        self
+    }
+
+    /// `ESC d n` with nothing buffered.
+    pub fn feed(&mut self, lines: u8) -> &mut Self {
+        self.dots += lines as u32 * self.geometry.line_spacing();
+        self
+    }
+
+    /// A raster image `rows` dots tall.
+    pub fn raster(&mut self, rows: u32) -> &mut Self {
+        self.dots += rows;
+        self
+    }
+
+    /// An image printed as `prepare_raster` would scale it. Images that
+    /// can't be read count for nothing, as the encoder skips them.
+    pub fn image(&mut self, image_bytes: &[u8], rotation: Rotation) -> &mut Self {
+        let width = self.geometry.width.dots as u32;
+        self.raster(image_proc::raster_height(image_bytes, rotation, width).unwrap_or(0))
+    }
+
+    /// Receipt blocks, split the way `generate_commands` splits them.
+    pub fn blocks(&mut self, blocks: &[ReceiptBlock]) -> &mut Self {
+        let breaks = |b: &ReceiptBlock| matches!(b, ReceiptBlock::Command(_) | ReceiptBlock::Page(_));
+        for run in blocks.split_inclusive(breaks) {
+            let (text, last) = match run.split_last() {
+                Some((last, text)) if breaks(last) => (text, Some(last)),
+                _ => (run, None),
+            };
+            self.lines(&wrap_document(text, self.geometry.width.chars));
+            match last {
+                Some(ReceiptBlock::Command(cmd)) => {
+                    self.commands(std::slice::from_ref(cmd));
+                }
+                Some(ReceiptBlock::Page(page)) => {
+                    self.raster(page.height as u32);
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// A command list. Text written without a line feed is wrapped at the
+    /// printer's line length, as the printer does.
+    pub fn commands(&mut self, commands: &[PrintCommand]) -> &mut Self {
+        let chars = self.geometry.width.chars.max(1) as u32;
+        let mut double = false;
+        // Buffered text: width in Font A cells and tallest character
+        let mut cells = 0u32;
+        let mut height = 0u32;
+        let end_line = |est: &mut Self, cells: &mut u32, height: &mut u32| {
+            let lines = cells.div_ceil(chars).max(1);
+            for _ in 0..lines {
+                est.text_line(*height);
+            }
+            *cells = 0;
+            *height = 0;
+        };
+
+        for cmd in commands {
+            match cmd {
+                PrintCommand::SetDoubleSize(on) => double = *on,
+                PrintCommand::Write(text) if !text.is_empty() => {
+                    let scale = if double { 2 } else { 1 };
+                    cells += text.chars().count() as u32 * scale;
+                    height = height.max(CHAR_HEIGHT * scale);
+                }
+                PrintCommand::Feed => end_line(self, &mut cells, &mut height),
+                PrintCommand::FeedLines(0) if cells > 0 => end_line(self, &mut cells, &mut height),
+                PrintCommand::FeedLines(n) if *n > 0 => {
+                    end_line(self, &mut cells, &mut height);
+                    self.feed(n - 1);
+                }
+                PrintCommand::Image { data, .. } => {
+                    self.image(data, Rotation::None);
+                }
+                PrintCommand::Barcode { symbology: Symbology::Qr, data } => {
+                    let version = QR_CAPACITY
+                        .iter()
+                        .position(|&c| data.len() <= c)
+                        .unwrap_or(QR_CAPACITY.len() + (data.len() - 213) / 24) as u32;
+                    self.raster((21 + 4 * version) * QR_MODULE);
+                }
+                PrintCommand::Barcode { .. } => {
+                    self.raster(BARCODE_HEIGHT);
+                }
+                PrintCommand::Page(page) => {
+                    self.raster(page.height as u32);
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
+    pub fn dots(&self) -> u32 {
+        self.dots
+    }
+
+    /// Paper length in whole millimetres, rounded up.
+    pub fn mm(&self) -> u32 {
+        (self.dots as u64 * 254).div_ceil(self.geometry.dpi.max(1) as u64 * 10) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::encoder::{Dialect, EncodedJob};
+    use crate::printer::virtual_printer::render_escpos;
+    use crate::receipt_markdown::parse_receipt_markdown;
+
+    #[test]
+    fn receipt_estimate_matches_rendered_length() {
+        let blocks = parse_receipt_markdown(
+            "# HEADING\nSome text that is long enough to wrap onto a second line of the receipt\n\n---\nlast",
+        );
+        let geometry = PaperGeometry::default();
+        let mut estimate = PaperEstimate::new(geometry);
+        estimate.blocks(&blocks).feed(3);
+
+        let bytes = EncodedJob::receipt(&blocks, geometry.width.chars, false, Dialect::EPSON).to_bytes();
+        let rendered = render_escpos(&bytes, geometry.width.dots as u32);
+        assert_eq!(estimate.dots(), rendered.height());
+    }
+
+    #[test]
+    fn commands_wrap_and_feed() {
+        let geometry = PaperGeometry::default();
+        let mut estimate = PaperEstimate::new(geometry);
+        estimate.commands(&[
+            PrintCommand::Write("A".repeat(50)),
+            PrintCommand::Feed,
+            PrintCommand::SetDoubleSize(true),
+            PrintCommand::Write("BIG".into()),
+            PrintCommand::FeedLines(3),
+        ]);
+        // Two wrapped lines, a 48-dot line, then two blank lines
+        assert_eq!(estimate.dots(), 30 + 30 + 48 + 2 * 30);
+    }
+
+    #[test]
+    fn millimetres_follow_resolution() {
+        let mut at_180 = PaperEstimate::new(PaperGeometry::default());
+        at_180.raster(180);
+        assert_eq!(at_180.mm(), 26);
+
+        let mut at_203 = PaperEstimate::new(PaperGeometry {
+            dpi: 203,
+            ..PaperGeometry::default()
+        });
+        at_203.feed(6);
+        // 1/6 inch lines: six make an inch, less integer rounding of the spacing
+        assert_eq!(at_203.dots(), 198);
+        assert_eq!(at_203.mm(), 25);
+    }
+}
@@ -0,0 +1,165 @@
+//! Print queue state machine shared by the GUI and the headless server.
+//!
+//! The job being printed stays at the head of the queue until it finishes.
+//! If the printer turns out to be out of paper or open, the queue pauses with
+//! that job still at the head, and resumes once status reports the printer
+//! ready again — so changing the roll never loses or fails a job.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::printer::status::PrinterStatus;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueState {
+    Ready,
+    /// The head job has been handed to the printer.
+    Printing,
+    /// Waiting for the printer; the reason is shown to the user.
+    Paused(String),
+}
+
+impl fmt::Display for QueueState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueState::Ready => write!(f, "Ready"),
+            QueueState::Printing => write!(f, "Printing"),
+            QueueState::Paused(reason) => write!(f, "Paused: {reason}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrintQueue<J> {
+    jobs: VecDeque<J>,
+    state: QueueState,
+}
+
+impl<J> Default for PrintQueue<J> {
+    fn default() -> Self {
+        Self {
+            jobs: VecDeque::new(),
+            state: QueueState::Ready,
+        }
+    }
+}
+
+impl<J: Clone> PrintQueue<J> {
+    pub fn push(&mut self, job: J) {
+        self.jobs.push_back(job);
+    }
+
+    /// Hand out the head job, unless one is already printing or the queue
+    /// is paused. The job stays queued until `finish`.
+    pub fn start(&mut self) -> Option<J> {
+        if self.state != QueueState::Ready {
+            return None;
+        }
+        let job = self.jobs.front()?.clone();
+        self.state = QueueState::Printing;
+        Some(job)
+    }
+
+    /// The head job is done — printed, or failed for good.
+    pub fn finish(&mut self) -> Option<J> {
+        self.state = QueueState::Ready;
+        self.jobs.pop_front()
+    }
+
+    /// Keep the head job and wait for the printer.
+    pub fn pause(&mut self, reason: impl Into<String>) {
+        self.state = QueueState::Paused(reason.into());
+    }
+
+    /// Resume when the printer reports it can print again. Returns true if
+    /// the queue was paused and is now ready.
+    pub fn update_status(&mut self, status: &PrinterStatus) -> bool {
+        let resumed = matches!(self.state, QueueState::Paused(_)) && status.blocking_error().is_none();
+        if resumed {
+            self.state = QueueState::Ready;
+        }
+        resumed
+    }
+
+    pub fn state(&self) -> &QueueState {
+        &self.state
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, QueueState::Paused(_))
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &J> {
+        self.jobs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut J> {
+        self.jobs.iter_mut()
+    }
+
+    /// Take out every job the printer doesn't have, to send them to another
+    /// printer. A paused queue gives up its head job too and is ready again.
+    pub fn take_waiting(&mut self) -> Vec<J> {
+        let keep = usize::from(self.state == QueueState::Printing);
+        let taken = self.jobs.drain(keep.min(self.jobs.len())..).collect();
+        if keep == 0 {
+            self.state = QueueState::Ready;
+        }
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paper_out_keeps_job_at_head_until_ready() {
+        let mut queue = PrintQueue::default();
+        queue.push("first");
+        queue.push("second");
+
+        assert_eq!(queue.start(), Some("first"));
+        assert_eq!(queue.start(), None, "one job at a time");
+
+        queue.pause("Paper out");
+        assert!(queue.is_paused());
+        assert_eq!(queue.start(), None);
+
+        let paper_out = PrinterStatus::from_status_bytes(0x1a, 0x32, 0x72);
+        assert!(!queue.update_status(&paper_out));
+
+        let ready = PrinterStatus::from_status_bytes(0x12, 0x12, 0x12);
+        assert!(queue.update_status(&ready));
+        assert_eq!(queue.start(), Some("first"));
+        assert_eq!(queue.finish(), Some("first"));
+        assert_eq!(queue.start(), Some("second"));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn waiting_jobs_move_but_not_the_one_printing() {
+        let mut queue = PrintQueue::default();
+        queue.push("first");
+        queue.push("second");
+        queue.push("third");
+
+        assert_eq!(queue.start(), Some("first"));
+        assert_eq!(queue.take_waiting(), ["second", "third"]);
+        assert_eq!(queue.len(), 1);
+
+        queue.push("fourth");
+        queue.pause("Cover open");
+        assert_eq!(queue.take_waiting(), ["first", "fourth"]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.state(), &QueueState::Ready);
+    }
+}
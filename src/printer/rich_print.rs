@@ -1,12 +1,19 @@
-use escpos::driver::Driver;
-use escpos::printer::Printer;
-use escpos::utils::{JustifyMode, UnderlineMode};
+use serde::{Deserialize, Serialize};
 
+use crate::printer::logo;
+use crate::printer::page::{Page, PageRegion};
 use crate::receipt_markdown::{Alignment, ReceiptBlock};
 use crate::word_wrap::{wrap_document, WrappedLine};
 
+/// Upper bound on the number of commands accepted in one job.
+pub const MAX_COMMANDS: usize = 4096;
+
 /// A pure, testable representation of an ESC/POS command.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Serializes as externally tagged snake_case JSON, so a command list looks like
+/// `[{"set_alignment": "center"}, {"write": "Hello"}, "feed", {"cut": "partial"}]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PrintCommand {
     SetBold(bool),
     SetUnderline(bool),
@@ -14,13 +21,293 @@ pub enum PrintCommand {
     SetAlignment(Alignment),
     Write(String),
     Feed,
+    /// Feed `n` lines in one command.
+    FeedLines(u8),
+    /// A raster image. `data` is an encoded image file (PNG, JPEG, ...),
+    /// base64 in JSON. It is dithered and scaled down to the printer width.
+    Image {
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        /// Indoor brightness boost before dithering.
+        #[serde(default)]
+        bright: bool,
+    },
+    Cut(CutMode),
+    Barcode {
+        symbology: Symbology,
+        data: String,
+    },
+    /// Pulse a cash drawer kick-out pin (`ESC p`). Times are rounded down to
+    /// the printer's 2 ms steps; `{"open_drawer": {}}` uses the defaults.
+    OpenDrawer {
+        /// Connector pin: 2 or 5.
+        #[serde(default = "default_drawer_pin")]
+        pin: u8,
+        #[serde(default = "default_drawer_on_ms")]
+        on_ms: u16,
+        #[serde(default = "default_drawer_off_ms")]
+        off_ms: u16,
+    },
+    /// Print a logo stored in the printer's NV graphics memory by its
+    /// two-character key code, e.g. `{"nv_logo": "LG"}`.
+    NvLogo(String),
+    /// Sound the buzzer `times` times, each `duration` × 50 ms (`ESC B`),
+    /// or with beep pattern `duration` (Epson `ESC ( A`).
+    Beep {
+        #[serde(default = "default_beep_times")]
+        times: u8,
+        #[serde(default = "default_beep_duration")]
+        duration: u8,
+    },
+    /// A page-mode layout: regions placed side by side or rotated, each
+    /// with its own commands. E.g.
+    /// `{"page": {"height": 120, "regions": [{"x": 0, "y": 0, "width": 200,
+    /// "height": 120, "content": [{"nv_logo": "LG"}]}, ...]}}`.
+    Page(Page<PrintCommand>),
+}
+
+impl PrintCommand {
+    /// A drawer kick with the default pulse.
+    pub fn open_drawer() -> Self {
+        PrintCommand::OpenDrawer {
+            pin: default_drawer_pin(),
+            on_ms: default_drawer_on_ms(),
+            off_ms: default_drawer_off_ms(),
+        }
+    }
+
+    /// A single short beep.
+    pub fn beep() -> Self {
+        PrintCommand::Beep {
+            times: default_beep_times(),
+            duration: default_beep_duration(),
+        }
+    }
+
+    /// Range checks for the drawer and buzzer parameters.
+    pub fn validate_peripheral(&self) -> Result<(), String> {
+        match *self {
+            PrintCommand::OpenDrawer { pin, on_ms, off_ms } => {
+                if pin != 2 && pin != 5 {
+                    return Err(format!("Drawer pin must be 2 or 5, not {pin}"));
+                }
+                if !(2..=510).contains(&on_ms) || off_ms > 510 {
+                    return Err(format!(
+                        "Drawer pulse must be 2–510 ms on and at most 510 ms off (got {on_ms}/{off_ms})"
+                    ));
+                }
+                Ok(())
+            }
+            PrintCommand::Beep { times, duration } => {
+                if !(1..=9).contains(&times) || !(1..=9).contains(&duration) {
+                    return Err(format!(
+                        "Beep times and duration must be 1–9 (got {times}/{duration})"
+                    ));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn default_drawer_pin() -> u8 {
+    2
+}
+
+fn default_drawer_on_ms() -> u16 {
+    100
+}
+
+fn default_drawer_off_ms() -> u16 {
+    200
+}
+
+fn default_beep_times() -> u8 {
+    1
+}
+
+fn default_beep_duration() -> u8 {
+    2
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CutMode {
+    Full,
+    Partial,
+}
+
+/// Barcode symbologies supported by the printers we drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symbology {
+    Ean13,
+    Ean8,
+    UpcA,
+    UpcE,
+    Code39,
+    Codabar,
+    Itf,
+    Qr,
+}
+
+impl Symbology {
+    /// Check that `data` can be encoded in this symbology.
+    pub fn validate(&self, data: &str) -> Result<(), String> {
+        let all_digits = !data.is_empty() && data.bytes().all(|b| b.is_ascii_digit());
+        // GS k sends the length in one byte
+        let fits = (1..=255).contains(&data.len());
+        let ok = match self {
+            Symbology::Ean13 => all_digits && matches!(data.len(), 12 | 13),
+            Symbology::Ean8 => all_digits && matches!(data.len(), 7 | 8),
+            Symbology::UpcA => all_digits && matches!(data.len(), 11 | 12),
+            Symbology::UpcE => all_digits && matches!(data.len(), 6..=8),
+            Symbology::Itf => all_digits && fits && data.len().is_multiple_of(2),
+            Symbology::Code39 => {
+                fits && data
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b.is_ascii_uppercase() || b" -.$/+%".contains(&b))
+            }
+            Symbology::Codabar => {
+                fits && data
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b"-$:/.+ABCD".contains(&b))
+            }
+            Symbology::Qr => !data.is_empty() && data.len() <= 7089,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("Invalid {self:?} barcode data: {data:?}"))
+        }
+    }
+}
+
+/// Check a command list before queueing it: bounded length, decodable images,
+/// encodable barcodes, and no raw control characters smuggled in via `Write`.
+pub fn validate_commands(commands: &[PrintCommand]) -> Result<(), String> {
+    if commands.is_empty() {
+        return Err("No commands".into());
+    }
+    if commands.len() > MAX_COMMANDS {
+        return Err(format!(
+            "Too many commands: {} (max {MAX_COMMANDS})",
+            commands.len()
+        ));
+    }
+
+    for (i, cmd) in commands.iter().enumerate() {
+        match cmd {
+            PrintCommand::Write(text) if text.chars().any(|c| c.is_control()) => {
+                return Err(format!(
+                    "Command {i}: text contains control characters (use feed instead)"
+                ));
+            }
+            PrintCommand::Image { data, .. } => {
+                if data.is_empty() {
+                    return Err(format!("Command {i}: empty image"));
+                }
+                image::ImageReader::new(std::io::Cursor::new(data))
+                    .with_guessed_format()
+                    .map_err(|e| format!("Command {i}: {e}"))?
+                    .into_dimensions()
+                    .map_err(|e| format!("Command {i}: unreadable image: {e}"))?;
+            }
+            PrintCommand::Barcode { symbology, data } => {
+                symbology
+                    .validate(data)
+                    .map_err(|e| format!("Command {i}: {e}"))?;
+            }
+            PrintCommand::NvLogo(key) => {
+                logo::key_bytes(key).map_err(|e| format!("Command {i}: {e}"))?;
+            }
+            PrintCommand::OpenDrawer { .. } | PrintCommand::Beep { .. } => {
+                cmd.validate_peripheral()
+                    .map_err(|e| format!("Command {i}: {e}"))?;
+            }
+            PrintCommand::Page(page) => {
+                validate_page(page).map_err(|e| format!("Command {i}: {e}"))?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Page geometry and region content. The page width depends on the
+/// printer, so it is checked when the job is encoded.
+fn validate_page(page: &Page<PrintCommand>) -> Result<(), String> {
+    page.validate(u16::MAX)?;
+    for (r, region) in page.regions.iter().enumerate() {
+        if let Some(cmd) = region.content.iter().find(|c| {
+            matches!(c, PrintCommand::Page(_) | PrintCommand::Cut(_))
+        }) {
+            return Err(format!("Region {r}: {cmd:?} is not allowed in a page"));
+        }
+        if !region.content.is_empty() {
+            validate_commands(&region.content).map_err(|e| format!("Region {r}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Serde helper: `Vec<u8>` as a standard base64 string.
+mod base64_bytes {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(s.trim())
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 /// Generate a sequence of print commands from receipt blocks.
 /// This is a pure function — no side effects, fully testable.
+///
+/// Directive blocks (drawer, buzzer, NV logos) are emitted in place, between
+/// the lines of text around them. Logos are centered, like headings.
+/// Pages are emitted in place too, with each region's blocks wrapped to the
+/// region's width.
 pub fn generate_commands(blocks: &[ReceiptBlock], max_chars: u8) -> Vec<PrintCommand> {
-    let lines = wrap_document(blocks, max_chars);
-    generate_commands_from_lines(&lines)
+    let mut commands = Vec::new();
+    let breaks = |b: &ReceiptBlock| matches!(b, ReceiptBlock::Command(_) | ReceiptBlock::Page(_));
+    for run in blocks.split_inclusive(breaks) {
+        let (text, last) = match run.split_last() {
+            Some((last, text)) if breaks(last) => (text, Some(last)),
+            _ => (run, None),
+        };
+        let lines = wrap_document(text, max_chars);
+        commands.extend(generate_commands_from_lines(&lines));
+        match last {
+            Some(ReceiptBlock::Command(cmd @ PrintCommand::NvLogo(_))) => {
+                commands.push(PrintCommand::SetAlignment(Alignment::Center));
+                commands.push(cmd.clone());
+                commands.push(PrintCommand::SetAlignment(Alignment::Left));
+            }
+            Some(ReceiptBlock::Command(cmd)) => commands.push(cmd.clone()),
+            Some(ReceiptBlock::Page(page)) => commands.push(PrintCommand::Page(Page {
+                height: page.height,
+                regions: page.regions.iter().map(region_commands).collect(),
+            })),
+            _ => {}
+        }
+    }
+    commands
+}
+
+/// A region's blocks as commands, wrapped to the region. Alignment (and
+/// logo centering) is relative to the region.
+fn region_commands(region: &PageRegion<ReceiptBlock>) -> PageRegion<PrintCommand> {
+    region.map(|blocks, region| generate_commands(blocks, region.chars_per_line()))
 }
 
 /// Generate print commands from pre-wrapped lines.
@@ -82,51 +369,6 @@ pub fn generate_commands_from_lines(lines: &[WrappedLine]) -> Vec<PrintCommand>
     commands
 }
 
-/// Execute print commands against a real printer.
-/// This is the imperative shell — the only function that touches hardware.
-pub fn execute_commands<D: Driver>(
-    printer: &mut Printer<D>,
-    commands: &[PrintCommand],
-) -> Result<(), String> {
-    for cmd in commands {
-        match cmd {
-            PrintCommand::SetBold(on) => {
-                printer.bold(*on).map_err(|e| e.to_string())?;
-            }
-            PrintCommand::SetUnderline(on) => {
-                let mode = if *on {
-                    UnderlineMode::Single
-                } else {
-                    UnderlineMode::None
-                };
-                printer.underline(mode).map_err(|e| e.to_string())?;
-            }
-            PrintCommand::SetDoubleSize(on) => {
-                if *on {
-                    printer.size(2, 2).map_err(|e| e.to_string())?;
-                } else {
-                    printer.size(1, 1).map_err(|e| e.to_string())?;
-                }
-            }
-            PrintCommand::SetAlignment(align) => {
-                let mode = match align {
-                    Alignment::Left => JustifyMode::LEFT,
-                    Alignment::Center => JustifyMode::CENTER,
-                    Alignment::Right => JustifyMode::RIGHT,
-                };
-                printer.justify(mode).map_err(|e| e.to_string())?;
-            }
-            PrintCommand::Write(text) => {
-                printer.write(text).map_err(|e| e.to_string())?;
-            }
-            PrintCommand::Feed => {
-                printer.feed().map_err(|e| e.to_string())?;
-            }
-        }
-    }
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,4 +526,156 @@ Croissant | $4.50
             &PrintCommand::SetAlignment(Alignment::Left)
         );
     }
+
+    #[test]
+    fn commands_deserialize_from_json() {
+        let json = r#"[
+            {"set_alignment": "center"},
+            {"set_bold": true},
+            {"write": "ORDER 42"},
+            "feed",
+            {"feed_lines": 3},
+            {"barcode": {"symbology": "code39", "data": "ORDER42"}},
+            {"cut": "partial"}
+        ]"#;
+        let cmds: Vec<PrintCommand> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(cmds[0], PrintCommand::SetAlignment(Alignment::Center));
+        assert_eq!(cmds[2], PrintCommand::Write("ORDER 42".into()));
+        assert_eq!(cmds[3], PrintCommand::Feed);
+        assert_eq!(cmds[4], PrintCommand::FeedLines(3));
+        assert_eq!(cmds[6], PrintCommand::Cut(CutMode::Partial));
+        assert!(validate_commands(&cmds).is_ok());
+    }
+
+    #[test]
+    fn image_data_round_trips_as_base64() {
+        let cmd = PrintCommand::Image {
+            data: vec![0x89, b'P', b'N', b'G'],
+            bright: false,
+        };
+        let json = serde_json::to_string(&cmd).unwrap();
+        assert!(json.contains("iVBORw=="), "Expected base64 payload: {json}");
+        let back: PrintCommand = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, cmd);
+    }
+
+    #[test]
+    fn validation_rejects_bad_input() {
+        assert!(validate_commands(&[]).is_err());
+        assert!(validate_commands(&[PrintCommand::Write("\x1b@".into())]).is_err());
+        assert!(validate_commands(&[PrintCommand::Image {
+            data: b"not an image".to_vec(),
+            bright: false,
+        }])
+        .is_err());
+        assert!(validate_commands(&[PrintCommand::Barcode {
+            symbology: Symbology::Ean13,
+            data: "12345".into(),
+        }])
+        .is_err());
+        assert!(validate_commands(&vec![PrintCommand::Feed; MAX_COMMANDS + 1]).is_err());
+        assert!(validate_commands(&[PrintCommand::OpenDrawer {
+            pin: 3,
+            on_ms: 100,
+            off_ms: 200,
+        }])
+        .is_err());
+        assert!(validate_commands(&[PrintCommand::Beep {
+            times: 10,
+            duration: 1,
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn variable_length_barcodes_fit_one_length_byte() {
+        assert!(Symbology::Code39.validate(&"A".repeat(255)).is_ok());
+        assert!(Symbology::Code39.validate(&"A".repeat(256)).is_err());
+        assert!(Symbology::Codabar.validate(&"1".repeat(256)).is_err());
+        assert!(Symbology::Itf.validate(&"12".repeat(127)).is_ok());
+        assert!(Symbology::Itf.validate(&"12".repeat(128)).is_err());
+        assert!(Symbology::Itf.validate("").is_err());
+    }
+
+    #[test]
+    fn drawer_and_beep_from_json_use_defaults() {
+        let json = r#"[{"open_drawer": {}}, {"open_drawer": {"pin": 5, "on_ms": 50}}, {"beep": {"times": 3}}]"#;
+        let cmds: Vec<PrintCommand> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(cmds[0], PrintCommand::open_drawer());
+        assert_eq!(
+            cmds[1],
+            PrintCommand::OpenDrawer {
+                pin: 5,
+                on_ms: 50,
+                off_ms: 200,
+            }
+        );
+        assert_eq!(cmds[2], PrintCommand::Beep { times: 3, duration: 2 });
+        assert!(validate_commands(&cmds).is_ok());
+    }
+
+    #[test]
+    fn nv_logo_from_markdown_is_centered() {
+        let blocks = parse_receipt_markdown("![logo](nv:LG)\n# CAFE");
+        let cmds = generate_commands(&blocks, 42);
+        assert_eq!(
+            &cmds[..3],
+            &[
+                PrintCommand::SetAlignment(Alignment::Center),
+                PrintCommand::NvLogo("LG".into()),
+                PrintCommand::SetAlignment(Alignment::Left),
+            ]
+        );
+        assert!(validate_commands(&[PrintCommand::NvLogo("LOGO".into())]).is_err());
+    }
+
+    #[test]
+    fn directives_are_emitted_between_lines() {
+        let blocks = parse_receipt_markdown("Paid\n{drawer}\nThanks");
+        let cmds = generate_commands(&blocks, 42);
+        let drawer = cmds
+            .iter()
+            .position(|c| *c == PrintCommand::open_drawer())
+            .expect("drawer kick");
+        assert_eq!(cmds[drawer - 2], PrintCommand::Write("Paid".into()));
+        assert_eq!(cmds[drawer + 1], PrintCommand::Write("Thanks".into()));
+    }
+
+    #[test]
+    fn validation_accepts_png_image() {
+        let img = image::DynamicImage::ImageLuma8(image::GrayImage::new(8, 8));
+        let mut buf = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        let cmds = vec![PrintCommand::Image {
+            data: buf.into_inner(),
+            bright: false,
+        }];
+        assert!(validate_commands(&cmds).is_ok());
+    }
+
+    #[test]
+    fn page_blocks_wrap_to_their_regions() {
+        let page = Page::side_by_side(
+            512,
+            96,
+            200,
+            vec![ReceiptBlock::Command(PrintCommand::NvLogo("LG".into()))],
+            parse_receipt_markdown("# Order 42"),
+        );
+        let commands = generate_commands(&[ReceiptBlock::Page(page)], 42);
+        let [PrintCommand::Page(page)] = commands.as_slice() else {
+            panic!("expected one page: {commands:?}");
+        };
+        assert_eq!(page.regions[0].content[1], PrintCommand::NvLogo("LG".into()));
+        assert!(page.regions[1].content.contains(&PrintCommand::Write("Order 42".into())));
+
+        let json = r#"[{"page": {"height": 96, "regions": [
+            {"x": 0, "y": 0, "width": 256, "height": 96, "direction": "bottom_to_top",
+             "content": [{"write": "SIDEWAYS"}, {"cut": "full"}]}]}}]"#;
+        let cmds: Vec<PrintCommand> = serde_json::from_str(json).unwrap();
+        let err = validate_commands(&cmds).unwrap_err();
+        assert!(err.contains("not allowed"), "{err}");
+    }
 }
@@ -0,0 +1,319 @@
+//! Routing jobs between several open printers.
+//!
+//! `[[route]]` entries in the config file send each kind of job to a set of
+//! printers, picking one by a strategy:
+//!
+//! ```toml
+//! [[route]]
+//! jobs = ["strip"]
+//! printers = ["X58-0001"]
+//!
+//! [[route]]
+//! jobs = ["upload"]
+//! strategy = "least_busy"
+//! ```
+//!
+//! Printers are named by USB serial or address; no `printers` means all of
+//! them. The first entry listing a job's kind wins, and kinds no entry lists
+//! go to the first printer. A printer that can't print (offline, paper out,
+//! cover open) is skipped: the job fails over to the route's other printers,
+//! then to any printer that's ready. With none ready the job waits on the
+//! route's first printer.
+//!
+//! A photo strip arrives as one job per frame. Frames sent with the same
+//! strip session all go to the printer that took the first one, ready or
+//! not, so a strip is never split between printers; the next strip is
+//! routed afresh.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::printer::backend::PrinterAddress;
+use crate::printer::discovery::DiscoveredPrinter;
+
+/// The kinds of job a route can name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// A message from the website poller.
+    Message,
+    /// A photo from `/print/upload`.
+    Upload,
+    /// A photo booth strip frame from `/print/strip`.
+    Strip,
+    /// Log text from `/print/text`.
+    Text,
+    /// A command list from `/print/commands`.
+    Commands,
+    /// An NV logo operation from the admin endpoints.
+    Logo,
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JobKind::Message => "message",
+            JobKind::Upload => "upload",
+            JobKind::Strip => "strip",
+            JobKind::Text => "text",
+            JobKind::Commands => "commands",
+            JobKind::Logo => "logo",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How a route picks between its ready printers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// The first listed printer, the rest only as failover.
+    #[default]
+    First,
+    /// Each printer in turn.
+    RoundRobin,
+    /// The printer with the fewest jobs waiting.
+    LeastBusy,
+}
+
+/// A `[[route]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteRule {
+    pub jobs: Vec<JobKind>,
+    /// Serials or addresses, in order of preference; empty for all.
+    #[serde(default)]
+    pub printers: Vec<String>,
+    #[serde(default)]
+    pub strategy: Strategy,
+}
+
+impl RouteRule {
+    /// Position of `printer` in this route's list, if it's on it.
+    fn rank(&self, printer: &DiscoveredPrinter) -> Option<usize> {
+        if self.printers.is_empty() {
+            return Some(0);
+        }
+        self.printers.iter().position(|name| names(name, printer))
+    }
+}
+
+/// Whether a serial or address from the config names `printer`.
+fn names(name: &str, printer: &DiscoveredPrinter) -> bool {
+    printer.serial.as_deref() == Some(name)
+        || name
+            .parse::<PrinterAddress>()
+            .is_ok_and(|address| address.selects(&printer.address))
+}
+
+/// A printer a job could go to.
+#[derive(Debug, Clone, Copy)]
+pub struct Target<'a> {
+    pub printer: &'a DiscoveredPrinter,
+    /// Connected and able to print right now.
+    pub ready: bool,
+    /// Jobs queued or printing.
+    pub load: usize,
+}
+
+/// Picks a printer for each job, remembering whose turn it is.
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    rules: Vec<RouteRule>,
+    /// Round-robin position per rule.
+    turns: Vec<usize>,
+    /// The strip session being printed, and the key of its printer.
+    strip: Option<(String, String)>,
+}
+
+impl Router {
+    pub fn new(rules: Vec<RouteRule>) -> Self {
+        let turns = vec![0; rules.len()];
+        Self {
+            rules,
+            turns,
+            strip: None,
+        }
+    }
+
+    /// The routes from the config file.
+    pub fn from_config() -> Self {
+        Self::new(crate::config::file_config().route.clone())
+    }
+
+    /// The index in `targets` for a job of `kind`, or None with no printers.
+    pub fn route(&mut self, kind: JobKind, targets: &[Target<'_>]) -> Option<usize> {
+        if targets.is_empty() {
+            return None;
+        }
+        let rule = self.rules.iter().position(|r| r.jobs.contains(&kind));
+
+        // The route's printers, in its order of preference
+        let mut preferred: Vec<(usize, usize)> = targets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| {
+                let rank = match rule {
+                    Some(r) => self.rules[r].rank(t.printer)?,
+                    None => 0,
+                };
+                Some((rank, i))
+            })
+            .collect();
+        preferred.sort();
+        let preferred: Vec<usize> = preferred.into_iter().map(|(_, i)| i).collect();
+        let ready: Vec<usize> = preferred.iter().copied().filter(|&i| targets[i].ready).collect();
+
+        if !ready.is_empty() {
+            let strategy = rule.map_or(Strategy::First, |r| self.rules[r].strategy);
+            let pick = match strategy {
+                Strategy::First => ready[0],
+                Strategy::RoundRobin => {
+                    let turn = rule.map_or(0, |r| self.turns[r]);
+                    if let Some(r) = rule {
+                        self.turns[r] = turn.wrapping_add(1);
+                    }
+                    ready[turn % ready.len()]
+                }
+                Strategy::LeastBusy => *ready
+                    .iter()
+                    .min_by_key(|&&i| targets[i].load)
+                    .unwrap_or(&ready[0]),
+            };
+            return Some(pick);
+        }
+
+        // Failover: any printer that can print, then wait on the route's own
+        if let Some(i) = targets.iter().position(|t| t.ready) {
+            return Some(i);
+        }
+        Some(preferred.first().copied().unwrap_or(0))
+    }
+
+    /// The index in `targets` for a frame of the strip `session`: the
+    /// printer that took its first frame while it's still there, otherwise
+    /// the one the strip route picks.
+    pub fn route_strip(&mut self, session: &str, targets: &[Target<'_>]) -> Option<usize> {
+        if let Some((current, key)) = &self.strip {
+            if current == session {
+                if let Some(i) = targets.iter().position(|t| t.printer.key() == *key) {
+                    return Some(i);
+                }
+            }
+        }
+        let i = self.route(JobKind::Strip, targets)?;
+        self.strip = Some((session.to_string(), targets[i].printer.key()));
+        Some(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printer(serial: &str, address: &str) -> DiscoveredPrinter {
+        DiscoveredPrinter {
+            serial: Some(serial.to_string()),
+            ..DiscoveredPrinter::for_output(address.parse().unwrap())
+        }
+    }
+
+    fn targets<'a>(printers: &'a [DiscoveredPrinter], ready: &[bool], load: &[usize]) -> Vec<Target<'a>> {
+        printers
+            .iter()
+            .enumerate()
+            .map(|(i, printer)| Target {
+                printer,
+                ready: ready[i],
+                load: load[i],
+            })
+            .collect()
+    }
+
+    fn router(toml: &str) -> Router {
+        Router::new(crate::config::parse(toml).unwrap().route)
+    }
+
+    #[test]
+    fn routes_by_kind_with_failover() {
+        let printers = [printer("A-1", "tcp://10.0.0.1:9100"), printer("B-2", "tcp://10.0.0.2:9100")];
+        let mut router = router(
+            r#"
+            [[route]]
+            jobs = ["strip"]
+            printers = ["B-2"]
+
+            [[route]]
+            jobs = ["message", "text"]
+            printers = ["tcp://10.0.0.2:9100", "A-1"]
+            "#,
+        );
+
+        let both = targets(&printers, &[true, true], &[0, 0]);
+        assert_eq!(router.route(JobKind::Strip, &both), Some(1));
+        assert_eq!(router.route(JobKind::Message, &both), Some(1));
+        // No route: the first printer
+        assert_eq!(router.route(JobKind::Upload, &both), Some(0));
+
+        // B out of paper: strips and messages move to A
+        let a_only = targets(&printers, &[true, false], &[0, 0]);
+        assert_eq!(router.route(JobKind::Strip, &a_only), Some(0));
+        assert_eq!(router.route(JobKind::Text, &a_only), Some(0));
+
+        // Neither ready: wait on the route's own printer
+        let neither = targets(&printers, &[false, false], &[0, 0]);
+        assert_eq!(router.route(JobKind::Strip, &neither), Some(1));
+        assert_eq!(router.route(JobKind::Strip, &[]), None);
+    }
+
+    #[test]
+    fn spreads_uploads_by_strategy() {
+        let printers = [
+            printer("A-1", "tcp://10.0.0.1:9100"),
+            printer("B-2", "tcp://10.0.0.2:9100"),
+            printer("C-3", "tcp://10.0.0.3:9100"),
+        ];
+        let mut router = router(
+            r#"
+            [[route]]
+            jobs = ["upload"]
+            strategy = "round_robin"
+
+            [[route]]
+            jobs = ["strip"]
+            strategy = "least_busy"
+            "#,
+        );
+
+        let all = targets(&printers, &[true, true, true], &[2, 0, 1]);
+        let turns: Vec<_> = (0..4).map(|_| router.route(JobKind::Upload, &all).unwrap()).collect();
+        assert_eq!(turns, [0, 1, 2, 0]);
+        assert_eq!(router.route(JobKind::Strip, &all), Some(1));
+
+        let b_offline = targets(&printers, &[true, false, true], &[2, 0, 1]);
+        assert_eq!(router.route(JobKind::Strip, &b_offline), Some(2));
+    }
+
+    #[test]
+    fn strip_frames_stay_on_one_printer() {
+        let printers = [printer("A-1", "tcp://10.0.0.1:9100"), printer("B-2", "tcp://10.0.0.2:9100")];
+        let mut router = router(
+            r#"
+            [[route]]
+            jobs = ["strip"]
+            strategy = "round_robin"
+            "#,
+        );
+
+        let both = targets(&printers, &[true, true], &[0, 0]);
+        assert_eq!(router.route_strip("one", &both), Some(0));
+        assert_eq!(router.route_strip("one", &both), Some(0));
+        // A out of paper mid-strip: the rest of the strip waits for it
+        let b_only = targets(&printers, &[false, true], &[1, 0]);
+        assert_eq!(router.route_strip("one", &b_only), Some(0));
+        // The next strip can go elsewhere
+        assert_eq!(router.route_strip("two", &b_only), Some(1));
+        assert_eq!(router.route_strip("two", &both), Some(1));
+    }
+}
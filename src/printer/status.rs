@@ -1,9 +1,20 @@
-#[derive(Debug, Clone, Default)]
+use std::sync::{Arc, LazyLock, Mutex};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::error::AppError;
+use crate::printer::paper::PaperGeometry;
+use crate::printer::usage::RollReport;
+
+/// Printer state decoded from the `DLE EOT 1/2/4` real-time status bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
 pub struct PrinterStatus {
     pub online: bool,
     pub cover_open: bool,
     pub paper_near_end: bool,
     pub paper_out: bool,
+    /// Mechanical, cutter or head-temperature error.
     pub error: bool,
 }
 
@@ -12,16 +23,16 @@ impl PrinterStatus {
         Self {
             online: (printer_byte & 0x08) == 0,
             cover_open: (offline_byte & 0x04) != 0,
-            error: (offline_byte & 0x20) != 0,
+            error: (offline_byte & 0x40) != 0,
             paper_near_end: (paper_byte & 0x0C) != 0,
-            paper_out: (paper_byte & 0x60) != 0,
+            // Printing stopped at paper end, or the roll sensor sees no paper
+            paper_out: (offline_byte & 0x20) != 0 || (paper_byte & 0x60) != 0,
         }
     }
 
+    /// Most important condition first — a printer out of paper also
+    /// reports itself offline.
     pub fn summary(&self) -> &'static str {
-        if !self.online {
-            return "Offline";
-        }
         if self.paper_out {
             return "Paper Out";
         }
@@ -31,9 +42,120 @@ impl PrinterStatus {
         if self.error {
             return "Error";
         }
+        if !self.online {
+            return "Offline";
+        }
         if self.paper_near_end {
             return "Paper Low";
         }
         "Ready"
     }
+
+    /// The condition that stops this printer from printing, if any.
+    pub fn blocking_error(&self) -> Option<AppError> {
+        if self.paper_out {
+            Some(AppError::PaperOut)
+        } else if self.cover_open {
+            Some(AppError::CoverOpen)
+        } else if self.error {
+            Some(AppError::Printer(
+                "mechanical or cutter error — check for a paper jam".to_string(),
+            ))
+        } else if !self.online {
+            Some(AppError::PrinterOffline)
+        } else {
+            None
+        }
+    }
+}
+
+/// The latest status of the active printer, shared between the code that
+/// polls it and whatever displays it (`GET /status`, the GUI).
+pub type SharedStatus = Arc<Mutex<StatusReport>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct StatusReport {
+    pub printer: Option<String>,
+    /// Line width and resolution of that printer, for paper estimates.
+    pub geometry: Option<PaperGeometry>,
+    /// Paper used on its current roll.
+    pub roll: Option<RollReport>,
+    /// None when the printer doesn't report status (or there is no printer).
+    pub status: Option<PrinterStatus>,
+}
+
+impl StatusReport {
+    pub fn summary(&self) -> &'static str {
+        match (&self.printer, &self.status) {
+            (None, _) => "No printer",
+            (Some(_), None) => "Unknown",
+            (Some(_), Some(status)) => status.summary(),
+        }
+    }
+}
+
+pub fn new_shared_status() -> SharedStatus {
+    Arc::new(Mutex::new(StatusReport::default()))
+}
+
+/// Every open printer's status, by `DiscoveredPrinter::key()`, in the order
+/// the printers were opened.
+pub type SharedStatuses = Arc<Mutex<Vec<(String, SharedStatus)>>>;
+
+pub fn new_shared_statuses() -> SharedStatuses {
+    Arc::new(Mutex::new(Vec::new()))
+}
+
+/// A status change reported by the printer itself (ASB), as it happens.
+#[derive(Debug, Clone)]
+pub struct StatusChange {
+    /// The printer's model name, for logs.
+    pub printer: String,
+    /// `DiscoveredPrinter::key()`, telling apart printers of one model.
+    pub key: String,
+    pub status: PrinterStatus,
+}
+
+static STATUS_EVENTS: LazyLock<broadcast::Sender<StatusChange>> =
+    LazyLock::new(|| broadcast::channel(16).0);
+
+/// Receive every status change from any open connection.
+pub fn subscribe() -> broadcast::Receiver<StatusChange> {
+    STATUS_EVENTS.subscribe()
+}
+
+pub(crate) fn publish(change: StatusChange) {
+    // No subscribers is fine
+    let _ = STATUS_EVENTS.send(change);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_printer() {
+        // Fixed bits (0x12) set, nothing else
+        let status = PrinterStatus::from_status_bytes(0x12, 0x12, 0x12);
+        assert_eq!(status.summary(), "Ready");
+        assert!(status.blocking_error().is_none());
+    }
+
+    #[test]
+    fn paper_out_outranks_offline() {
+        let status = PrinterStatus::from_status_bytes(0x1a, 0x32, 0x72);
+        assert!(!status.online);
+        assert_eq!(status.summary(), "Paper Out");
+        assert!(matches!(status.blocking_error(), Some(AppError::PaperOut)));
+    }
+
+    #[test]
+    fn cover_open_and_paper_low() {
+        let cover = PrinterStatus::from_status_bytes(0x1a, 0x16, 0x12);
+        assert!(matches!(cover.blocking_error(), Some(AppError::CoverOpen)));
+
+        let low = PrinterStatus::from_status_bytes(0x12, 0x12, 0x1e);
+        assert_eq!(low.summary(), "Paper Low");
+        assert!(low.blocking_error().is_none());
+    }
 }
@@ -0,0 +1,230 @@
+//! Paper used per printer since its last roll change.
+//!
+//! Each job printed on a real printer has its estimate (see `paper`) added to
+//! a running total per printer, keyed by USB serial number (or address, for
+//! printers without one), and saved to a small JSON file so the count
+//! survives restarts: `RECEIPTS_USAGE`, or `receipts/usage.json` in the
+//! user's data directory (e.g. `~/.local/share` on Linux).
+//! Against the roll length from the config file, that predicts the paper
+//! left well before the printer's near-end sensor trips.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::printer::discovery::DiscoveredPrinter;
+
+/// Where the usage file goes under the user's data directory.
+const DEFAULT_PATH: &str = "receipts/usage.json";
+
+/// Remaining paper below which a roll is reported low, if the config
+/// doesn't say.
+const DEFAULT_LOW_ROLL_M: u32 = 3;
+
+/// Paper used on one printer's current roll.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollUsage {
+    pub used_mm: u64,
+    /// When the roll was last changed (RFC 3339), if ever reset.
+    pub changed_at: Option<String>,
+}
+
+/// The usage file: roll usage by printer key.
+#[derive(Debug, Default)]
+pub struct UsageFile {
+    path: PathBuf,
+    rolls: BTreeMap<String, RollUsage>,
+}
+
+impl UsageFile {
+    /// Read the file at `path`. A missing file starts every count at zero;
+    /// a malformed one is logged and replaced on the next save.
+    pub fn load(path: &Path) -> Self {
+        let rolls = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring invalid paper usage file {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                tracing::warn!("Can't read paper usage file {}: {e}", path.display());
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path: path.to_path_buf(),
+            rolls,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> RollUsage {
+        self.rolls.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Add a job's paper to a printer's roll and save.
+    pub fn add(&mut self, key: &str, mm: u32) {
+        self.rolls.entry(key.to_string()).or_default().used_mm += mm as u64;
+        self.save();
+    }
+
+    /// Start a new roll and save.
+    pub fn reset(&mut self, key: &str) -> RollUsage {
+        let usage = RollUsage {
+            used_mm: 0,
+            changed_at: Some(chrono::Local::now().to_rfc3339()),
+        };
+        self.rolls.insert(key.to_string(), usage.clone());
+        self.save();
+        usage
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.rolls)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&self.path, json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            tracing::warn!("Can't save paper usage to {}: {e}", self.path.display());
+        }
+    }
+}
+
+static USAGE: LazyLock<Mutex<UsageFile>> = LazyLock::new(|| {
+    let path = std::env::var_os("RECEIPTS_USAGE")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_dir().map(|dir| dir.join(DEFAULT_PATH)))
+        .unwrap_or_else(|| PathBuf::from("receipts-usage.json"));
+    Mutex::new(UsageFile::load(&path))
+});
+
+/// The key a printer's usage is kept under.
+pub fn usage_key(printer: &DiscoveredPrinter) -> String {
+    printer
+        .serial
+        .clone()
+        .unwrap_or_else(|| printer.address.to_string())
+}
+
+/// Add a printed job's estimated paper to the printer's roll. Outputs
+/// other than printers (dry runs, files) have no roll and aren't counted.
+pub fn record(printer: &DiscoveredPrinter, mm: u32) {
+    if !printer.address.is_device() {
+        return;
+    }
+    if let Ok(mut usage) = USAGE.lock() {
+        usage.add(&usage_key(printer), mm);
+    }
+}
+
+/// Start counting a new roll on the printer with this key.
+pub fn reset(key: &str) -> RollUsage {
+    match USAGE.lock() {
+        Ok(mut usage) => usage.reset(key),
+        Err(_) => RollUsage::default(),
+    }
+}
+
+/// A printer's roll: paper used, and what's left if the roll length is
+/// configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollReport {
+    pub key: String,
+    pub usage: RollUsage,
+    pub roll_mm: Option<u64>,
+    pub low_mm: u64,
+}
+
+impl RollReport {
+    pub fn for_printer(printer: &DiscoveredPrinter) -> Self {
+        let key = usage_key(printer);
+        let usage = USAGE.lock().map(|u| u.get(&key)).unwrap_or_default();
+        let paper = &config::file_config().paper;
+        let roll_m = printer
+            .settings()
+            .and_then(|s| s.roll_length_m)
+            .or(paper.roll_length_m);
+        Self {
+            key,
+            usage,
+            roll_mm: roll_m.map(|m| m as u64 * 1000),
+            low_mm: paper.low_roll_m.unwrap_or(DEFAULT_LOW_ROLL_M) as u64 * 1000,
+        }
+    }
+
+    pub fn remaining_mm(&self) -> Option<u64> {
+        self.roll_mm
+            .map(|roll| roll.saturating_sub(self.usage.used_mm))
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.remaining_mm().is_some_and(|left| left <= self.low_mm)
+    }
+
+    /// "≈2 m left" ("≈40 cm left" under a metre), if the roll length is
+    /// known.
+    pub fn summary(&self) -> Option<String> {
+        let left = self.remaining_mm()?;
+        Some(if left >= 1000 {
+            format!("≈{} m left", (left + 500) / 1000)
+        } else {
+            format!("≈{} cm left", left.div_ceil(10))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_survives_reload_and_resets() {
+        let path = std::env::temp_dir().join(format!("receipts-usage-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut usage = UsageFile::load(&path);
+        usage.add("J4KF012345", 1200);
+        usage.add("J4KF012345", 300);
+        usage.add("tcp://10.0.0.20:9100", 50);
+
+        let mut reloaded = UsageFile::load(&path);
+        assert_eq!(reloaded.get("J4KF012345").used_mm, 1500);
+        assert_eq!(reloaded.get("tcp://10.0.0.20:9100").used_mm, 50);
+
+        reloaded.reset("J4KF012345");
+        let reset = UsageFile::load(&path).get("J4KF012345");
+        assert_eq!(reset.used_mm, 0);
+        assert!(reset.changed_at.is_some());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn remaining_paper_predicts_low_roll() {
+        let mut report = RollReport {
+            key: "J4KF012345".into(),
+            usage: RollUsage {
+                used_mm: 77_600,
+                changed_at: None,
+            },
+            roll_mm: Some(80_000),
+            low_mm: 3000,
+        };
+        assert_eq!(report.remaining_mm(), Some(2400));
+        assert!(report.is_low());
+        assert_eq!(report.summary().as_deref(), Some("≈2 m left"));
+
+        report.usage.used_mm = 79_650;
+        assert_eq!(report.summary().as_deref(), Some("≈35 cm left"));
+
+        report.roll_mm = None;
+        assert!(!report.is_low());
+        assert_eq!(report.summary(), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Direct USB access through nusb.
+//!
+//! Replaces escpos' `NativeUsbDriver`, whose `read` copies the reply into a
+//! temporary buffer (so status bytes never reach the caller) and opens a new
+//! reader per call. Here the bulk endpoints are claimed once and kept, so a
+//! late status reply is still picked up by the next read.
+//!
+//! Devices are picked by serial number or port as well as VID/PID, so two
+//! identical printers each open as themselves.
+
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use escpos::driver::Driver;
+use escpos::errors::{PrinterError, Result as DriverResult};
+use nusb::io::{EndpointRead, EndpointWrite};
+use nusb::transfer::{Bulk, Direction, EndpointType, In, Out};
+use nusb::MaybeFuture;
+
+use crate::printer::models::USB_CLASS_PRINTER;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct UsbBackend {
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    writer: Mutex<EndpointWrite<Bulk>>,
+    /// None for write-only printers (no bulk IN endpoint).
+    reader: Option<Mutex<EndpointRead<Bulk>>>,
+}
+
+impl UsbBackend {
+    /// Open the device with these IDs and, when given, this serial number
+    /// and port path (see `port_path`).
+    pub fn open(
+        vendor_id: u16,
+        product_id: u16,
+        serial: Option<&str>,
+        port: Option<&str>,
+    ) -> Result<Self, String> {
+        let info = nusb::list_devices()
+            .wait()
+            .map_err(|e| e.to_string())?
+            .find(|d| {
+                d.vendor_id() == vendor_id
+                    && d.product_id() == product_id
+                    && serial.is_none_or(|want| d.serial_number() == Some(want))
+                    && port.is_none_or(|want| port_path(d).as_deref() == Some(want))
+            })
+            .ok_or_else(|| match (serial, port) {
+                (Some(serial), _) => format!("USB device with serial {serial} not found"),
+                (None, Some(port)) => format!("USB device not found on port {port}"),
+                (None, None) => "USB device not found".to_string(),
+            })?;
+        let device = info.open().wait().map_err(|e| e.to_string())?;
+
+        // Prefer the printer-class interface; fall back to the first one
+        // with a bulk OUT endpoint (vendor-specific ESC/POS interfaces).
+        let config = device.active_configuration().map_err(|e| e.to_string())?;
+        let mut candidates: Vec<_> = config
+            .interface_alt_settings()
+            .filter(|alt| alt.alternate_setting() == 0)
+            .filter_map(|alt| {
+                let bulk = |dir: Direction| {
+                    alt.endpoints()
+                        .find(|ep| ep.transfer_type() == Bulk::TYPE && ep.direction() == dir)
+                        .map(|ep| ep.address())
+                };
+                let out = bulk(Direction::Out)?;
+                let printer_class = alt.class() == USB_CLASS_PRINTER;
+                Some((printer_class, alt.interface_number(), out, bulk(Direction::In)))
+            })
+            .collect();
+        candidates.sort_by_key(|(printer_class, ..)| !printer_class);
+        let (_, number, out, input) = candidates
+            .into_iter()
+            .next()
+            .ok_or_else(|| "No bulk OUT endpoint found on USB device".to_string())?;
+
+        let interface = device
+            .detach_and_claim_interface(number)
+            .wait()
+            .map_err(|e| e.to_string())?;
+
+        let out = interface.endpoint::<Bulk, Out>(out).map_err(|e| e.to_string())?;
+        let packet_size = out.max_packet_size();
+        let writer = out.writer(packet_size).with_write_timeout(WRITE_TIMEOUT);
+
+        let reader = match input {
+            Some(addr) => {
+                let ep = interface
+                    .endpoint::<Bulk, In>(addr)
+                    .map_err(|e| e.to_string())?;
+                let packet_size = ep.max_packet_size();
+                Some(Mutex::new(ep.reader(packet_size).with_read_timeout(READ_TIMEOUT)))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            vendor_id,
+            product_id,
+            serial: info.serial_number().map(str::to_string),
+            writer: Mutex::new(writer),
+            reader,
+        })
+    }
+}
+
+/// Where a device is plugged in, as bus and hub port numbers (`001-2.3`).
+/// Unlike the device address, this stays the same when the device is
+/// unplugged and plugged back into the same port.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+pub fn port_path(info: &nusb::DeviceInfo) -> Option<String> {
+    let chain = info.port_chain();
+    if chain.is_empty() {
+        return None;
+    }
+    let ports: Vec<String> = chain.iter().map(u8::to_string).collect();
+    Some(format!("{}-{}", info.bus_id(), ports.join(".")))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn port_path(_info: &nusb::DeviceInfo) -> Option<String> {
+    None
+}
+
+impl Driver for UsbBackend {
+    fn name(&self) -> String {
+        match &self.serial {
+            Some(serial) => format!(
+                "USB ({:04x}:{:04x}, serial {serial})",
+                self.vendor_id, self.product_id
+            ),
+            None => format!("USB ({:04x}:{:04x})", self.vendor_id, self.product_id),
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> DriverResult<()> {
+        // Submit immediately, like NativeUsbDriver did: a write that returns
+        // Ok has reached the printer.
+        let mut writer = self.writer.lock()?;
+        writer.write_all(data)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8]) -> DriverResult<usize> {
+        let Some(reader) = &self.reader else {
+            return Ok(0);
+        };
+        let mut reader = reader.lock()?;
+        reader.read(buf).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::TimedOut {
+                PrinterError::Io("No reply from USB printer".to_string())
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    fn flush(&self) -> DriverResult<()> {
+        self.writer.lock()?.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1246 @@
+//! Virtual printer: an ESC/POS interpreter that renders to an image.
+//!
+//! Consumes the same byte stream a real printer would — text, `ESC E`, `ESC -`,
+//! `GS !`, `ESC a`, `GS v 0` rasters, cuts — and draws it onto a canvas the
+//! width of the print head. Text uses the public-domain 8x8 bitmap font scaled
+//! into Font A (12×24) and Font B (9×17) cells, so line lengths match paper.
+//! Barcodes and QR codes are drawn as placeholders, and cuts as a
+//! dashed line. NV graphics (`GS ( L`) are kept for the life of the printer,
+//! so logos can be stored, listed and printed. Page mode (`ESC L` … `FF`)
+//! draws each region separately, rotates it to its print direction and
+//! composes the page when it is printed. Upside-down text (`ESC {`) is
+//! drawn turned 180° in place, line by line.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use font8x8::UnicodeFonts;
+use image::{imageops, GrayImage};
+
+use crate::printer::page::{self, PageDirection};
+use crate::receipt_markdown::Alignment;
+
+/// Print head width on 80mm paper with the margins used by `image_proc`.
+pub const DEFAULT_WIDTH_DOTS: u32 = 512;
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const DLE: u8 = 0x10;
+const DC2: u8 = 0x12;
+const LF: u8 = 0x0a;
+const HT: u8 = 0x09;
+const FF: u8 = 0x0c;
+
+/// Default line spacing: 1/6 inch at 180 dpi.
+const DEFAULT_LINE_SPACING: u32 = 30;
+const WHITE: u8 = 255;
+const BLACK: u8 = 0;
+const CUT_MARK: u8 = 160;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Font {
+    #[default]
+    A,
+    B,
+}
+
+impl Font {
+    /// Character cell (width, height) in dots.
+    fn cell(self) -> (u32, u32) {
+        match self {
+            Font::A => (12, 24),
+            Font::B => (9, 17),
+        }
+    }
+
+    /// Top-left offset of the 8x16 glyph (8x8 doubled vertically) in the cell.
+    fn glyph_offset(self) -> (u32, u32) {
+        match self {
+            Font::A => (2, 4),
+            Font::B => (0, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Style {
+    font: Font,
+    bold: bool,
+    /// Underline thickness in dots (0 = off).
+    underline: u8,
+    width_mult: u8,
+    height_mult: u8,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            font: Font::A,
+            bold: false,
+            underline: 0,
+            width_mult: 1,
+            height_mult: 1,
+        }
+    }
+}
+
+impl Style {
+    fn cell(&self) -> (u32, u32) {
+        let (w, h) = self.font.cell();
+        (w * self.width_mult as u32, h * self.height_mult as u32)
+    }
+}
+
+/// A raster graphic held in NV memory.
+#[derive(Debug, Clone)]
+struct NvGraphic {
+    width_bytes: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+/// Page mode: the page laid out so far, plus a nested interpreter drawing
+/// the current region in its own (unrotated) coordinates.
+struct PageState {
+    canvas: GrayImage,
+    /// Print area (x, y, width, height); None until `ESC W`, meaning the
+    /// whole page, as tall as its content.
+    area: Option<[u32; 4]>,
+    direction: PageDirection,
+    region: VirtualPrinter,
+    /// Bottom of the lowest region drawn.
+    used: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    ch: char,
+    style: Style,
+}
+
+/// Stateful ESC/POS interpreter with a growing canvas.
+pub struct VirtualPrinter {
+    width: u32,
+    /// Row-major luminance, `width` pixels per row. 255 = paper, 0 = dot.
+    pixels: Vec<u8>,
+    y: u32,
+    style: Style,
+    align: Alignment,
+    line_spacing: u32,
+    /// `ESC {`: lines are printed turned 180°.
+    upside_down: bool,
+    line: Vec<Glyph>,
+    /// Text bytes not yet decoded into glyphs (decoded as UTF-8 on the next
+    /// control byte, so multi-byte characters may span `feed` calls).
+    text_bytes: Vec<u8>,
+    /// Bytes of an incomplete command, kept until more data arrives.
+    pending: Vec<u8>,
+    cuts: Vec<u32>,
+    barcode_height: u32,
+    barcode_module: u32,
+    barcode_hri: bool,
+    qr_module: u32,
+    qr_data: Vec<u8>,
+    /// Replies to status requests, drained by `read_responses`.
+    responses: Vec<u8>,
+    /// NV graphics by key code. Survives `ESC @`.
+    nv_graphics: BTreeMap<[u8; 2], NvGraphic>,
+    /// Set between `ESC L` and `FF`.
+    page: Option<Box<PageState>>,
+}
+
+impl Default for VirtualPrinter {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH_DOTS)
+    }
+}
+
+impl VirtualPrinter {
+    pub fn new(width: u32) -> Self {
+        Self {
+            width: width.max(8),
+            pixels: Vec::new(),
+            y: 0,
+            style: Style::default(),
+            align: Alignment::Left,
+            line_spacing: DEFAULT_LINE_SPACING,
+            upside_down: false,
+            line: Vec::new(),
+            text_bytes: Vec::new(),
+            pending: Vec::new(),
+            cuts: Vec::new(),
+            barcode_height: 162,
+            barcode_module: 3,
+            barcode_hri: false,
+            qr_module: 3,
+            qr_data: Vec::new(),
+            responses: Vec::new(),
+            nv_graphics: BTreeMap::new(),
+            page: None,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Paper positions (in dots from the top) where a cut was issued.
+    pub fn cuts(&self) -> &[u32] {
+        &self.cuts
+    }
+
+    /// Take any bytes the printer would have sent back (status replies).
+    pub fn read_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// Interpret a chunk of the byte stream. Commands split across calls are
+    /// buffered until complete.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        let data = std::mem::take(&mut self.pending);
+        let mut pos = 0;
+        while pos < data.len() {
+            match self.step(&data[pos..]) {
+                Some(consumed) => pos += consumed,
+                None => break,
+            }
+        }
+        self.pending = data[pos..].to_vec();
+    }
+
+    /// Render the paper so far, including any unprinted line buffer, with
+    /// cut positions marked.
+    pub fn render(&self) -> GrayImage {
+        let mut copy = VirtualPrinter {
+            pixels: self.pixels.clone(),
+            line: self.line.clone(),
+            text_bytes: self.text_bytes.clone(),
+            pending: Vec::new(),
+            cuts: self.cuts.clone(),
+            qr_data: Vec::new(),
+            responses: Vec::new(),
+            nv_graphics: BTreeMap::new(),
+            // An unprinted page isn't on the paper yet
+            page: None,
+            ..*self
+        };
+        if !copy.line.is_empty() || !copy.text_bytes.is_empty() {
+            copy.print_line();
+        }
+
+        let height = copy.rows().max(copy.y).max(1);
+        copy.ensure_rows(height);
+        for &cut in &copy.cuts {
+            let row = cut.min(height - 1);
+            for x in (0..copy.width).filter(|x| (x / 8).is_multiple_of(2)) {
+                copy.pixels[(row * copy.width + x) as usize] = CUT_MARK;
+            }
+        }
+
+        GrayImage::from_raw(copy.width, height, copy.pixels)
+            .unwrap_or_else(|| GrayImage::from_pixel(copy.width, 1, image::Luma([WHITE])))
+    }
+
+    /// Whether anything has been printed since the paper was last torn off.
+    pub fn has_paper(&self) -> bool {
+        self.y > 0 || self.rows() > 0 || !self.line.is_empty() || !self.text_bytes.is_empty()
+    }
+
+    /// Tear off the paper printed so far, keeping the printer's settings
+    /// and NV graphics.
+    pub fn tear_off(&mut self) {
+        self.pixels.clear();
+        self.y = 0;
+        self.cuts.clear();
+        self.line.clear();
+        self.text_bytes.clear();
+    }
+
+    /// Render and encode as PNG.
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageLuma8(self.render())
+            .write_to(&mut buf, image::ImageFormat::Png)
+            .map_err(|e| format!("PNG encode failed: {e}"))?;
+        Ok(buf.into_inner())
+    }
+
+    /// Execute one command or text byte at the start of `data`.
+    /// Returns the number of bytes consumed, or `None` if incomplete.
+    fn step(&mut self, data: &[u8]) -> Option<usize> {
+        if self.page.is_some() {
+            return self.page_step(data);
+        }
+        let b = data[0];
+        if b >= 0x20 && b != 0x7f {
+            self.text_bytes.push(b);
+            return Some(1);
+        }
+
+        self.decode_text();
+        match b {
+            LF => {
+                self.print_line();
+                Some(1)
+            }
+            HT => {
+                let col = self.line.len();
+                for _ in col..((col / 8) + 1) * 8 {
+                    self.push_glyph(' ');
+                }
+                Some(1)
+            }
+            ESC => self.esc(data),
+            GS => self.gs(data),
+            DLE => self.dle(data),
+            // DC2 # n — print density on clone mechanisms
+            DC2 if data.get(1) == Some(&b'#') => data.get(2).map(|_| 3),
+            // CR, FF and other control bytes have no effect in standard mode
+            _ => Some(1),
+        }
+    }
+
+    fn esc(&mut self, data: &[u8]) -> Option<usize> {
+        let cmd = *data.get(1)?;
+        let arg = |i: usize| data.get(i).copied();
+        match cmd {
+            b'@' => {
+                self.reset();
+                Some(2)
+            }
+            b'L' => {
+                self.begin_page();
+                Some(2)
+            }
+            // ESC S — already in standard mode
+            b'S' => Some(2),
+            // ESC W — print area, only used in page mode
+            b'W' => data.get(9).map(|_| 10),
+            // ESC $ / ESC \ — horizontal positions
+            b'$' | b'\\' => data.get(3).map(|_| 4),
+            b'E' | b'G' => {
+                self.style.bold = arg(2)? & 1 != 0;
+                Some(3)
+            }
+            b'-' => {
+                self.style.underline = match arg(2)? {
+                    1 | b'1' => 1,
+                    2 | b'2' => 2,
+                    _ => 0,
+                };
+                Some(3)
+            }
+            b'!' => {
+                let n = arg(2)?;
+                self.style.font = if n & 0x01 != 0 { Font::B } else { Font::A };
+                self.style.bold = n & 0x08 != 0;
+                self.style.height_mult = if n & 0x10 != 0 { 2 } else { 1 };
+                self.style.width_mult = if n & 0x20 != 0 { 2 } else { 1 };
+                self.style.underline = if n & 0x80 != 0 { 1 } else { 0 };
+                Some(3)
+            }
+            b'{' => {
+                self.upside_down = arg(2)? & 1 != 0;
+                Some(3)
+            }
+            b'M' => {
+                self.style.font = if arg(2)? & 1 != 0 { Font::B } else { Font::A };
+                Some(3)
+            }
+            b'a' => {
+                self.align = match arg(2)? {
+                    1 | b'1' => Alignment::Center,
+                    2 | b'2' => Alignment::Right,
+                    _ => Alignment::Left,
+                };
+                Some(3)
+            }
+            b'2' => {
+                self.line_spacing = DEFAULT_LINE_SPACING;
+                Some(2)
+            }
+            b'3' => {
+                self.line_spacing = arg(2)? as u32;
+                Some(3)
+            }
+            b'd' => {
+                let n = arg(2)? as u32;
+                if n > 0 {
+                    self.print_line();
+                    self.y += (n - 1) * self.line_spacing;
+                } else if !self.line.is_empty() {
+                    self.print_line();
+                }
+                Some(3)
+            }
+            b'J' => {
+                let n = arg(2)? as u32;
+                if self.line.is_empty() {
+                    self.y += n;
+                } else {
+                    let h = self.draw_line();
+                    self.advance(n.max(h));
+                }
+                Some(3)
+            }
+            // ESC p m t1 t2 — drawer kick pulse
+            b'p' => data.get(4).map(|_| 5),
+            // ESC B n t — buzzer
+            b'B' => data.get(3).map(|_| 4),
+            // ESC ( A pL pH ... — Epson buzzer
+            b'(' => {
+                let len = arg(3)? as usize | (arg(4)? as usize) << 8;
+                data.get(4 + len).map(|_| 5 + len)
+            }
+            // ESC c 3/4/5 n — panel and sensor settings
+            b'c' => data.get(3).map(|_| 4),
+            // Single-argument settings with no visible effect here:
+            // ESC SP, ESC t, ESC R, ESC V, ESC U, ESC =
+            _ => data.get(2).map(|_| 3),
+        }
+    }
+
+    fn gs(&mut self, data: &[u8]) -> Option<usize> {
+        let cmd = *data.get(1)?;
+        let arg = |i: usize| data.get(i).copied();
+        match cmd {
+            b'!' => {
+                let n = arg(2)?;
+                self.style.width_mult = ((n >> 4) & 0x07) + 1;
+                self.style.height_mult = (n & 0x07) + 1;
+                Some(3)
+            }
+            b'v' => self.raster(data),
+            b'V' => {
+                let m = arg(2)?;
+                let (len, feed) = match m {
+                    0 | 1 | b'0' | b'1' => (3, 0),
+                    _ => (4, arg(3)? as u32),
+                };
+                if !self.line.is_empty() {
+                    self.print_line();
+                }
+                self.y += feed;
+                self.cuts.push(self.y);
+                Some(len)
+            }
+            b'H' => {
+                self.barcode_hri = matches!(arg(2)?, 2 | 3 | b'2' | b'3');
+                Some(3)
+            }
+            b'h' => {
+                self.barcode_height = arg(2)?.max(1) as u32;
+                Some(3)
+            }
+            b'w' => {
+                self.barcode_module = arg(2)?.clamp(1, 6) as u32;
+                Some(3)
+            }
+            b'k' => self.barcode(data),
+            // GS a n — enable ASB: answer with an initial "all clear" packet
+            b'a' => {
+                if arg(2)? != 0 {
+                    self.responses.extend_from_slice(&[0x10, 0x00, 0x00, 0x00]);
+                }
+                Some(3)
+            }
+            b'(' => {
+                let len = *data.get(3)? as usize | (*data.get(4)? as usize) << 8;
+                let body = data.get(5..5 + len)?;
+                match arg(2)? {
+                    b'k' => self.qr_function(body),
+                    b'L' => self.nv_function(body),
+                    // GS ( H fn 48 — transmission response, sent at once
+                    // since nothing here is buffered
+                    b'H' if body.len() == 6 && body[0] == 48 => {
+                        self.responses.extend_from_slice(&[0x37, 0x22]);
+                        self.responses.extend_from_slice(&body[2..6]);
+                        self.responses.push(0x00);
+                    }
+                    _ => {}
+                }
+                Some(5 + len)
+            }
+            // GS 8 L p1 p2 p3 p4 — long-form graphics data
+            b'8' => {
+                let len = u32::from_le_bytes([*data.get(3)?, *data.get(4)?, *data.get(5)?, *data.get(6)?]);
+                let total = 7 + len as usize;
+                let body = data.get(7..total)?;
+                if arg(2)? == b'L' {
+                    self.nv_function(body);
+                }
+                Some(total)
+            }
+            // GS L / GS W / GS P — margins, print width, motion units;
+            // GS $ / GS \ — vertical positions in page mode
+            b'L' | b'W' | b'P' | b'$' | b'\\' => data.get(3).map(|_| 4),
+            _ => data.get(2).map(|_| 3),
+        }
+    }
+
+    fn dle(&mut self, data: &[u8]) -> Option<usize> {
+        match *data.get(1)? {
+            // DLE EOT n — real-time status: always "online, ready, paper OK"
+            0x04 => {
+                let n = *data.get(2)?;
+                self.responses.push(match n {
+                    1 => 0x16,
+                    _ => 0x12,
+                });
+                Some(3)
+            }
+            // DLE DC4 fn a b — real-time pulse / power / clear
+            0x14 => data.get(4).map(|_| 5),
+            _ => data.get(2).map(|_| 3),
+        }
+    }
+
+    /// GS v 0 m xL xH yL yH d1...dk
+    fn raster(&mut self, data: &[u8]) -> Option<usize> {
+        let header = data.get(..8)?;
+        let m = header[3];
+        let width_bytes = header[4] as usize | (header[5] as usize) << 8;
+        let height = header[6] as usize | (header[7] as usize) << 8;
+        let body = data.get(8..8 + width_bytes * height)?;
+
+        if !self.line.is_empty() {
+            self.print_line();
+        }
+
+        let sx = if m & 1 != 0 { 2 } else { 1 };
+        let sy = if m & 2 != 0 { 2 } else { 1 };
+        self.draw_bitmap(width_bytes, height, body, sx, sy);
+        Some(8 + width_bytes * height)
+    }
+
+    /// Draw a packed 1-bit bitmap at the current position, scaled by
+    /// `sx` × `sy`, and advance past it.
+    fn draw_bitmap(&mut self, width_bytes: usize, height: usize, body: &[u8], sx: u32, sy: u32) {
+        let img_w = (width_bytes * 8) as u32 * sx;
+        let x0 = self.aligned_x(img_w);
+        self.ensure_rows(self.y + height as u32 * sy);
+
+        for row in 0..height {
+            for col in 0..width_bytes * 8 {
+                if body[row * width_bytes + col / 8] & (0x80 >> (col % 8)) != 0 {
+                    let x = x0 + col as u32 * sx;
+                    let y = self.y + row as u32 * sy;
+                    self.fill(x, y, sx, sy);
+                }
+            }
+        }
+        self.y += height as u32 * sy;
+    }
+
+    /// GS ( L / GS 8 L m fn ... — NV graphics: list, delete, define, print.
+    fn nv_function(&mut self, body: &[u8]) {
+        let Some(&func) = body.get(1) else {
+            return;
+        };
+        let key = |i: usize| Some([*body.get(i)?, *body.get(i + 1)?]);
+        match func {
+            // Key code list: one block, "no more data"
+            64 => {
+                self.responses.extend_from_slice(&[0x37, 0x72, 0x40]);
+                for key in self.nv_graphics.keys() {
+                    self.responses.extend_from_slice(key);
+                }
+                self.responses.push(0x00);
+            }
+            66 => {
+                if let Some(key) = key(2) {
+                    self.nv_graphics.remove(&key);
+                }
+            }
+            67 => {
+                let (Some(key), Some(header)) = (key(3), body.get(6..10)) else {
+                    return;
+                };
+                let width = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let height = u16::from_le_bytes([header[2], header[3]]) as usize;
+                let width_bytes = width.div_ceil(8);
+                if let Some(data) = body.get(11..11 + width_bytes * height) {
+                    let graphic = NvGraphic {
+                        width_bytes,
+                        height,
+                        data: data.to_vec(),
+                    };
+                    self.nv_graphics.insert(key, graphic);
+                }
+            }
+            69 => {
+                let Some(graphic) = key(2).and_then(|k| self.nv_graphics.get(&k)).cloned() else {
+                    return;
+                };
+                if !self.line.is_empty() {
+                    self.print_line();
+                }
+                let sx = body.get(4).map_or(1, |&x| x.clamp(1, 2) as u32);
+                let sy = body.get(5).map_or(1, |&y| y.clamp(1, 2) as u32);
+                self.draw_bitmap(graphic.width_bytes, graphic.height, &graphic.data, sx, sy);
+            }
+            _ => {}
+        }
+    }
+
+    /// GS k — function A (NUL-terminated) or function B (length-prefixed).
+    fn barcode(&mut self, data: &[u8]) -> Option<usize> {
+        let m = *data.get(2)?;
+        let (payload, len) = if m <= 6 {
+            let end = data[3..].iter().position(|&b| b == 0)?;
+            (&data[3..3 + end], 4 + end)
+        } else {
+            let n = *data.get(3)? as usize;
+            (data.get(4..4 + n)?, 4 + n)
+        };
+
+        if !self.line.is_empty() {
+            self.print_line();
+        }
+
+        // Placeholder: quiet-zone guards plus one module per payload bit
+        let module = self.barcode_module;
+        let mut bars: Vec<bool> = vec![true, false, true, false];
+        for byte in payload {
+            bars.extend((0..8).map(|i| byte & (0x80 >> i) != 0));
+        }
+        bars.extend([false, true, false, true]);
+        let bar_w = (bars.len() as u32 * module).min(self.width);
+        let x0 = self.aligned_x(bar_w);
+        let h = self.barcode_height;
+        self.ensure_rows(self.y + h);
+        for (i, &on) in bars.iter().enumerate() {
+            let x = x0 + i as u32 * module;
+            if on && x < self.width {
+                self.fill(x, self.y, module, h);
+            }
+        }
+        self.y += h;
+
+        if self.barcode_hri {
+            let saved = self.style;
+            self.style = Style::default();
+            for ch in String::from_utf8_lossy(payload).chars() {
+                self.push_glyph(ch);
+            }
+            self.print_line();
+            self.style = saved;
+        }
+        Some(len)
+    }
+
+    /// GS ( k cn fn ... — only the QR (cn = 49) functions matter.
+    fn qr_function(&mut self, body: &[u8]) {
+        let (Some(&cn), Some(&func)) = (body.first(), body.get(1)) else {
+            return;
+        };
+        if cn != 49 {
+            return;
+        }
+        match func {
+            67 => self.qr_module = body.get(2).copied().unwrap_or(3).clamp(1, 16) as u32,
+            80 => self.qr_data = body.get(3..).unwrap_or_default().to_vec(),
+            81 => self.draw_qr_placeholder(),
+            _ => {}
+        }
+    }
+
+    /// Draw a 25×25-module square with finder patterns and a data-derived fill.
+    fn draw_qr_placeholder(&mut self) {
+        if !self.line.is_empty() {
+            self.print_line();
+        }
+        const MODULES: u32 = 25;
+        let m = self.qr_module;
+        let side = MODULES * m;
+        let x0 = self.aligned_x(side);
+        self.ensure_rows(self.y + side);
+
+        let seed: u32 = self
+            .qr_data
+            .iter()
+            .fold(2166136261u32, |h, &b| (h ^ b as u32).wrapping_mul(16777619));
+        let in_finder = |cx: u32, cy: u32| -> Option<bool> {
+            for (fx, fy) in [(0, 0), (MODULES - 7, 0), (0, MODULES - 7)] {
+                if (fx..fx + 7).contains(&cx) && (fy..fy + 7).contains(&cy) {
+                    let (dx, dy) = (cx - fx, cy - fy);
+                    let ring = dx == 0 || dy == 0 || dx == 6 || dy == 6;
+                    let core = (2..=4).contains(&dx) && (2..=4).contains(&dy);
+                    return Some(ring || core);
+                }
+            }
+            None
+        };
+        for cy in 0..MODULES {
+            for cx in 0..MODULES {
+                let on = in_finder(cx, cy).unwrap_or_else(|| {
+                    let h = seed ^ (cx * 31 + cy * 17).wrapping_mul(2654435761);
+                    h.count_ones().is_multiple_of(2)
+                });
+                if on {
+                    self.fill(x0 + cx * m, self.y + cy * m, m, m);
+                }
+            }
+        }
+        self.y += side;
+    }
+
+    /// `ESC L`: finish the current line and start an empty page.
+    fn begin_page(&mut self) {
+        if !self.line.is_empty() || !self.text_bytes.is_empty() {
+            self.print_line();
+        }
+        let region = self.region_printer(self.width);
+        self.page = Some(Box::new(PageState {
+            canvas: GrayImage::from_pixel(self.width, page::MAX_HEIGHT as u32, image::Luma([WHITE])),
+            area: None,
+            direction: PageDirection::LeftToRight,
+            region,
+            used: 0,
+        }));
+    }
+
+    /// A blank interpreter for one region, in the current text style.
+    fn region_printer(&self, line_dots: u32) -> VirtualPrinter {
+        VirtualPrinter {
+            style: self.style,
+            align: self.align,
+            line_spacing: self.line_spacing,
+            barcode_height: self.barcode_height,
+            barcode_module: self.barcode_module,
+            barcode_hri: self.barcode_hri,
+            nv_graphics: self.nv_graphics.clone(),
+            ..VirtualPrinter::new(line_dots)
+        }
+    }
+
+    /// Page mode: area, direction and end-of-page commands are handled here;
+    /// everything else draws into the current region.
+    fn page_step(&mut self, data: &[u8]) -> Option<usize> {
+        match data[0] {
+            ESC => match *data.get(1)? {
+                b'W' => {
+                    let p = data.get(2..10)?;
+                    let v = |i: usize| u16::from_le_bytes([p[i], p[i + 1]]) as u32;
+                    self.end_region();
+                    let width = self.width;
+                    let page_h = page::MAX_HEIGHT as u32;
+                    let (x, y) = (v(0).min(width - 1), v(2).min(page_h - 1));
+                    let area = [x, y, v(4).clamp(1, width - x), v(6).clamp(1, page_h - y)];
+                    self.start_region(Some(area), None);
+                    Some(10)
+                }
+                b'T' => {
+                    let direction = PageDirection::from_code(*data.get(2)?);
+                    self.end_region();
+                    self.start_region(None, Some(direction));
+                    Some(3)
+                }
+                b'S' => {
+                    self.page = None;
+                    Some(2)
+                }
+                b'@' => {
+                    self.page = None;
+                    self.reset();
+                    Some(2)
+                }
+                _ => self.page.as_mut()?.region.step(data),
+            },
+            FF => {
+                self.end_page();
+                Some(1)
+            }
+            _ => self.page.as_mut()?.region.step(data),
+        }
+    }
+
+    /// Start a fresh region, changing its area and/or direction.
+    fn start_region(&mut self, area: Option<[u32; 4]>, direction: Option<PageDirection>) {
+        let Some(mut page) = self.page.take() else {
+            return;
+        };
+        page.area = area.or(page.area);
+        page.direction = direction.unwrap_or(page.direction);
+        let [_, _, w, h] = page.area.unwrap_or([0, 0, self.width, page::MAX_HEIGHT as u32]);
+        let line_dots = if page.direction.is_sideways() { h } else { w };
+        page.region = self.region_printer(line_dots.max(8));
+        self.page = Some(page);
+    }
+
+    /// Draw the current region onto the page, rotated to its direction.
+    fn end_region(&mut self) {
+        let Some(page) = self.page.as_mut() else {
+            return;
+        };
+        let region = std::mem::replace(&mut page.region, VirtualPrinter::new(8));
+        let drawn = region.render();
+        // Later regions continue in the same text style
+        self.style = region.style;
+        self.align = region.align;
+        self.line_spacing = region.line_spacing;
+
+        // An explicit print area prints in full, even where it's blank
+        if let Some([_, y, _, h]) = page.area {
+            page.used = page.used.max(y + h);
+        }
+        if drawn.pixels().all(|p| p[0] == WHITE) {
+            return;
+        }
+        let sideways = page.direction.is_sideways();
+        let [x, y, w, h] = page.area.unwrap_or([0, 0, self.width, drawn.height()]);
+        // The region's own coordinates: lines across, length down
+        let (across, length) = if sideways { (h, w) } else { (w, h) };
+        let mut unrotated = GrayImage::from_pixel(across, length, image::Luma([WHITE]));
+        imageops::replace(&mut unrotated, &imageops::crop_imm(&drawn, 0, 0, across, length).to_image(), 0, 0);
+        let placed = match page.direction {
+            PageDirection::LeftToRight => unrotated,
+            PageDirection::BottomToTop => imageops::rotate270(&unrotated),
+            PageDirection::RightToLeft => imageops::rotate180(&unrotated),
+            PageDirection::TopToBottom => imageops::rotate90(&unrotated),
+        };
+        for (px, py, p) in placed.enumerate_pixels() {
+            let (cx, cy) = (x + px, y + py);
+            if cx < page.canvas.width() && cy < page.canvas.height() && p[0] < page.canvas.get_pixel(cx, cy)[0] {
+                page.canvas.put_pixel(cx, cy, *p);
+            }
+        }
+        page.used = page.used.max((y + placed.height()).min(page.canvas.height()));
+    }
+
+    /// `FF`: print the page onto the paper and return to standard mode.
+    fn end_page(&mut self) {
+        self.end_region();
+        let Some(mut page) = self.page.take() else {
+            return;
+        };
+        self.ensure_rows(self.y + page.used);
+        for (px, py, p) in imageops::crop(&mut page.canvas, 0, 0, self.width, page.used).to_image().enumerate_pixels() {
+            if p[0] == BLACK {
+                self.pixels[((self.y + py) * self.width + px) as usize] = BLACK;
+            }
+        }
+        self.y += page.used;
+    }
+
+    fn reset(&mut self) {
+        self.style = Style::default();
+        self.align = Alignment::Left;
+        self.line_spacing = DEFAULT_LINE_SPACING;
+        self.upside_down = false;
+        self.line.clear();
+        self.text_bytes.clear();
+        self.barcode_height = 162;
+        self.barcode_module = 3;
+        self.barcode_hri = false;
+    }
+
+    /// Turn buffered text bytes into glyphs in the current style.
+    fn decode_text(&mut self) {
+        if self.text_bytes.is_empty() {
+            return;
+        }
+        let bytes = std::mem::take(&mut self.text_bytes);
+        for ch in String::from_utf8_lossy(&bytes).chars() {
+            self.push_glyph(ch);
+        }
+    }
+
+    /// Append a glyph, wrapping onto a new line when the print area is full.
+    fn push_glyph(&mut self, ch: char) {
+        let (w, _) = self.style.cell();
+        if self.line_width() + w > self.width && !self.line.is_empty() {
+            self.print_line();
+        }
+        self.line.push(Glyph {
+            ch,
+            style: self.style,
+        });
+    }
+
+    fn line_width(&self) -> u32 {
+        self.line.iter().map(|g| g.style.cell().0).sum()
+    }
+
+    /// Print the line buffer and advance one line.
+    fn print_line(&mut self) {
+        self.decode_text();
+        let h = self.draw_line();
+        self.advance(h.max(self.line_spacing));
+    }
+
+    /// Draw the line buffer at the current position without advancing.
+    /// Returns the height of the tallest glyph (0 if the line was empty).
+    fn draw_line(&mut self) -> u32 {
+        let line = std::mem::take(&mut self.line);
+        let line_h = line.iter().map(|g| g.style.cell().1).max().unwrap_or(0);
+        if line.is_empty() {
+            return 0;
+        }
+
+        let width: u32 = line.iter().map(|g| g.style.cell().0).sum();
+        let mut x = self.aligned_x(width);
+        self.ensure_rows(self.y + line_h);
+        for g in &line {
+            let (w, h) = g.style.cell();
+            // Glyphs sit on a common baseline at the bottom of the line
+            self.draw_glyph(x, self.y + line_h - h, g);
+            x += w;
+        }
+        line_h
+    }
+
+    /// Advance past a printed line `h` dots tall, turning it 180° first
+    /// under `ESC {`.
+    fn advance(&mut self, h: u32) {
+        if self.upside_down && h > 0 {
+            self.ensure_rows(self.y + h);
+            // Reversing whole rows of pixels turns them 180°
+            let start = (self.y * self.width) as usize;
+            let end = ((self.y + h) * self.width) as usize;
+            self.pixels[start..end].reverse();
+        }
+        self.y += h;
+    }
+
+    fn draw_glyph(&mut self, x: u32, y: u32, g: &Glyph) {
+        let bitmap = font8x8::BASIC_FONTS
+            .get(g.ch)
+            .or_else(|| font8x8::LATIN_FONTS.get(g.ch))
+            .or_else(|| font8x8::BOX_FONTS.get(g.ch))
+            .or_else(|| font8x8::BASIC_FONTS.get('?'))
+            .unwrap_or([0; 8]);
+
+        let wm = g.style.width_mult as u32;
+        let hm = g.style.height_mult as u32;
+        let (ox, oy) = g.style.font.glyph_offset();
+        // Bold is a one-dot horizontal double strike
+        let dot_w = if g.style.bold { 2 * wm } else { wm };
+
+        for (row, bits) in bitmap.iter().enumerate() {
+            for col in 0..8u32 {
+                if bits & (1 << col) != 0 {
+                    let px = x + (ox + col) * wm;
+                    let py = y + (oy + row as u32 * 2) * hm;
+                    self.fill(px, py, dot_w, 2 * hm);
+                }
+            }
+        }
+
+        if g.style.underline > 0 {
+            let (cw, ch) = g.style.cell();
+            let thickness = g.style.underline as u32;
+            self.fill(x, y + ch - thickness, cw, thickness);
+        }
+    }
+
+    fn aligned_x(&self, content_width: u32) -> u32 {
+        let free = self.width.saturating_sub(content_width);
+        match self.align {
+            Alignment::Left => 0,
+            Alignment::Center => free / 2,
+            Alignment::Right => free,
+        }
+    }
+
+    fn rows(&self) -> u32 {
+        (self.pixels.len() / self.width as usize) as u32
+    }
+
+    fn ensure_rows(&mut self, rows: u32) {
+        if rows > self.rows() {
+            self.pixels.resize((rows * self.width) as usize, WHITE);
+        }
+    }
+
+    fn fill(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        self.ensure_rows(y + h);
+        for yy in y..y + h {
+            for xx in x..(x + w).min(self.width) {
+                self.pixels[(yy * self.width + xx) as usize] = BLACK;
+            }
+        }
+    }
+}
+
+/// Render a complete ESC/POS byte stream to an image.
+pub fn render_escpos(bytes: &[u8], width: u32) -> GrayImage {
+    let mut printer = VirtualPrinter::new(width);
+    printer.feed(bytes);
+    printer.render()
+}
+
+/// An `escpos` driver backed by a `VirtualPrinter`.
+///
+/// Every write is interpreted immediately; every flush re-renders the paper to
+/// `output` as a PNG (when set). Status requests are answered as "ready".
+#[derive(Clone)]
+pub struct VirtualDriver {
+    printer: Arc<Mutex<VirtualPrinter>>,
+    output: Option<PathBuf>,
+}
+
+impl VirtualDriver {
+    pub fn open(width: u32, output: Option<PathBuf>) -> Self {
+        Self {
+            printer: Arc::new(Mutex::new(VirtualPrinter::new(width))),
+            output,
+        }
+    }
+
+    /// Handle to the underlying interpreter, e.g. to render on demand.
+    pub fn printer(&self) -> Arc<Mutex<VirtualPrinter>> {
+        self.printer.clone()
+    }
+}
+
+impl escpos::driver::Driver for VirtualDriver {
+    fn name(&self) -> String {
+        match &self.output {
+            Some(path) => format!("Virtual printer ({})", path.display()),
+            None => "Virtual printer".to_string(),
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> escpos::errors::Result<()> {
+        self.printer.lock()?.feed(data);
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8]) -> escpos::errors::Result<usize> {
+        let mut printer = self.printer.lock()?;
+        let mut replies = printer.read_responses();
+        let n = replies.len().min(buf.len());
+        buf[..n].copy_from_slice(&replies[..n]);
+        // Keep anything that didn't fit for the next read
+        printer.responses = replies.split_off(n);
+        Ok(n)
+    }
+
+    /// Write the paper printed since the last flush to the output file and
+    /// tear it off, so each job replaces the last. Connections to outputs
+    /// flush once per job, not per band.
+    fn flush(&self) -> escpos::errors::Result<()> {
+        let Some(path) = &self.output else {
+            return Ok(());
+        };
+        let mut printer = self.printer.lock()?;
+        // Status requests flush too
+        if !printer.has_paper() {
+            return Ok(());
+        }
+        let png = printer.to_png().map_err(escpos::errors::PrinterError::Io)?;
+        printer.tear_off();
+        std::fs::write(path, png)
+            .map_err(|e| escpos::errors::PrinterError::Io(format!("{}: {e}", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::encoder::{encode_commands, Dialect, EncodedJob, Encoder};
+    use crate::printer::image_proc::RasterImage;
+    use crate::printer::logo;
+    use crate::printer::page::{Page, PageRegion};
+    use crate::printer::rich_print::PrintCommand;
+    use crate::receipt_markdown::parse_receipt_markdown;
+
+    fn black_in(img: &GrayImage, x0: u32, x1: u32, y0: u32, y1: u32) -> usize {
+        let mut n = 0;
+        for y in y0..y1.min(img.height()) {
+            for x in x0..x1.min(img.width()) {
+                if img.get_pixel(x, y)[0] == BLACK {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    #[test]
+    fn text_line_advances_by_line_spacing() {
+        let img = render_escpos(b"HELLO\n", 512);
+        assert_eq!(img.width(), 512);
+        assert_eq!(img.height(), DEFAULT_LINE_SPACING);
+        // Five Font A cells = 60 dots of ink, nothing beyond
+        assert!(black_in(&img, 0, 60, 0, 24) > 0);
+        assert_eq!(black_in(&img, 60, 512, 0, 30), 0);
+    }
+
+    #[test]
+    fn centered_text_is_offset() {
+        let img = render_escpos(b"\x1ba\x01AB\n", 512);
+        // 24 dots of text centered in 512 → starts at 244
+        assert_eq!(black_in(&img, 0, 244, 0, 30), 0);
+        assert!(black_in(&img, 244, 268, 0, 30) > 0);
+    }
+
+    #[test]
+    fn double_size_line_is_taller() {
+        let img = render_escpos(b"\x1d!\x11X\n\x1d!\x00X\n", 512);
+        assert_eq!(img.height(), 48 + DEFAULT_LINE_SPACING);
+    }
+
+    #[test]
+    fn raster_pixels_are_exact() {
+        let raster = RasterImage {
+            width_bytes: 1,
+            height: 2,
+            data: vec![0x80, 0x01],
+        };
+        let mut enc = Encoder::new(Dialect::EPSON);
+        enc.raster(&raster);
+        let img = render_escpos(&enc.finish().to_bytes(), 64);
+        assert_eq!(img.height(), 2);
+        assert_eq!(img.get_pixel(0, 0)[0], BLACK);
+        assert_eq!(img.get_pixel(7, 1)[0], BLACK);
+        assert_eq!(black_in(&img, 0, 64, 0, 2), 2);
+    }
+
+    #[test]
+    fn cuts_are_recorded() {
+        let blocks = parse_receipt_markdown("Hi");
+        let bytes = EncodedJob::receipt(&blocks, 42, true, Dialect::EPSON).to_bytes();
+        let mut printer = VirtualPrinter::new(512);
+        printer.feed(&bytes);
+        // One text line + three feed lines, then the cut
+        assert_eq!(printer.cuts(), &[4 * DEFAULT_LINE_SPACING]);
+    }
+
+    #[test]
+    fn output_file_holds_the_last_job() {
+        use escpos::driver::Driver;
+        let path = std::env::temp_dir().join(format!("receipts-virtual-{}.png", std::process::id()));
+        let driver = VirtualDriver::open(512, Some(path.clone()));
+        let blocks = parse_receipt_markdown("Hi");
+        let bytes = EncodedJob::receipt(&blocks, 42, true, Dialect::EPSON).to_bytes();
+        for _ in 0..2 {
+            driver.write(&bytes).unwrap();
+            driver.flush().unwrap();
+            let png = image::open(&path).unwrap();
+            assert_eq!(png.height(), 4 * DEFAULT_LINE_SPACING);
+        }
+
+        // Nothing new printed, nothing written
+        std::fs::remove_file(&path).unwrap();
+        driver.write(&[DLE, 0x04, 1]).unwrap();
+        driver.flush().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn split_stream_matches_whole_stream() {
+        let bytes = b"\x1bE\x01Bold\x1bE\x00 caf\xc3\xa9\n\x1dVA\x00".to_vec();
+        let whole = render_escpos(&bytes, 256);
+
+        let mut printer = VirtualPrinter::new(256);
+        for b in &bytes {
+            printer.feed(std::slice::from_ref(b));
+        }
+        assert_eq!(printer.render(), whole);
+    }
+
+    #[test]
+    fn status_request_is_answered() {
+        let mut printer = VirtualPrinter::new(512);
+        printer.feed(&[0x10, 0x04, 1, 0x10, 0x04, 4]);
+        assert_eq!(printer.read_responses(), vec![0x16, 0x12]);
+    }
+
+    #[test]
+    fn nv_logo_is_stored_listed_and_printed() {
+        let raster = RasterImage {
+            width_bytes: 1,
+            height: 2,
+            data: vec![0xFF, 0xFF],
+        };
+        let mut printer = VirtualPrinter::new(64);
+        printer.feed(&logo::store_command("LG", &raster).unwrap());
+        printer.feed(&logo::LIST_KEYS);
+        assert_eq!(printer.read_responses(), vec![0x37, 0x72, 0x40, b'L', b'G', 0x00]);
+
+        printer.feed(&logo::print_command("LG").unwrap());
+        let img = printer.render();
+        assert_eq!(img.height(), 2);
+        assert_eq!(black_in(&img, 0, 64, 0, 2), 16);
+
+        printer.feed(&logo::delete_command("LG").unwrap());
+        printer.feed(&logo::LIST_KEYS);
+        assert_eq!(printer.read_responses(), vec![0x37, 0x72, 0x40, 0x00]);
+    }
+
+    #[test]
+    fn page_regions_print_side_by_side() {
+        let page = Page::side_by_side(
+            512,
+            60,
+            256,
+            vec![PrintCommand::Write("LEFT".into())],
+            vec![PrintCommand::Write("RIGHT".into())],
+        );
+        let bytes = encode_commands(&[PrintCommand::Page(page)], Dialect::EPSON).unwrap();
+        let img = render_escpos(&[&bytes[..], b"BELOW\n"].concat(), 512);
+
+        // Both regions share the first 24 rows; the page is 60 dots tall
+        assert!(black_in(&img, 0, 48, 0, 24) > 0);
+        assert!(black_in(&img, 256, 316, 0, 24) > 0);
+        assert_eq!(black_in(&img, 60, 256, 0, 60), 0);
+        assert!(black_in(&img, 0, 60, 60, 90) > 0, "text after the page");
+    }
+
+    #[test]
+    fn sideways_region_is_rotated() {
+        let page = Page {
+            height: 120,
+            regions: vec![PageRegion {
+                x: 0,
+                y: 0,
+                width: 24,
+                height: 120,
+                direction: PageDirection::TopToBottom,
+                content: vec![PrintCommand::Write("ABCDE".into())],
+            }],
+        };
+        let bytes = encode_commands(&[PrintCommand::Page(page)], Dialect::EPSON).unwrap();
+        let img = render_escpos(&bytes, 512);
+        assert_eq!(img.height(), 120);
+        // Five 12-dot characters run down a 24-dot column instead of across
+        assert!(black_in(&img, 0, 24, 48, 60) > 0);
+        assert_eq!(black_in(&img, 0, 24, 60, 120), 0);
+        assert_eq!(black_in(&img, 24, 512, 0, 120), 0);
+    }
+
+    #[test]
+    fn long_text_wraps_at_print_width() {
+        let text = "A".repeat(50);
+        let img = render_escpos(format!("{text}\n").as_bytes(), 512);
+        // 42 Font A cells fit in 512 dots; the rest wraps onto a second line
+        assert_eq!(img.height(), 2 * DEFAULT_LINE_SPACING);
+    }
+
+    #[test]
+    fn upside_down_receipt_reads_top_to_bottom_when_turned() {
+        let blocks = parse_receipt_markdown("# TITLE\n**bold** line\n\nlast line");
+        let upside_down = Dialect {
+            upside_down: true,
+            ..Dialect::EPSON
+        };
+        let normal = render_escpos(&EncodedJob::receipt(&blocks, 42, false, Dialect::EPSON).to_bytes(), 512);
+        let turned = imageops::rotate180(&render_escpos(
+            &EncodedJob::receipt(&blocks, 42, false, upside_down).to_bytes(),
+            512,
+        ));
+        assert_eq!(normal.height(), turned.height());
+
+        // The trailing feed comes out last either way, so it ends up on top
+        // once the upside-down receipt is turned
+        let feed = 3 * DEFAULT_LINE_SPACING;
+        let text_h = normal.height() - feed;
+        let text = |img: &GrayImage, y| imageops::crop_imm(img, 0, y, 512, text_h).to_image();
+        assert_eq!(text(&normal, 0), text(&turned, feed));
+        assert_eq!(black_in(&turned, 0, 512, 0, feed), 0);
+    }
+}
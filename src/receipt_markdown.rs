@@ -1,4 +1,8 @@
 use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+use crate::printer::page::Page;
+use crate::printer::rich_print::PrintCommand;
 
 /// Formatting state for a span of receipt text.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -56,7 +60,8 @@ impl ReceiptSpan {
 }
 
 /// Alignment for a line or block.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Alignment {
     #[default]
     Left,
@@ -80,13 +85,39 @@ pub enum ReceiptBlock {
     Columns { cells: Vec<Vec<ReceiptSpan>> },
     /// A blank line.
     BlankLine,
+    /// A printer action from a directive line (`{drawer}`, `{beep}`).
+    Command(PrintCommand),
+    /// A page-mode layout with positioned (and possibly rotated) regions of
+    /// blocks, e.g. a logo beside an order number. Built in code; there is
+    /// no markdown syntax for it.
+    Page(Page<ReceiptBlock>),
 }
 
 /// Parse receipt markdown into blocks.
 ///
-/// Supports standard markdown (bold, underline/emphasis, headings, dividers)
-/// and ReceiptLine pipe syntax for columns.
+/// Supports standard markdown (bold, underline/emphasis, headings, dividers),
+/// ReceiptLine pipe syntax for columns, and directive lines for the cash
+/// drawer, the buzzer and logos stored in the printer (NV graphics):
+///
+/// ```text
+/// {drawer}
+/// {drawer pin=5 on_ms=100 off_ms=200}
+/// {beep}
+/// {beep times=3 duration=2}
+/// ![logo](nv:LG)
+/// ```
 pub fn parse_receipt_markdown(input: &str) -> Vec<ReceiptBlock> {
+    parse_blocks(input, true)
+}
+
+/// Parse receipt markdown from an untrusted source, such as the /print/text
+/// log sink: directive lines print as plain text instead of opening the
+/// drawer or sounding the buzzer.
+pub fn parse_log_markdown(input: &str) -> Vec<ReceiptBlock> {
+    parse_blocks(input, false)
+}
+
+fn parse_blocks(input: &str, directives: bool) -> Vec<ReceiptBlock> {
     let mut blocks = Vec::new();
     let mut markdown_buf = String::new();
 
@@ -100,6 +131,12 @@ pub fn parse_receipt_markdown(input: &str) -> Vec<ReceiptBlock> {
             continue;
         }
 
+        if let Some(command) = parse_directive(trimmed).filter(|_| directives) {
+            flush_markdown(&mut markdown_buf, &mut blocks);
+            blocks.push(ReceiptBlock::Command(command));
+            continue;
+        }
+
         if is_column_line(trimmed) {
             flush_markdown(&mut markdown_buf, &mut blocks);
             blocks.push(parse_column_line(trimmed));
@@ -117,6 +154,40 @@ pub fn parse_receipt_markdown(input: &str) -> Vec<ReceiptBlock> {
     blocks
 }
 
+/// Parse a `{name key=value ...}` directive line or an `nv:` logo image.
+/// Anything that isn't a well-formed, in-range directive is left to print
+/// as text.
+fn parse_directive(line: &str) -> Option<PrintCommand> {
+    if line.starts_with("![") {
+        let (_, target) = line.strip_suffix(')')?.split_once("](nv:")?;
+        crate::printer::logo::key_bytes(target).ok()?;
+        return Some(PrintCommand::NvLogo(target.to_string()));
+    }
+
+    let inner = line.strip_prefix('{')?.strip_suffix('}')?;
+    let mut words = inner.split_whitespace();
+    let mut command = match words.next()? {
+        "drawer" => PrintCommand::open_drawer(),
+        "beep" => PrintCommand::beep(),
+        _ => return None,
+    };
+
+    for word in words {
+        let (key, value) = word.split_once('=')?;
+        match (&mut command, key) {
+            (PrintCommand::OpenDrawer { pin, .. }, "pin") => *pin = value.parse().ok()?,
+            (PrintCommand::OpenDrawer { on_ms, .. }, "on_ms") => *on_ms = value.parse().ok()?,
+            (PrintCommand::OpenDrawer { off_ms, .. }, "off_ms") => *off_ms = value.parse().ok()?,
+            (PrintCommand::Beep { times, .. }, "times") => *times = value.parse().ok()?,
+            (PrintCommand::Beep { duration, .. }, "duration") => *duration = value.parse().ok()?,
+            _ => return None,
+        }
+    }
+
+    command.validate_peripheral().ok()?;
+    Some(command)
+}
+
 /// Check if a line is a pipe-delimited column (ReceiptLine syntax).
 /// Must contain `|` but not be a markdown table header (starting/ending with |).
 fn is_column_line(line: &str) -> bool {
@@ -253,13 +324,11 @@ fn flush_markdown(buf: &mut String, blocks: &mut Vec<ReceiptBlock>) {
                     });
                 }
             }
-            Event::End(TagEnd::Paragraph) => {
-                if !spans.is_empty() {
-                    blocks.push(ReceiptBlock::Line {
-                        spans: std::mem::take(&mut spans),
-                        alignment: Alignment::Left,
-                    });
-                }
+            Event::End(TagEnd::Paragraph) if !spans.is_empty() => {
+                blocks.push(ReceiptBlock::Line {
+                    spans: std::mem::take(&mut spans),
+                    alignment: Alignment::Left,
+                });
             }
             Event::Text(text) => {
                 let format = SpanFormat {
@@ -275,13 +344,11 @@ fn flush_markdown(buf: &mut String, blocks: &mut Vec<ReceiptBlock>) {
             Event::SoftBreak => {
                 spans.push(ReceiptSpan::plain(" "));
             }
-            Event::HardBreak => {
-                if !spans.is_empty() {
-                    blocks.push(ReceiptBlock::Line {
-                        spans: std::mem::take(&mut spans),
-                        alignment: Alignment::Left,
-                    });
-                }
+            Event::HardBreak if !spans.is_empty() => {
+                blocks.push(ReceiptBlock::Line {
+                    spans: std::mem::take(&mut spans),
+                    alignment: Alignment::Left,
+                });
             }
             Event::Rule => {
                 blocks.push(ReceiptBlock::Divider);
@@ -519,4 +586,29 @@ _Thank you!_";
         assert_eq!(spans[2].text, "underline");
         assert!(spans[2].format.underline);
     }
+
+    #[test]
+    fn parse_drawer_and_beep_directives() {
+        let blocks = parse_receipt_markdown("{drawer pin=5 on_ms=60}\n{beep times=2}\n{drawer pin=3}");
+        assert_eq!(
+            blocks[0],
+            ReceiptBlock::Command(PrintCommand::OpenDrawer {
+                pin: 5,
+                on_ms: 60,
+                off_ms: 200,
+            })
+        );
+        assert_eq!(
+            blocks[1],
+            ReceiptBlock::Command(PrintCommand::Beep { times: 2, duration: 2 })
+        );
+        // Out-of-range directives print as text
+        assert!(matches!(blocks[2], ReceiptBlock::Line { .. }));
+    }
+
+    #[test]
+    fn log_text_ignores_directives() {
+        let blocks = parse_log_markdown("{drawer}\n{beep}");
+        assert!(blocks.iter().all(|b| matches!(b, ReceiptBlock::Line { .. })), "{blocks:?}");
+    }
 }
@@ -1,26 +1,122 @@
 use axum::{
     body::Bytes,
-    extract::{DefaultBodyLimit, Multipart, Query, State},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse},
+    Json,
     routing::{get, post},
     Router,
 };
 use serde::Deserialize;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::{self, OverMax};
+use crate::printer::encoder::PrintOptions;
+use crate::printer::image_proc::Dither;
+use crate::printer::image_proc::Rotation;
+use crate::printer::logo::{self, LogoOp};
+use crate::printer::paper::{PaperEstimate, PaperGeometry};
+use crate::printer::rich_print::{self, PrintCommand};
+use crate::printer::router::JobKind;
+use crate::printer::status::{SharedStatus, SharedStatuses, StatusReport};
+use crate::printer::usage::{self, RollReport};
+use crate::receipt_markdown;
 
 #[derive(Debug, Clone)]
 pub enum PrintPayload {
-    Image(Vec<u8>),
+    Image(Vec<u8>, PrintOptions),
     /// Image printed without cutting — for photo strip sequences.
-    /// Fields: image bytes, feed lines, indoor brightness boost.
-    ImageNoCut(Vec<u8>, u8, bool),
-    Text { text: String, source: String },
+    /// Fields: image bytes, feed lines, indoor brightness boost, the strip
+    /// session it belongs to, options.
+    ImageNoCut(Vec<u8>, u8, bool, Option<String>, PrintOptions),
+    Text {
+        text: String,
+        source: String,
+        options: PrintOptions,
+    },
+    /// A pre-laid-out command list from `POST /print/commands`.
+    Commands(Vec<PrintCommand>, PrintOptions),
+    /// NV logo management from the `/admin/logos` endpoints.
+    Logo(LogoRequest),
+}
+
+impl PrintPayload {
+    /// What kind of job this is, for routing between printers.
+    pub fn kind(&self) -> JobKind {
+        match self {
+            PrintPayload::Image(..) => JobKind::Upload,
+            PrintPayload::ImageNoCut(..) => JobKind::Strip,
+            PrintPayload::Text { .. } => JobKind::Text,
+            PrintPayload::Commands(..) => JobKind::Commands,
+            PrintPayload::Logo(_) => JobKind::Logo,
+        }
+    }
+
+    /// The strip session of a strip frame, whose frames all print on one
+    /// printer.
+    pub fn strip_session(&self) -> Option<&str> {
+        match self {
+            PrintPayload::ImageNoCut(_, _, _, session, _) => session.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Paper the job will use, as the print workers print it.
+    pub fn paper(&self, geometry: PaperGeometry) -> PaperEstimate {
+        let mut paper = PaperEstimate::new(geometry);
+        match self {
+            // A website message: feed, rotated image, feed and cut
+            PrintPayload::Image(bytes, _) => {
+                paper.feed(2).image(bytes, Rotation::Quarter).feed(3);
+            }
+            PrintPayload::ImageNoCut(bytes, feed, ..) => {
+                paper.image(bytes, Rotation::Quarter).feed(*feed);
+            }
+            // Three blank lines, then the receipt's three-line feed
+            PrintPayload::Text { text, .. } => {
+                paper.blocks(&receipt_markdown::parse_log_markdown(text)).feed(6);
+            }
+            PrintPayload::Commands(commands, _) => {
+                paper.commands(commands);
+            }
+            PrintPayload::Logo(_) => {}
+        }
+        paper
+    }
+}
+
+/// A logo operation queued with the print jobs, plus where to send the
+/// outcome back to the waiting HTTP request.
+#[derive(Debug, Clone)]
+pub struct LogoRequest {
+    pub op: LogoOp,
+    reply: mpsc::Sender<Result<String, String>>,
+}
+
+impl LogoRequest {
+    pub fn respond(&self, result: Result<String, String>) {
+        let _ = self.reply.try_send(result);
+    }
+}
+
+/// A job from the web handlers on its way to a printer. Whatever routes it
+/// checks its paper against the printer it picked (`paper_check`) and sends
+/// the outcome back to the waiting HTTP request.
+#[derive(Debug)]
+pub struct Submission {
+    pub payload: PrintPayload,
+    /// The paper note for the response, e.g. "≈312 mm of paper", or why
+    /// the job wasn't queued.
+    pub reply: oneshot::Sender<Result<String, (StatusCode, String)>>,
 }
 
 #[derive(Clone)]
 pub struct UploadState {
-    pub tx: mpsc::Sender<PrintPayload>,
+    pub tx: mpsc::Sender<Submission>,
+    /// The first (or selected) printer's status.
+    pub status: SharedStatus,
+    /// Every open printer's status, when there are several.
+    pub printers: SharedStatuses,
 }
 
 /// GET / — mobile upload page
@@ -28,8 +124,90 @@ async fn index() -> Html<&'static str> {
     Html(UPLOAD_PAGE)
 }
 
-/// POST /print/upload — accept multipart form with "image" field
-async fn upload(State(state): State<UploadState>, mut multipart: Multipart) -> impl IntoResponse {
+/// `?density=N&speed=N&dither=NAME` on the print endpoints.
+#[derive(Deserialize)]
+struct OptionParams {
+    density: Option<i8>,
+    speed: Option<u8>,
+    dither: Option<Dither>,
+}
+
+/// Options for a job on `endpoint`: the request's, then the endpoint's
+/// configured defaults. Printer defaults are applied by the connection.
+fn job_options(endpoint: &str, requested: PrintOptions) -> Result<PrintOptions, (StatusCode, String)> {
+    let options = requested.or(config::endpoint_options(endpoint));
+    options
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(options)
+}
+
+/// Estimate a job's paper on a printer with `geometry` and check it
+/// against `[paper] max_job_mm`. Returns a note for the response, e.g.
+/// "≈312 mm of paper".
+pub fn paper_check(payload: &PrintPayload, geometry: PaperGeometry) -> Result<String, (StatusCode, String)> {
+    let mm = payload.paper(geometry).mm();
+
+    let settings = &config::file_config().paper;
+    match settings.exceeded_by(mm) {
+        None => Ok(format!("≈{mm} mm of paper")),
+        Some(max) if settings.over_max == OverMax::Reject => Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Job would use ≈{mm} mm of paper (max {max} mm)"),
+        )),
+        Some(max) => {
+            tracing::warn!("Printing a job over the paper limit: ≈{mm} mm (max {max} mm)");
+            Ok(format!("≈{mm} mm of paper, over the {max} mm limit"))
+        }
+    }
+}
+
+/// Hand a job to be routed and wait until it's queued on a printer,
+/// returning the paper note.
+async fn submit(state: &UploadState, payload: PrintPayload) -> Result<String, (StatusCode, String)> {
+    let closed = || (StatusCode::INTERNAL_SERVER_ERROR, "Print queue closed".to_string());
+    let (reply, queued) = oneshot::channel();
+    if state.tx.send(Submission { payload, reply }).await.is_err() {
+        return Err(closed());
+    }
+    queued.await.unwrap_or_else(|_| Err(closed()))
+}
+
+/// Reject an upload the printer thread couldn't decode, reading only the
+/// image header.
+fn check_image(bytes: &[u8]) -> Result<(), (StatusCode, String)> {
+    if bytes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty file".to_string()));
+    }
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())
+        .and_then(|reader| reader.into_dimensions().map_err(|e| e.to_string()))
+        .map(|_| ())
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Unreadable image: {e}")))
+}
+
+impl From<OptionParams> for PrintOptions {
+    fn from(params: OptionParams) -> Self {
+        PrintOptions {
+            density: params.density,
+            speed: params.speed,
+            dither: params.dither,
+        }
+    }
+}
+
+/// POST /print/upload — accept multipart form with "image" field.
+/// Optional query params: ?density=-6..6&speed=1..13&dither=atkinson
+async fn upload(
+    State(state): State<UploadState>,
+    Query(option_params): Query<OptionParams>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let options = match job_options("upload", option_params.into()) {
+        Ok(options) => options,
+        Err(e) => return e,
+    };
     while let Ok(Some(field)) = multipart.next_field().await {
         let name = field.name().unwrap_or("").to_string();
         if name == "image" {
@@ -39,17 +217,16 @@ async fn upload(State(state): State<UploadState>, mut multipart: Multipart) -> i
                     return (StatusCode::BAD_REQUEST, format!("Read error: {e}"));
                 }
             };
-            if bytes.is_empty() {
-                return (StatusCode::BAD_REQUEST, "Empty file".to_string());
+            if let Err(e) = check_image(&bytes) {
+                return e;
             }
             tracing::info!("Upload received: {} bytes", bytes.len());
-            if state.tx.send(PrintPayload::Image(bytes)).await.is_err() {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Print queue closed".to_string(),
-                );
-            }
-            return (StatusCode::OK, "Queued for printing".to_string());
+            let payload = PrintPayload::Image(bytes, options);
+            let paper = match submit(&state, payload).await {
+                Ok(paper) => paper,
+                Err(e) => return e,
+            };
+            return (StatusCode::OK, format!("Queued for printing ({paper})"));
         }
     }
     (StatusCode::BAD_REQUEST, "No 'image' field found".to_string())
@@ -70,6 +247,8 @@ struct StripParams {
     feed: Option<u8>,
     /// If set, apply indoor brightness boost to the thermal print pipeline.
     bright: Option<u8>,
+    /// Frames with the same session print on the same printer.
+    session: Option<String>,
 }
 
 /// POST /print/text?source=phx.server — accept plain text body and queue for printing.
@@ -79,8 +258,13 @@ struct StripParams {
 async fn print_text(
     State(state): State<UploadState>,
     Query(params): Query<TextParams>,
+    Query(option_params): Query<OptionParams>,
     body: Bytes,
 ) -> impl IntoResponse {
+    let options = match job_options("text", option_params.into()) {
+        Ok(options) => options,
+        Err(e) => return e,
+    };
     let text = match String::from_utf8(body.to_vec()) {
         Ok(t) => t,
         Err(_) => return (StatusCode::BAD_REQUEST, "Invalid UTF-8".to_string()),
@@ -102,21 +286,195 @@ async fn print_text(
         source,
         text.len()
     );
-    if state
-        .tx
-        .send(PrintPayload::Text {
-            text: filtered,
-            source,
+    let payload = PrintPayload::Text {
+        text: filtered,
+        source,
+        options,
+    };
+    let paper = match submit(&state, payload).await {
+        Ok(paper) => paper,
+        Err(e) => return e,
+    };
+    (StatusCode::OK, format!("Queued for printing ({paper})"))
+}
+
+#[derive(Deserialize)]
+struct CommandsRequest {
+    commands: Vec<PrintCommand>,
+    #[serde(default)]
+    options: PrintOptions,
+}
+
+/// POST /print/commands — accept a JSON receipt IR and queue it verbatim.
+/// Body: `{"commands": [{"write": "Hi"}, "feed", {"cut": "full"}]}`, with
+/// optional `"options": {"density": 2, "speed": 5, "dither": "atkinson"}`.
+/// Lets other services lay out receipts without markdown or raw ESC/POS.
+async fn print_commands(State(state): State<UploadState>, body: Bytes) -> impl IntoResponse {
+    let request: CommandsRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid JSON: {e}")),
+    };
+    if let Err(e) = rich_print::validate_commands(&request.commands) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e);
+    }
+    let options = match job_options("commands", request.options) {
+        Ok(options) => options,
+        Err(e) => return e,
+    };
+
+    let count = request.commands.len();
+    let payload = PrintPayload::Commands(request.commands, options);
+    tracing::info!("Command list received: {count} commands");
+    let paper = match submit(&state, payload).await {
+        Ok(paper) => paper,
+        Err(e) => return e,
+    };
+    (StatusCode::OK, format!("Queued {count} commands ({paper})"))
+}
+
+/// GET /status — the printer's last polled real-time status.
+/// `{"printer": "TM-T88VI", "summary": "Paper Low", "status": {"paper_near_end": true, ...},
+/// "paper": {"used_mm": 77600, "left_mm": 2400, "low": true, "summary": "≈2 m left"},
+/// "printers": {"J4KF012345": {"printer": "TM-T88VI", "summary": "Paper Low", ...}}}`;
+/// `status` is null when the printer doesn't report it, `left_mm` when no
+/// roll length is configured. The top level is the first printer;
+/// `printers` has every open printer by serial (or address).
+async fn printer_status(State(state): State<UploadState>) -> impl IntoResponse {
+    let report = match state.status.lock() {
+        Ok(report) => report.clone(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let members: Vec<(String, SharedStatus)> = match state.printers.lock() {
+        Ok(printers) => printers.clone(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let printers: serde_json::Map<String, serde_json::Value> = members
+        .into_iter()
+        .filter_map(|(key, status)| {
+            let report = status.lock().ok()?.clone();
+            Some((key, status_json(&report)))
         })
-        .await
-        .is_err()
-    {
-        return (
+        .collect();
+
+    let mut body = status_json(&report);
+    body["printers"] = serde_json::Value::Object(printers);
+    (StatusCode::OK, Json(body)).into_response()
+}
+
+/// One printer's report, as `GET /status` shows it.
+fn status_json(report: &StatusReport) -> serde_json::Value {
+    serde_json::json!({
+        "printer": report.printer,
+        "summary": report.summary(),
+        "status": report.status,
+        "paper": report.roll.as_ref().map(|roll| serde_json::json!({
+            "used_mm": roll.usage.used_mm,
+            "roll_mm": roll.roll_mm,
+            "left_mm": roll.remaining_mm(),
+            "low": roll.is_low(),
+            "summary": roll.summary(),
+            "changed_at": roll.usage.changed_at,
+        })),
+    })
+}
+
+/// Plain-text summary of the current printer's roll.
+fn roll_summary(roll: &RollReport) -> String {
+    let used = format!("{:.2} m used", roll.usage.used_mm as f64 / 1000.0);
+    let since = roll
+        .usage
+        .changed_at
+        .as_deref()
+        .map(|at| format!(" since {at}"))
+        .unwrap_or_default();
+    match roll.summary() {
+        Some(left) => format!("{}: {used}{since}, {left}", roll.key),
+        None => format!("{}: {used}{since} (set roll_length_m to predict what's left)", roll.key),
+    }
+}
+
+/// GET /admin/paper — paper used on the current printer's roll.
+async fn paper_usage(State(state): State<UploadState>) -> (StatusCode, String) {
+    match state.status.lock().ok().and_then(|r| r.roll.clone()) {
+        Some(roll) => (StatusCode::OK, roll_summary(&roll)),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No printer".to_string()),
+    }
+}
+
+/// POST /admin/paper/reset — a new roll was loaded: start counting again.
+async fn reset_paper(State(state): State<UploadState>) -> (StatusCode, String) {
+    let Ok(mut report) = state.status.lock() else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Status unavailable".to_string());
+    };
+    let Some(roll) = report.roll.as_mut() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "No printer".to_string());
+    };
+    roll.usage = usage::reset(&roll.key);
+    tracing::info!("Paper counter reset for {}", roll.key);
+    (StatusCode::OK, format!("New roll: {}", roll_summary(roll)))
+}
+
+/// Queue a logo operation behind any pending prints and wait for its result.
+async fn run_logo_op(state: &UploadState, op: LogoOp) -> (StatusCode, String) {
+    let (reply, mut result) = mpsc::channel(1);
+    if let Err(e) = submit(state, PrintPayload::Logo(LogoRequest { op, reply })).await {
+        return e;
+    }
+    match result.recv().await {
+        Some(Ok(message)) => (StatusCode::OK, message),
+        Some(Err(e)) => (StatusCode::BAD_GATEWAY, e),
+        None => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            "Print queue closed".to_string(),
-        );
+            "Logo request dropped".to_string(),
+        ),
     }
-    (StatusCode::OK, "Queued for printing".to_string())
+}
+
+/// GET /admin/logos — key codes of the logos stored in the printer, one per line.
+async fn list_logos(State(state): State<UploadState>) -> impl IntoResponse {
+    run_logo_op(&state, LogoOp::List).await
+}
+
+/// POST /admin/logos/{key} — store the multipart "image" field as NV logo `key`.
+/// Print it from markdown with `![logo](nv:KEY)`.
+async fn store_logo(
+    State(state): State<UploadState>,
+    Path(key): Path<String>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    if let Err(e) = logo::key_bytes(&key) {
+        return (StatusCode::BAD_REQUEST, e);
+    }
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() != Some("image") {
+            continue;
+        }
+        let image = match field.bytes().await {
+            Ok(b) => b.to_vec(),
+            Err(e) => return (StatusCode::BAD_REQUEST, format!("Read error: {e}")),
+        };
+        if image.is_empty() {
+            return (StatusCode::BAD_REQUEST, "Empty file".to_string());
+        }
+        tracing::info!("Logo {key} received: {} bytes", image.len());
+        return run_logo_op(&state, LogoOp::Store { key, image }).await;
+    }
+    (StatusCode::BAD_REQUEST, "No 'image' field found".to_string())
+}
+
+/// DELETE /admin/logos/{key} — remove NV logo `key`.
+async fn delete_logo(
+    State(state): State<UploadState>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = logo::key_bytes(&key) {
+        return (StatusCode::BAD_REQUEST, e);
+    }
+    run_logo_op(&state, LogoOp::Delete { key }).await
 }
 
 /// Filter text based on the source program's log format.
@@ -159,12 +517,19 @@ fn filter_elixir_errors(text: &str) -> String {
 
 /// POST /print/strip — accept multipart image and print WITHOUT cutting.
 /// Used by the photo booth to print a strip of photos.
-/// Optional query param: ?feed=N (default 3, lines of feed after image)
+/// Optional query params: ?feed=N (default 3, lines of feed after image),
+/// ?session=ID (keeps a strip's frames on one printer),
+/// ?density=-6..6&speed=1..13&dither=NAME (defaults from `[endpoint.strip]`)
 async fn upload_strip(
     State(state): State<UploadState>,
     Query(params): Query<StripParams>,
+    Query(option_params): Query<OptionParams>,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
+    let options = match job_options("strip", option_params.into()) {
+        Ok(options) => options,
+        Err(e) => return e,
+    };
     let feed = params.feed.unwrap_or(3);
     let bright = params.bright.unwrap_or(0) > 0;
     while let Ok(Some(field)) = multipart.next_field().await {
@@ -176,22 +541,17 @@ async fn upload_strip(
                     return (StatusCode::BAD_REQUEST, format!("Read error: {e}"));
                 }
             };
-            if bytes.is_empty() {
-                return (StatusCode::BAD_REQUEST, "Empty file".to_string());
+            if let Err(e) = check_image(&bytes) {
+                return e;
             }
             tracing::info!("Strip photo received: {} bytes (bright={})", bytes.len(), bright);
-            if state
-                .tx
-                .send(PrintPayload::ImageNoCut(bytes, feed, bright))
-                .await
-                .is_err()
-            {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Print queue closed".to_string(),
-                );
-            }
-            return (StatusCode::OK, "Queued (no cut)".to_string());
+            let session = params.session.clone();
+            let payload = PrintPayload::ImageNoCut(bytes, feed, bright, session, options);
+            let paper = match submit(&state, payload).await {
+                Ok(paper) => paper,
+                Err(e) => return e,
+            };
+            return (StatusCode::OK, format!("Queued (no cut, {paper})"));
         }
     }
     (StatusCode::BAD_REQUEST, "No 'image' field found".to_string())
@@ -338,18 +698,32 @@ async fn generate_204() -> StatusCode {
     StatusCode::NO_CONTENT
 }
 
-pub fn build_router(tx: mpsc::Sender<PrintPayload>) -> Router {
-    let state = UploadState { tx };
+pub fn build_router(
+    tx: mpsc::Sender<Submission>,
+    status: SharedStatus,
+    printers: SharedStatuses,
+) -> Router {
+    let state = UploadState {
+        tx,
+        status,
+        printers,
+    };
     Router::new()
         .route("/", get(index))
         .route("/print/upload", post(upload))
         .route("/print/strip", post(upload_strip))
         .route("/print/text", post(print_text))
+        .route("/print/commands", post(print_commands))
+        .route("/status", get(printer_status))
         .route("/booth/preview", post(booth_preview))
         .route("/booth/shoot", post(booth_shoot))
         .route("/booth", get(booth_page))
         .route("/admin", get(admin_page))
         .route("/admin/run", post(admin_run))
+        .route("/admin/logos", get(list_logos))
+        .route("/admin/logos/{key}", post(store_logo).delete(delete_logo))
+        .route("/admin/paper", get(paper_usage))
+        .route("/admin/paper/reset", post(reset_paper))
         .route("/hotspot-detect.html", get(captive_success))
         .route("/library/test/success.html", get(captive_success))
         .route("/generate_204", get(generate_204))
@@ -643,6 +1017,25 @@ h1{font-size:20px;margin-bottom:4px;color:#fff}
 </div>
 </div>
 
+<div class="section">
+<h2>Logos</h2>
+<div class="grid">
+<input id="logo-key" class="btn" maxlength="2" placeholder="Key (e.g. LG)">
+<input id="logo-file" class="btn" type="file" accept="image/*">
+<button class="btn" onclick="logo('GET')">List</button>
+<button class="btn btn-success" onclick="logo('POST')">Store</button>
+<button class="btn btn-danger btn-wide" onclick="logo('DELETE')">Delete</button>
+</div>
+</div>
+
+<div class="section">
+<h2>Paper</h2>
+<div class="grid">
+<button class="btn" onclick="paper('GET','/admin/paper')">Roll Usage</button>
+<button class="btn btn-success" onclick="if(confirm('Reset the counter for a new roll?'))paper('POST','/admin/paper/reset')">New Roll</button>
+</div>
+</div>
+
 <div class="section">
 <h2>Display</h2>
 <div class="grid">
@@ -668,6 +1061,35 @@ async function run(cmd){
   }
   document.querySelectorAll('.btn').forEach(b=>b.disabled=false);
 }
+async function paper(method,url){
+  out.style.display='block';
+  out.textContent='Working...';
+  try{
+    const r=await fetch(url,{method});
+    out.textContent=await r.text();
+  }catch(e){
+    out.textContent='Connection failed: '+e;
+  }
+}
+async function logo(method){
+  const key=document.getElementById('logo-key').value;
+  let url='/admin/logos',body;
+  if(method!=='GET'){
+    url+='/'+encodeURIComponent(key);
+    if(method==='POST'){
+      body=new FormData();
+      body.append('image',document.getElementById('logo-file').files[0]);
+    }
+  }
+  out.style.display='block';
+  out.textContent='Working...';
+  try{
+    const r=await fetch(url,{method,body});
+    out.textContent=await r.text();
+  }catch(e){
+    out.textContent='Connection failed: '+e;
+  }
+}
 </script>
 </body>
 </html>"#;
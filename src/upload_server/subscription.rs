@@ -1,22 +1,35 @@
-use super::handler::{self, PrintPayload};
+use super::handler::{self, LogoRequest, PrintPayload, Submission};
+use crate::printer::encoder::PrintOptions;
+use crate::printer::status::{new_shared_statuses, SharedStatus};
 
 #[derive(Debug, Clone)]
 pub enum UploadEvent {
     Started(String),
-    PhotoReceived(Vec<u8>),
+    PhotoReceived(Vec<u8>, PrintOptions),
     /// Photo that should print without cutting (for photo strip sequences).
-    /// Fields: image bytes, feed lines, indoor brightness boost.
-    StripPhotoReceived(Vec<u8>, u8, bool),
-    TextReceived { text: String, source: String },
+    /// Fields: image bytes, feed lines, indoor brightness boost, strip
+    /// session, options.
+    StripPhotoReceived(Vec<u8>, u8, bool, Option<String>, PrintOptions),
+    TextReceived {
+        text: String,
+        source: String,
+        options: PrintOptions,
+    },
+    CommandsReceived(Vec<crate::printer::rich_print::PrintCommand>, PrintOptions),
+    LogoRequested(LogoRequest),
     Error(String),
 }
 
-pub fn upload_server(bind_addr: String) -> impl futures::Stream<Item = UploadEvent> {
+pub fn upload_server(
+    bind_addr: String,
+    status: SharedStatus,
+) -> impl futures::Stream<Item = UploadEvent> {
     iced::stream::channel(10, |mut output| async move {
         use futures::SinkExt;
 
-        let (tx, mut rx) = tokio::sync::mpsc::channel::<PrintPayload>(16);
-        let router = handler::build_router(tx);
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<Submission>(16);
+        // The GUI shares only the selected printer's status
+        let router = handler::build_router(tx, status.clone(), new_shared_statuses());
 
         let listener = match tokio::net::TcpListener::bind(&bind_addr).await {
             Ok(l) => l,
@@ -37,13 +50,33 @@ pub fn upload_server(bind_addr: String) -> impl futures::Stream<Item = UploadEve
             }
         });
 
-        while let Some(payload) = rx.recv().await {
+        while let Some(Submission { payload, reply }) = rx.recv().await {
+            // Paper is checked against the selected printer
+            let geometry = status.lock().ok().and_then(|r| r.geometry).unwrap_or_default();
+            let checked = handler::paper_check(&payload, geometry);
+            let accepted = checked.is_ok();
+            let _ = reply.send(checked);
+            if !accepted {
+                continue;
+            }
             let event = match payload {
-                PrintPayload::Image(bytes) => UploadEvent::PhotoReceived(bytes),
-                PrintPayload::ImageNoCut(bytes, feed, bright) => UploadEvent::StripPhotoReceived(bytes, feed, bright),
-                PrintPayload::Text { text, source } => {
-                    UploadEvent::TextReceived { text, source }
+                PrintPayload::Image(bytes, options) => UploadEvent::PhotoReceived(bytes, options),
+                PrintPayload::ImageNoCut(bytes, feed, bright, session, options) => {
+                    UploadEvent::StripPhotoReceived(bytes, feed, bright, session, options)
+                }
+                PrintPayload::Text {
+                    text,
+                    source,
+                    options,
+                } => UploadEvent::TextReceived {
+                    text,
+                    source,
+                    options,
+                },
+                PrintPayload::Commands(commands, options) => {
+                    UploadEvent::CommandsReceived(commands, options)
                 }
+                PrintPayload::Logo(request) => UploadEvent::LogoRequested(request),
             };
             if output.send(event).await.is_err() {
                 break;
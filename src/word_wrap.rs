@@ -46,6 +46,15 @@ pub fn wrap_document(blocks: &[ReceiptBlock], max_chars: u8) -> Vec<WrappedLine>
                     alignment: Alignment::Left,
                 });
             }
+            // Printer actions take no space on the paper
+            ReceiptBlock::Command(_) => {}
+            // Text previews can't place regions side by side: show each
+            // region's lines in turn, wrapped to the region
+            ReceiptBlock::Page(page) => {
+                for region in &page.regions {
+                    lines.extend(wrap_document(&region.content, region.chars_per_line()));
+                }
+            }
         }
     }
 
@@ -270,6 +279,20 @@ mod tests {
         assert!(line.spans.last().unwrap().format.bold);
     }
 
+    #[test]
+    fn narrow_paper_layout() {
+        // 58mm roll: 32 columns, 16 for headings
+        let blocks = parse_receipt_markdown(
+            "# RIVERSIDE CAFE\n\nCroissant with butter | $4.50\n\n---",
+        );
+        let lines = wrap_document(&blocks, 32);
+
+        assert_eq!(lines[0].spans[0].text, "RIVERSIDE CAFE");
+        let columns = lines.iter().find(|l| l.spans.len() > 1).unwrap();
+        assert_eq!(line_char_count(&columns.spans), 32);
+        assert_eq!(lines.last().unwrap().spans[0].text, "-".repeat(32));
+    }
+
     #[test]
     fn real_receipt_wrap() {
         let input = "\